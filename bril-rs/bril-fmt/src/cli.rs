@@ -0,0 +1,18 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+#[derive(Parser)]
+#[clap(about, version, author)] // keeps the cli synced with Cargo.toml
+pub struct Cli {
+    /// The Bril text files to format. Reads from stdin if there are none
+    pub files: Vec<PathBuf>,
+
+    /// Don't print anything and instead exit with 1 if any input is not already formatted
+    #[clap(long)]
+    pub check: bool,
+
+    /// Rewrite the files in place instead of printing them
+    #[clap(short, long, conflicts_with = "check")]
+    pub write: bool,
+}
@@ -0,0 +1,185 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+// clap and serde pull in different versions of their proc-macro dependencies
+#![allow(clippy::multiple_crate_versions)]
+
+#[doc(hidden)]
+pub mod cli;
+/// Provides a parse tree of Bril text which keeps its comments and blank lines
+pub mod tree;
+
+use bril2json::ParseError;
+use tree::{Function, Item, Line, Program, Token};
+
+const INDENT: &str = "  ";
+
+/// Formats the Bril text in ```text```
+/// # Errors
+/// When ```text``` is not well-formed Bril text, since there would be no way to know what it is meant to look like
+pub fn format(text: &str) -> Result<String, ParseError> {
+    bril2json::parse_abstract_program_from_str(text, false)?;
+    Ok(print(&tree::parse(text)))
+}
+
+/// Prints ```program``` in the canonical layout
+#[must_use]
+pub fn print(program: &Program) -> String {
+    let mut out = String::new();
+    // Whether there should be an empty line before the next item
+    let mut blank = false;
+    for item in &program.items {
+        match item {
+            Item::Blank => blank = true,
+            Item::Comment(c) => {
+                if blank && !out.is_empty() {
+                    out.push('\n');
+                }
+                blank = false;
+                out.push_str(c.trim_end());
+                out.push('\n');
+            }
+            Item::Import { tokens, comment } => {
+                if blank && !out.is_empty() {
+                    out.push('\n');
+                }
+                blank = false;
+                out.push_str(&join(tokens));
+                if let Some(c) = comment {
+                    out.push(' ');
+                    out.push_str(c.trim_end());
+                }
+                out.push('\n');
+            }
+            Item::Function(f) => {
+                if blank && !out.is_empty() {
+                    out.push('\n');
+                }
+                print_function(f, &mut out);
+                // Functions are always separated by an empty line
+                blank = true;
+            }
+        }
+    }
+    out
+}
+
+fn print_function(f: &Function, out: &mut String) {
+    for c in &f.leading {
+        out.push_str(c.trim_end());
+        out.push('\n');
+    }
+    out.push_str(f.name);
+    if !f.args.is_empty() {
+        let args: Vec<String> = f.args.iter().map(|(a, t)| format!("{a}: {t}")).collect();
+        out.push('(');
+        out.push_str(&args.join(", "));
+        out.push(')');
+    }
+    if let Some(t) = &f.return_type {
+        out.push_str(": ");
+        out.push_str(t);
+    }
+    out.push_str(" {");
+    if let Some(c) = f.comment {
+        out.push(' ');
+        out.push_str(c.trim_end());
+    }
+    out.push('\n');
+
+    // Blank lines are dropped from either end and collapsed everywhere else
+    let mut body: Vec<&Line> = Vec::new();
+    for line in &f.body {
+        if *line == Line::Blank && matches!(body.last(), None | Some(Line::Blank)) {
+            continue;
+        }
+        body.push(line);
+    }
+    if body.last() == Some(&&Line::Blank) {
+        body.pop();
+    }
+
+    let code: Vec<Option<(String, Option<&str>)>> = body.iter().map(|l| code(l)).collect();
+    for (i, line) in body.iter().enumerate() {
+        match (line, &code[i]) {
+            (Line::Blank, _) => out.push('\n'),
+            (Line::Comment(c), _) => {
+                // A comment is indented like the code that comes after it
+                let next = body[i..]
+                    .iter()
+                    .find(|l| !matches!(l, Line::Comment(_) | Line::Blank));
+                if !matches!(next, Some(Line::Label { .. })) {
+                    out.push_str(INDENT);
+                }
+                out.push_str(c.trim_end());
+                out.push('\n');
+            }
+            (_, Some((text, comment))) => {
+                out.push_str(text);
+                if let Some(c) = comment {
+                    out.push_str(&" ".repeat(comment_column(&code, i) - text.chars().count()));
+                    out.push_str(c.trim_end());
+                }
+                out.push('\n');
+            }
+            (_, None) => {}
+        }
+    }
+    out.push('}');
+    if let Some(c) = f.end_comment {
+        out.push(' ');
+        out.push_str(c.trim_end());
+    }
+    out.push('\n');
+}
+
+// The text of a label or instruction along with the comment after it
+fn code<'a>(line: &Line<'a>) -> Option<(String, Option<&'a str>)> {
+    match line {
+        Line::Label { name, comment } => Some((format!("{name}:"), *comment)),
+        Line::Instruction { tokens, comment } => {
+            Some((format!("{INDENT}{}", join(tokens)), *comment))
+        }
+        Line::Comment(_) | Line::Blank => None,
+    }
+}
+
+// Where the comment on line ```i``` starts, so that it lines up with the comments on the lines of code around it
+fn comment_column(code: &[Option<(String, Option<&str>)>], i: usize) -> usize {
+    let commented = |l: &Option<(String, Option<&str>)>| matches!(l, Some((_, Some(_))));
+    let start = code[..i]
+        .iter()
+        .rposition(|l| !commented(l))
+        .map_or(0, |s| s + 1);
+    let end = code[i..]
+        .iter()
+        .position(|l| !commented(l))
+        .map_or(code.len(), |e| i + e);
+    code[start..end]
+        .iter()
+        .flatten()
+        .map(|(text, _)| text.chars().count())
+        .max()
+        .unwrap_or_default()
+        + 1
+}
+
+// Puts single spaces between the tokens of an instruction except around punctuation that sticks to its neighbours, like `x: ptr<int>;`
+fn join(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    let mut prev = None;
+    for t in tokens {
+        let sticks = matches!(t, Token::Punct(':' | ';' | ',' | '(' | ')' | '<' | '>'))
+            || matches!(prev, None | Some(Token::Punct('(' | '<')));
+        if !sticks {
+            out.push(' ');
+        }
+        match t {
+            Token::Word(w) | Token::Comment(w) => out.push_str(w),
+            Token::Punct(c) => out.push(*c),
+            Token::Newline => {}
+        }
+        prev = Some(*t);
+    }
+    out
+}
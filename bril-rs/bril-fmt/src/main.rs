@@ -0,0 +1,53 @@
+use std::io::Read;
+
+use bril_fmt::cli::Cli;
+use clap::Parser;
+
+fn main() {
+    let args = Cli::parse();
+    let inputs: Vec<(String, String)> = if args.files.is_empty() {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text).unwrap();
+        vec![("<stdin>".to_string(), text)]
+    } else {
+        args.files
+            .iter()
+            .map(|path| {
+                let text = std::fs::read_to_string(path).unwrap_or_else(|e| {
+                    eprintln!("error: {}: {e}", path.display());
+                    std::process::exit(2)
+                });
+                (path.display().to_string(), text)
+            })
+            .collect()
+    };
+
+    // There is nowhere to write stdin back to so it is printed instead
+    let write = args.write && !args.files.is_empty();
+    let mut unformatted = false;
+    for (name, text) in inputs {
+        let formatted = bril_fmt::format(&text).unwrap_or_else(|e| {
+            let (line, col) = e.line_col(&text);
+            eprintln!("error: {name}:{line}:{col}: {e}");
+            std::process::exit(2)
+        });
+        if args.check {
+            if formatted != text {
+                eprintln!("{name} is not formatted");
+                unformatted = true;
+            }
+        } else if write {
+            if formatted != text {
+                std::fs::write(&name, formatted).unwrap_or_else(|e| {
+                    eprintln!("error: {name}: {e}");
+                    std::process::exit(2)
+                });
+            }
+        } else {
+            print!("{formatted}");
+        }
+    }
+    if unformatted {
+        std::process::exit(1)
+    }
+}
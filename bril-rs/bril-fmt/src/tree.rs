@@ -0,0 +1,322 @@
+/// A piece of Bril text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token<'a> {
+    /// A variable, type, op, literal, function, label or quoted path. Functions and labels keep their `@` or `.`
+    Word(&'a str),
+    /// One of `<>{}()=;:,`
+    Punct(char),
+    /// A comment from its `#` up to the end of the line
+    Comment(&'a str),
+    /// The end of a line
+    Newline,
+}
+
+impl Token<'_> {
+    const fn is_trivia(self) -> bool {
+        matches!(self, Token::Comment(_) | Token::Newline)
+    }
+}
+
+const PUNCTUATION: &str = "<>{}()=;:,";
+
+/// Splits ```text``` into [`Token`]s, keeping its comments and line breaks
+#[must_use]
+pub fn lex(text: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let len = if c == '\n' {
+            tokens.push(Token::Newline);
+            1
+        } else if c.is_whitespace() {
+            c.len_utf8()
+        } else if c == '#' {
+            let len = rest.find(['\n', '\r']).unwrap_or(rest.len());
+            tokens.push(Token::Comment(&rest[..len]));
+            len
+        } else if c == '"' {
+            // The path of an import, which may have spaces or a `#` in it
+            let len = rest[1..].find('"').map_or(rest.len(), |l| l + 2);
+            tokens.push(Token::Word(&rest[..len]));
+            len
+        } else if PUNCTUATION.contains(c) {
+            tokens.push(Token::Punct(c));
+            1
+        } else {
+            let len = rest
+                .find(|c: char| c.is_whitespace() || c == '#' || PUNCTUATION.contains(c))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Word(&rest[..len]));
+            len
+        };
+        rest = &rest[len..];
+    }
+    tokens
+}
+
+/// A Bril program along with its comments and blank lines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program<'a> {
+    /// Everything at the top level, in order
+    pub items: Vec<Item<'a>>,
+}
+
+/// Something at the top level of a [`Program`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item<'a> {
+    /// A comment on a line of its own
+    Comment(&'a str),
+    /// An empty line
+    Blank,
+    /// The tokens of an import up to and including its `;`, and the comment after it
+    Import {
+        /// The tokens of the import, without any trivia
+        tokens: Vec<Token<'a>>,
+        /// The comment at the end of the line
+        comment: Option<&'a str>,
+    },
+    /// A function definition
+    Function(Function<'a>),
+}
+
+/// A function definition along with the comments in it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function<'a> {
+    /// The name, including its `@`
+    pub name: &'a str,
+    /// The name and type of each argument
+    pub args: Vec<(&'a str, String)>,
+    /// The return type, if there is one
+    pub return_type: Option<String>,
+    /// Comments from in between the parts of the signature
+    pub leading: Vec<&'a str>,
+    /// The comment after the `{`
+    pub comment: Option<&'a str>,
+    /// Everything in between the braces, in order
+    pub body: Vec<Line<'a>>,
+    /// The comment after the `}`
+    pub end_comment: Option<&'a str>,
+}
+
+/// Something inside of a [`Function`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Line<'a> {
+    /// A comment on a line of its own
+    Comment(&'a str),
+    /// An empty line
+    Blank,
+    /// A label, including its `.`, and the comment after it
+    Label {
+        /// The name of the label
+        name: &'a str,
+        /// The comment at the end of the line
+        comment: Option<&'a str>,
+    },
+    /// The tokens of an instruction up to and including its `;`, and the comment after it
+    Instruction {
+        /// The tokens of the instruction, without any trivia
+        tokens: Vec<Token<'a>>,
+        /// The comment at the end of the line
+        comment: Option<&'a str>,
+    },
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    i: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.i).copied()
+    }
+
+    // The next token that isn't trivia, without moving past anything
+    fn peek_code(&self) -> Option<Token<'a>> {
+        self.tokens[self.i..]
+            .iter()
+            .copied()
+            .find(|t| !t.is_trivia())
+    }
+
+    // Moves past the next token that isn't trivia, keeping any comments on the way in ```comments```
+    fn next_code(&mut self, comments: &mut Vec<&'a str>) -> Option<Token<'a>> {
+        while let Some(t) = self.peek() {
+            self.i += 1;
+            match t {
+                Token::Comment(c) => comments.push(c),
+                Token::Newline => {}
+                _ => return Some(t),
+            }
+        }
+        None
+    }
+
+    // Moves past a line break and returns whether the line it ends is empty
+    fn newline(&mut self) -> bool {
+        self.i += 1;
+        self.i == 1 || self.tokens[self.i - 2] == Token::Newline
+    }
+
+    fn typ(&mut self, comments: &mut Vec<&'a str>) -> String {
+        let Some(Token::Word(name)) = self.next_code(comments) else {
+            return String::new();
+        };
+        if self.peek_code() == Some(Token::Punct('<')) {
+            self.next_code(comments);
+            let param = self.typ(comments);
+            self.next_code(comments);
+            format!("{name}<{param}>")
+        } else {
+            name.to_string()
+        }
+    }
+
+    fn function(&mut self) -> Function<'a> {
+        let mut leading = Vec::new();
+        let name = match self.next_code(&mut leading) {
+            Some(Token::Word(name)) => name,
+            _ => "",
+        };
+        let mut args = Vec::new();
+        let mut return_type = None;
+        loop {
+            match self.next_code(&mut leading) {
+                Some(Token::Punct('(')) => loop {
+                    match self.next_code(&mut leading) {
+                        Some(Token::Word(arg)) => {
+                            // The `:`
+                            self.next_code(&mut leading);
+                            args.push((arg, self.typ(&mut leading)));
+                        }
+                        Some(Token::Punct(',')) => {}
+                        _ => break,
+                    }
+                },
+                Some(Token::Punct(':')) => return_type = Some(self.typ(&mut leading)),
+                Some(Token::Punct('{')) | None => break,
+                Some(_) => {}
+            }
+        }
+        let comment = self.comment();
+
+        let mut body = Vec::new();
+        // Whether a label or instruction has been seen since the last line break
+        let mut code_on_line = false;
+        let mut end_comment = None;
+        while let Some(t) = self.peek() {
+            match t {
+                Token::Newline => {
+                    if self.newline() {
+                        body.push(Line::Blank);
+                    }
+                    code_on_line = false;
+                }
+                Token::Comment(c) => {
+                    self.i += 1;
+                    match body.last_mut() {
+                        Some(Line::Label { comment, .. } | Line::Instruction { comment, .. })
+                            if code_on_line =>
+                        {
+                            *comment = Some(c);
+                        }
+                        _ => body.push(Line::Comment(c)),
+                    }
+                }
+                Token::Punct('}') => {
+                    self.i += 1;
+                    end_comment = self.comment();
+                    break;
+                }
+                Token::Word(name)
+                    if name.starts_with('.')
+                        && self.tokens.get(self.i + 1) == Some(&Token::Punct(':')) =>
+                {
+                    self.i += 2;
+                    body.push(Line::Label {
+                        name,
+                        comment: None,
+                    });
+                    code_on_line = true;
+                }
+                _ => {
+                    let (tokens, comments) = self.statement();
+                    // Comments from the middle of an instruction that spans several lines go above it
+                    body.extend(comments.into_iter().map(Line::Comment));
+                    body.push(Line::Instruction {
+                        tokens,
+                        comment: None,
+                    });
+                    code_on_line = true;
+                }
+            }
+        }
+
+        Function {
+            name,
+            args,
+            return_type,
+            leading,
+            comment,
+            body,
+            end_comment,
+        }
+    }
+
+    // Moves past the tokens up to and including the next `;`, along with the comments in between them
+    fn statement(&mut self) -> (Vec<Token<'a>>, Vec<&'a str>) {
+        let mut tokens = Vec::new();
+        let mut comments = Vec::new();
+        while let Some(t) = self.next_code(&mut comments) {
+            tokens.push(t);
+            if t == Token::Punct(';') {
+                break;
+            }
+        }
+        (tokens, comments)
+    }
+
+    // Moves past a comment if it comes next on the same line
+    fn comment(&mut self) -> Option<&'a str> {
+        match self.peek() {
+            Some(Token::Comment(c)) => {
+                self.i += 1;
+                Some(c)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Builds the [`Program`] for ```text```, which should already be known to be well-formed Bril text
+#[must_use]
+pub fn parse(text: &str) -> Program<'_> {
+    let mut parser = Parser {
+        tokens: lex(text),
+        i: 0,
+    };
+    let mut items = Vec::new();
+    while let Some(t) = parser.peek() {
+        match t {
+            Token::Newline => {
+                if parser.newline() {
+                    items.push(Item::Blank);
+                }
+            }
+            Token::Comment(c) => {
+                parser.i += 1;
+                items.push(Item::Comment(c));
+            }
+            Token::Word("from") => {
+                let (tokens, comments) = parser.statement();
+                items.extend(comments.into_iter().map(Item::Comment));
+                items.push(Item::Import {
+                    tokens,
+                    comment: parser.comment(),
+                });
+            }
+            _ => items.push(Item::Function(parser.function())),
+        }
+    }
+    Program { items }
+}
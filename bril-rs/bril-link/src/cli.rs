@@ -0,0 +1,14 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+#[derive(Parser)]
+#[clap(about, version, author)] // keeps the cli synced with Cargo.toml
+pub struct Cli {
+    /// The programs to link, as Bril JSON if they end in `.json` and Bril text otherwise. Reads Bril JSON from stdin if there are none
+    pub files: Vec<PathBuf>,
+
+    /// Flag for whether position information should be included for files read as Bril text
+    #[clap(short)]
+    pub position: bool,
+}
@@ -0,0 +1,8 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+// clap and serde pull in different versions of their proc-macro dependencies
+#![allow(clippy::multiple_crate_versions)]
+
+#[doc(hidden)]
+pub mod cli;
@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+
+use bril2json::load_abstract_program_from_file;
+use bril_link::cli::Cli;
+use bril_rs::link::link;
+use bril_rs::{load_abstract_program, output_abstract_program};
+use clap::Parser;
+
+fn main() {
+    let args = Cli::parse();
+    let programs: Vec<(Option<PathBuf>, _)> = if args.files.is_empty() {
+        vec![(None, load_abstract_program())]
+    } else {
+        args.files
+            .iter()
+            .map(|path| {
+                let program =
+                    load_abstract_program_from_file(path, args.position).unwrap_or_else(|e| {
+                        eprintln!("error: {}: {e}", path.display());
+                        std::process::exit(2)
+                    });
+                (Some(path.clone()), program)
+            })
+            .collect()
+    };
+
+    let program = link(programs, |path| {
+        load_abstract_program_from_file(path, args.position)
+    })
+    .unwrap_or_else(|e| {
+        eprintln!("error: {e}");
+        std::process::exit(2)
+    });
+    output_abstract_program(&program);
+}
@@ -0,0 +1,266 @@
+use std::collections::{HashMap, HashSet};
+
+use bril_rs::graph::dominators;
+use bril_rs::{Code, EffectOps, Function, Instruction, Position};
+
+/// A straight-line sequence of instructions with at most one label at the start and at most one terminator at the end
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicBlock {
+    /// The label that starts this block if there is one
+    pub label: Option<String>,
+    /// The source position of the label if there is one
+    pub label_pos: Option<Position>,
+    /// The instructions of this block
+    pub instrs: Vec<Instruction>,
+}
+
+impl BasicBlock {
+    const fn new() -> Self {
+        Self {
+            label: None,
+            label_pos: None,
+            instrs: Vec::new(),
+        }
+    }
+
+    const fn is_empty(&self) -> bool {
+        self.label.is_none() && self.instrs.is_empty()
+    }
+}
+
+/// Whether this instruction ends a basic block
+#[must_use]
+pub const fn is_terminator(instr: &Instruction) -> bool {
+    matches!(
+        instr,
+        Instruction::Effect {
+            op: EffectOps::Jump | EffectOps::Branch | EffectOps::Return,
+            ..
+        }
+    )
+}
+
+/// Whether this instruction starts, ends, or aborts a speculation. Aborting puts every variable back to what it was at `speculate`, so values can not be carried across these instructions.
+#[must_use]
+pub const fn is_speculation(instr: &Instruction) -> bool {
+    matches!(
+        instr,
+        Instruction::Effect {
+            op: EffectOps::Speculate | EffectOps::Commit | EffectOps::Guard,
+            ..
+        }
+    )
+}
+
+/// Splits the body of a function into [`BasicBlock`]s. A new block is started at every label and after every terminator.
+#[must_use]
+pub fn form_blocks(code: Vec<Code>) -> Vec<BasicBlock> {
+    let mut blocks = Vec::new();
+    let mut curr_block = BasicBlock::new();
+
+    for c in code {
+        match c {
+            Code::Label { label, pos } => {
+                if !curr_block.is_empty() {
+                    blocks.push(curr_block);
+                }
+                curr_block = BasicBlock {
+                    label: Some(label),
+                    label_pos: pos,
+                    instrs: Vec::new(),
+                };
+            }
+            Code::Instruction(instr) => {
+                let terminator = is_terminator(&instr);
+                curr_block.instrs.push(instr);
+                if terminator {
+                    blocks.push(curr_block);
+                    curr_block = BasicBlock::new();
+                }
+            }
+        }
+    }
+
+    if !curr_block.is_empty() {
+        blocks.push(curr_block);
+    }
+    blocks
+}
+
+/// The inverse of [`form_blocks`] which turns [`BasicBlock`]s back into the body of a function
+#[must_use]
+pub fn flatten_blocks(blocks: Vec<BasicBlock>) -> Vec<Code> {
+    let mut code = Vec::new();
+    for block in blocks {
+        if let Some(label) = block.label {
+            code.push(Code::Label {
+                label,
+                pos: block.label_pos,
+            });
+        }
+        code.extend(block.instrs.into_iter().map(Code::Instruction));
+    }
+    code
+}
+
+/// The control flow graph of a function
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    /// The basic blocks of the function in their original order. The first block is the entry.
+    pub blocks: Vec<BasicBlock>,
+    /// The indices of the blocks that each block may jump or fall through to
+    pub successors: Vec<Vec<usize>>,
+    /// The indices of the blocks that may jump or fall through to each block
+    pub predecessors: Vec<Vec<usize>>,
+}
+
+impl Cfg {
+    /// Builds the control flow graph from the body of a function. Jumps to labels which do not exist in the function are ignored.
+    #[must_use]
+    pub fn new(code: Vec<Code>) -> Self {
+        let blocks = form_blocks(code);
+        let label_map: HashMap<&str, usize> = blocks
+            .iter()
+            .enumerate()
+            .filter_map(|(i, b)| b.label.as_deref().map(|l| (l, i)))
+            .collect();
+
+        let mut successors = vec![Vec::new(); blocks.len()];
+        for (i, block) in blocks.iter().enumerate() {
+            match block.instrs.last() {
+                Some(Instruction::Effect {
+                    op: EffectOps::Jump | EffectOps::Branch,
+                    labels,
+                    ..
+                }) => {
+                    for l in labels {
+                        if let Some(idx) = label_map.get(l.as_str()) {
+                            if !successors[i].contains(idx) {
+                                successors[i].push(*idx);
+                            }
+                        }
+                    }
+                }
+                Some(Instruction::Effect {
+                    op: EffectOps::Return,
+                    ..
+                }) => {}
+                _ if i + 1 < blocks.len() => successors[i].push(i + 1),
+                _ => {}
+            }
+            // A failed `guard` jumps to its label from the middle of the block, and nothing is known about operations from other extensions, so any label they name might be jumped to
+            for instr in &block.instrs {
+                if let Instruction::Effect {
+                    op: EffectOps::Guard | EffectOps::Other(_),
+                    labels,
+                    ..
+                } = instr
+                {
+                    for l in labels {
+                        if let Some(idx) = label_map.get(l.as_str()) {
+                            if !successors[i].contains(idx) {
+                                successors[i].push(*idx);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut predecessors = vec![Vec::new(); blocks.len()];
+        for (i, succs) in successors.iter().enumerate() {
+            for s in succs {
+                predecessors[*s].push(i);
+            }
+        }
+
+        Self {
+            blocks,
+            successors,
+            predecessors,
+        }
+    }
+
+    /// The index of the block that starts with ```label```
+    #[must_use]
+    pub fn block_index(&self, label: &str) -> Option<usize> {
+        self.blocks
+            .iter()
+            .position(|b| b.label.as_deref() == Some(label))
+    }
+
+    /// For each block, whether it can be reached from the entry block
+    #[must_use]
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut work_list = Vec::new();
+        if !self.blocks.is_empty() {
+            work_list.push(0);
+        }
+        while let Some(b) = work_list.pop() {
+            if !reachable[b] {
+                reachable[b] = true;
+                work_list.extend(self.successors[b].iter().copied());
+            }
+        }
+        reachable
+    }
+
+    /// For each block `b`, `dominators()[b][d]` is true when every path from the entry block to `b` goes through `d`. See [`bril_rs::graph::dominators`].
+    #[must_use]
+    pub fn dominators(&self) -> Vec<Vec<bool>> {
+        dominators(&self.predecessors)
+    }
+
+    /// Turns the control flow graph back into the body of a function. See [`flatten_blocks`].
+    #[must_use]
+    pub fn into_code(self) -> Vec<Code> {
+        flatten_blocks(self.blocks)
+    }
+}
+
+/// Generates variable names that do not clash with any in the function
+pub struct FreshNames {
+    prefix: &'static str,
+    used: HashSet<String>,
+    counter: usize,
+}
+
+impl FreshNames {
+    /// Avoids every variable in ```func```. Generated names look like `prefix.N`.
+    #[must_use]
+    pub fn new(func: &Function, prefix: &'static str) -> Self {
+        let mut used: HashSet<String> = func.args.iter().map(|a| a.name.clone()).collect();
+        for code in &func.instrs {
+            match code {
+                Code::Instruction(Instruction::Constant { dest, .. }) => {
+                    used.insert(dest.clone());
+                }
+                Code::Instruction(Instruction::Value { dest, args, .. }) => {
+                    used.insert(dest.clone());
+                    used.extend(args.iter().cloned());
+                }
+                Code::Instruction(Instruction::Effect { args, .. }) => {
+                    used.extend(args.iter().cloned());
+                }
+                Code::Label { .. } => {}
+            }
+        }
+        Self {
+            prefix,
+            used,
+            counter: 0,
+        }
+    }
+
+    /// A variable name which has not been used before
+    pub fn fresh(&mut self) -> String {
+        loop {
+            let name = format!("{}.{}", self.prefix, self.counter);
+            self.counter += 1;
+            if self.used.insert(name.clone()) {
+                return name;
+            }
+        }
+    }
+}
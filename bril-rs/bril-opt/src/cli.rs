@@ -0,0 +1,35 @@
+use clap::{ArgEnum, Parser};
+
+#[derive(Parser)]
+#[clap(about, version, author)] // keeps the cli synced with Cargo.toml
+pub struct Cli {
+    /// The optimization passes to run over the program, in order
+    #[clap(arg_enum)]
+    pub passes: Vec<Pass>,
+
+    /// Flag to output statistics about what each pass changed to stderr
+    #[clap(short, long)]
+    pub stats: bool,
+
+    /// The largest function, in instructions, that the inline pass will inline
+    #[clap(long, default_value_t = 32)]
+    pub inline_size: usize,
+
+    /// How many rounds of inlining to perform, which bounds how far recursive calls are unrolled
+    #[clap(long, default_value_t = 2)]
+    pub inline_depth: usize,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug)]
+pub enum Pass {
+    /// Local value numbering
+    Lvn,
+    /// Local and global dead code elimination
+    Dce,
+    /// Loop-invariant code motion
+    Licm,
+    /// Sparse conditional constant propagation, for programs in SSA form
+    Sccp,
+    /// Function inlining
+    Inline,
+}
@@ -0,0 +1,353 @@
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+use std::ops::AddAssign;
+
+use bril_rs::{EffectOps, Function, Instruction, Program, ValueOps};
+
+use crate::cfg::{flatten_blocks, form_blocks, is_speculation, BasicBlock, Cfg};
+
+/// How many instructions were removed by dead code elimination, grouped by the reason they were removed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DceStats {
+    /// Definitions that were overwritten later in their block before being used
+    pub killed: usize,
+    /// Definitions that are never used on any path through the function
+    pub dead: usize,
+    /// Instructions in blocks that can never be reached from the entry of the function
+    pub unreachable: usize,
+    /// Jumps to the block that immediately follows
+    pub jumps: usize,
+}
+
+impl DceStats {
+    /// The total number of instructions that were removed
+    #[must_use]
+    pub const fn total(&self) -> usize {
+        self.killed + self.dead + self.unreachable + self.jumps
+    }
+}
+
+impl AddAssign for DceStats {
+    fn add_assign(&mut self, other: Self) {
+        self.killed += other.killed;
+        self.dead += other.dead;
+        self.unreachable += other.unreachable;
+        self.jumps += other.jumps;
+    }
+}
+
+impl Display for DceStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "removed {} instructions ({} killed, {} dead, {} unreachable, {} jumps)",
+            self.total(),
+            self.killed,
+            self.dead,
+            self.unreachable,
+            self.jumps
+        )
+    }
+}
+
+// Only value instructions without side effects are candidates for removal. Calls may have side effects, allocations leak if never freed, loads and integer division can fail at runtime, and nothing is known about operations from other extensions.
+pub(crate) const fn is_removable(instr: &Instruction) -> bool {
+    match instr {
+        Instruction::Constant { .. } => true,
+        Instruction::Value { op, .. } => !matches!(
+            op,
+            ValueOps::Call | ValueOps::Alloc | ValueOps::Load | ValueOps::Div | ValueOps::Other(_)
+        ),
+        Instruction::Effect { .. } => false,
+    }
+}
+
+pub(crate) const fn get_dest(instr: &Instruction) -> Option<&String> {
+    match instr {
+        Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } => Some(dest),
+        Instruction::Effect { .. } => None,
+    }
+}
+
+pub(crate) fn get_args(instr: &Instruction) -> &[String] {
+    match instr {
+        Instruction::Constant { .. } => &[],
+        Instruction::Value { args, .. } | Instruction::Effect { args, .. } => args,
+    }
+}
+
+/// Removes definitions that are overwritten before they are used in the same block. Returns the number of instructions that were removed.
+fn drop_killed_block(block: &mut BasicBlock) -> usize {
+    // The index of the last definition of each variable that has not been used since
+    let mut last_def: Vec<(&str, usize)> = Vec::new();
+    let mut to_drop = HashSet::new();
+    for (i, instr) in block.instrs.iter().enumerate() {
+        // A definition from before a speculation is put back if it aborts, so writing the variable again inside of it does not kill the definition
+        if is_speculation(instr) {
+            last_def.clear();
+        }
+        // This has to happen before looking at the destination so that `a = add a b` does not count as killing `a`
+        last_def.retain(|(var, _)| !get_args(instr).iter().any(|a| a == var));
+        if let Some(dest) = get_dest(instr) {
+            if let Some(pos) = last_def.iter().position(|(var, _)| *var == dest) {
+                let (_, idx) = last_def.swap_remove(pos);
+                if is_removable(&block.instrs[idx]) {
+                    to_drop.insert(idx);
+                }
+            }
+            last_def.push((dest, i));
+        }
+    }
+
+    let num_dropped = to_drop.len();
+    let mut idx = 0;
+    block.instrs.retain(|_| {
+        idx += 1;
+        !to_drop.contains(&(idx - 1))
+    });
+    num_dropped
+}
+
+/// Removes, within each basic block of ```func```, definitions that are overwritten before they are used
+pub fn local_dce_function(func: &mut Function) -> DceStats {
+    let mut blocks = form_blocks(std::mem::take(&mut func.instrs));
+    let killed = blocks.iter_mut().map(drop_killed_block).sum();
+    func.instrs = flatten_blocks(blocks);
+    DceStats {
+        killed,
+        ..DceStats::default()
+    }
+}
+
+/// Removes the blocks of the function which can not be reached. Phi nodes are updated to no longer refer to removed blocks.
+fn remove_unreachable(func: &mut Function) -> usize {
+    let cfg = Cfg::new(std::mem::take(&mut func.instrs));
+    let reachable = cfg.reachable();
+
+    let mut removed_labels = HashSet::new();
+    let mut removed = 0;
+    let mut blocks = Vec::new();
+    for (block, reachable) in cfg.blocks.into_iter().zip(reachable) {
+        if reachable {
+            blocks.push(block);
+        } else {
+            removed += block.instrs.len();
+            removed_labels.extend(block.label);
+        }
+    }
+
+    if !removed_labels.is_empty() {
+        for instr in blocks.iter_mut().flat_map(|b| b.instrs.iter_mut()) {
+            if let Instruction::Value {
+                op: ValueOps::Phi,
+                args,
+                labels,
+                ..
+            } = instr
+            {
+                let (new_args, new_labels) = args
+                    .iter()
+                    .zip(labels.iter())
+                    .filter(|(_, l)| !removed_labels.contains(*l))
+                    .map(|(a, l)| (a.clone(), l.clone()))
+                    .unzip();
+                *args = new_args;
+                *labels = new_labels;
+            }
+        }
+    }
+
+    func.instrs = flatten_blocks(blocks);
+    removed
+}
+
+/// Removes jumps whose target is the block immediately after
+fn remove_jumps_to_next(func: &mut Function) -> usize {
+    let mut blocks = form_blocks(std::mem::take(&mut func.instrs));
+    let mut removed = 0;
+    for i in 1..blocks.len() {
+        let next_label = blocks[i].label.clone();
+        let block = &mut blocks[i - 1];
+        if let Some(Instruction::Effect {
+            op: EffectOps::Jump,
+            labels,
+            ..
+        }) = block.instrs.last()
+        {
+            if next_label.is_some() && labels.first() == next_label.as_ref() {
+                block.instrs.pop();
+                removed += 1;
+            }
+        }
+    }
+    func.instrs = flatten_blocks(blocks);
+    removed
+}
+
+/// Computes the set of variables which are live on exit from each block
+pub(crate) fn live_out(cfg: &Cfg) -> Vec<HashSet<String>> {
+    liveness(cfg).0
+}
+
+// The blocks that a failed `guard` may jump to
+fn guard_targets(cfg: &Cfg) -> Vec<usize> {
+    cfg.blocks
+        .iter()
+        .flat_map(|b| &b.instrs)
+        .filter_map(|instr| match instr {
+            Instruction::Effect {
+                op: EffectOps::Guard,
+                labels,
+                ..
+            } => labels.first().and_then(|l| cfg.block_index(l)),
+            _ => None,
+        })
+        .collect()
+}
+
+const fn is_speculate(instr: &Instruction) -> bool {
+    matches!(
+        instr,
+        Instruction::Effect {
+            op: EffectOps::Speculate,
+            ..
+        }
+    )
+}
+
+// The variables live on exit from each block, along with those that have to be live at every `speculate`. Aborting a speculation puts the variables back to what they were at `speculate` before jumping to the label of the `guard`, so anything live there has to be kept alive from `speculate` on.
+fn liveness(cfg: &Cfg) -> (Vec<HashSet<String>>, HashSet<String>) {
+    // Variables used before being defined, and variables defined, in each block
+    let (uses, defs): (Vec<HashSet<&str>>, Vec<HashSet<&str>>) = cfg
+        .blocks
+        .iter()
+        .map(|b| {
+            let mut uses = HashSet::new();
+            let mut defs = HashSet::new();
+            for instr in &b.instrs {
+                uses.extend(
+                    get_args(instr)
+                        .iter()
+                        .map(String::as_str)
+                        .filter(|a| !defs.contains(a)),
+                );
+                defs.extend(get_dest(instr).map(String::as_str));
+            }
+            (uses, defs)
+        })
+        .unzip();
+
+    let targets = guard_targets(cfg);
+    let has_speculate: Vec<bool> = cfg
+        .blocks
+        .iter()
+        .map(|b| b.instrs.iter().any(is_speculate))
+        .collect();
+
+    let mut live_in: Vec<HashSet<&str>> = vec![HashSet::new(); cfg.blocks.len()];
+    let mut live_out: Vec<HashSet<&str>> = vec![HashSet::new(); cfg.blocks.len()];
+    let mut speculated: HashSet<&str> = HashSet::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        speculated = targets
+            .iter()
+            .flat_map(|t| live_in[*t].iter().copied())
+            .collect();
+        for b in (0..cfg.blocks.len()).rev() {
+            let out: HashSet<&str> = cfg.successors[b]
+                .iter()
+                .flat_map(|s| live_in[*s].iter().copied())
+                .collect();
+            let mut new_in: HashSet<&str> = out.difference(&defs[b]).copied().collect();
+            new_in.extend(uses[b].iter().copied());
+            // This can be more than is needed when the variable is defined before `speculate` in the same block, which only keeps more alive
+            if has_speculate[b] {
+                new_in.extend(speculated.iter().copied());
+            }
+            if new_in != live_in[b] {
+                live_in[b] = new_in;
+                changed = true;
+            }
+            live_out[b] = out;
+        }
+    }
+
+    (
+        live_out
+            .into_iter()
+            .map(|s| s.into_iter().map(ToString::to_string).collect())
+            .collect(),
+        speculated.into_iter().map(ToString::to_string).collect(),
+    )
+}
+
+/// Removes definitions that are not live. Returns the number of instructions removed.
+fn remove_dead(func: &mut Function) -> usize {
+    let mut cfg = Cfg::new(std::mem::take(&mut func.instrs));
+    let (live_out, speculated) = liveness(&cfg);
+
+    let mut removed = 0;
+    for (block, mut live) in cfg.blocks.iter_mut().zip(live_out) {
+        let mut keep = vec![true; block.instrs.len()];
+        for (i, instr) in block.instrs.iter().enumerate().rev() {
+            if let Some(dest) = get_dest(instr) {
+                if !live.contains(dest) && is_removable(instr) {
+                    keep[i] = false;
+                    removed += 1;
+                    continue;
+                }
+                live.remove(dest);
+            }
+            live.extend(get_args(instr).iter().cloned());
+            if is_speculate(instr) {
+                live.extend(speculated.iter().cloned());
+            }
+        }
+        let mut keep = keep.into_iter();
+        block.instrs.retain(|_| keep.next().unwrap_or(true));
+    }
+
+    func.instrs = cfg.into_code();
+    removed
+}
+
+/// Removes unreachable blocks, definitions which are never used, and jumps to the immediately following block
+pub fn global_dce_function(func: &mut Function) -> DceStats {
+    let mut stats = DceStats {
+        unreachable: remove_unreachable(func),
+        ..DceStats::default()
+    };
+    loop {
+        let dead = remove_dead(func);
+        if dead == 0 {
+            break;
+        }
+        stats.dead += dead;
+    }
+    stats.jumps = remove_jumps_to_next(func);
+    stats
+}
+
+/// Runs both [`local_dce_function`] and [`global_dce_function`] over ```func``` until nothing else can be removed
+pub fn dce_function(func: &mut Function) -> DceStats {
+    let mut stats = DceStats::default();
+    loop {
+        let mut round = local_dce_function(func);
+        round += global_dce_function(func);
+        if round.total() == 0 {
+            break;
+        }
+        stats += round;
+    }
+    stats
+}
+
+/// Runs [`dce_function`] over every function of ```program```
+pub fn dce(program: &mut Program) -> DceStats {
+    let mut stats = DceStats::default();
+    for func in &mut program.functions {
+        stats += dce_function(func);
+    }
+    stats
+}
@@ -0,0 +1,328 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+use std::ops::AddAssign;
+
+use bril_rs::{Code, EffectOps, Function, Instruction, Position, Program, Type, ValueOps};
+
+/// Limits on how much code the inliner is allowed to duplicate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InlineOptions {
+    /// Functions with more instructions than this are never inlined
+    pub max_size: usize,
+    /// How many times calls which come from inlined code are themselves inlined. This bounds how far recursive functions are unrolled.
+    pub max_depth: usize,
+}
+
+impl Default for InlineOptions {
+    fn default() -> Self {
+        Self {
+            max_size: 32,
+            max_depth: 2,
+        }
+    }
+}
+
+/// What inlining changed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InlineStats {
+    /// The number of calls which were replaced by the body of the function being called
+    pub inlined: usize,
+}
+
+impl AddAssign for InlineStats {
+    fn add_assign(&mut self, other: Self) {
+        self.inlined += other.inlined;
+    }
+}
+
+impl Display for InlineStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "inlined {} calls", self.inlined)
+    }
+}
+
+fn size(func: &Function) -> usize {
+    func.instrs
+        .iter()
+        .filter(|c| matches!(c, Code::Instruction(_)))
+        .count()
+}
+
+// Every variable and label in the function. Bril keeps these apart but sharing one set keeps the renaming simple.
+fn names(func: &Function) -> HashSet<String> {
+    let mut names: HashSet<String> = func.args.iter().map(|a| a.name.clone()).collect();
+    for code in &func.instrs {
+        match code {
+            Code::Label { label, .. } => {
+                names.insert(label.clone());
+            }
+            Code::Instruction(Instruction::Constant { dest, .. }) => {
+                names.insert(dest.clone());
+            }
+            Code::Instruction(Instruction::Value {
+                dest, args, labels, ..
+            }) => {
+                names.insert(dest.clone());
+                names.extend(args.iter().cloned());
+                names.extend(labels.iter().cloned());
+            }
+            Code::Instruction(Instruction::Effect { args, labels, .. }) => {
+                names.extend(args.iter().cloned());
+                names.extend(labels.iter().cloned());
+            }
+        }
+    }
+    names
+}
+
+/// A call site which is being replaced by the body of ```callee```
+struct CallSite<'a> {
+    callee: &'a Function,
+    // The variable and type that receive the return value, if any
+    dest: Option<(String, Type)>,
+    args: Vec<String>,
+    pos: Option<Position>,
+}
+
+/// Picks a name to prefix the names of the callee with, which is also used as the label after the inlined body.
+/// Neither the label nor any of the prefixed names may clash with anything in ```used```.
+fn fresh_prefix(
+    callee: &Function,
+    callee_names: &HashSet<String>,
+    used: &HashSet<String>,
+) -> String {
+    let mut counter = 0;
+    loop {
+        let prefix = format!("{}.inline.{}", callee.name, counter);
+        if !used.contains(&prefix)
+            && callee_names
+                .iter()
+                .all(|n| !used.contains(&format!("{prefix}.{n}")))
+        {
+            return prefix;
+        }
+        counter += 1;
+    }
+}
+
+/// The code which replaces ```call```. Arguments are copied into the renamed parameters of the callee, and every `ret` becomes an assignment to the destination of the call followed by a jump to the label placed after the body.
+fn expand(call: &CallSite, prefix: &str) -> (Vec<Code>, String) {
+    let rename = |name: &String| format!("{prefix}.{name}");
+    let ret_label = prefix.to_string();
+    let mut code = Vec::new();
+
+    for (param, arg) in call.callee.args.iter().zip(&call.args) {
+        code.push(Code::Instruction(Instruction::Value {
+            args: vec![arg.clone()],
+            dest: rename(&param.name),
+            funcs: Vec::new(),
+            labels: Vec::new(),
+            op: ValueOps::Id,
+            pos: call.pos.clone(),
+            op_type: param.arg_type.clone(),
+        }));
+    }
+
+    for c in &call.callee.instrs {
+        match c {
+            Code::Label { label, pos } => code.push(Code::Label {
+                label: rename(label),
+                pos: pos.clone(),
+            }),
+            Code::Instruction(Instruction::Effect {
+                op: EffectOps::Return,
+                args,
+                pos,
+                ..
+            }) => {
+                if let (Some((dest, op_type)), Some(arg)) = (&call.dest, args.first()) {
+                    code.push(Code::Instruction(Instruction::Value {
+                        args: vec![rename(arg)],
+                        dest: dest.clone(),
+                        funcs: Vec::new(),
+                        labels: Vec::new(),
+                        op: ValueOps::Id,
+                        pos: pos.clone(),
+                        op_type: op_type.clone(),
+                    }));
+                }
+                code.push(Code::Instruction(Instruction::Effect {
+                    args: Vec::new(),
+                    funcs: Vec::new(),
+                    labels: vec![ret_label.clone()],
+                    op: EffectOps::Jump,
+                    pos: pos.clone(),
+                }));
+            }
+            Code::Instruction(Instruction::Constant {
+                dest,
+                op,
+                pos,
+                const_type,
+                value,
+            }) => code.push(Code::Instruction(Instruction::Constant {
+                dest: rename(dest),
+                op: *op,
+                pos: pos.clone(),
+                const_type: const_type.clone(),
+                value: value.clone(),
+            })),
+            Code::Instruction(Instruction::Value {
+                args,
+                dest,
+                funcs,
+                labels,
+                op,
+                pos,
+                op_type,
+            }) => code.push(Code::Instruction(Instruction::Value {
+                args: args.iter().map(rename).collect(),
+                dest: rename(dest),
+                funcs: funcs.clone(),
+                labels: labels.iter().map(rename).collect(),
+                op: *op,
+                pos: pos.clone(),
+                op_type: op_type.clone(),
+            })),
+            Code::Instruction(Instruction::Effect {
+                args,
+                funcs,
+                labels,
+                op,
+                pos,
+            }) => code.push(Code::Instruction(Instruction::Effect {
+                args: args.iter().map(rename).collect(),
+                funcs: funcs.clone(),
+                labels: labels.iter().map(rename).collect(),
+                op: *op,
+                pos: pos.clone(),
+            })),
+        }
+    }
+
+    code.push(Code::Label {
+        label: ret_label.clone(),
+        pos: None,
+    });
+    (code, ret_label)
+}
+
+/// Replaces calls in ```func``` with the bodies of the functions in ```callees``` that they call.
+///
+/// Only calls made from the code of ```func``` itself are inlined at first. Calls that come from inlined code are inlined in the next round, for up to [`InlineOptions::max_depth`] rounds, so recursive functions are unrolled a bounded number of times.
+pub fn inline_function(
+    func: &mut Function,
+    callees: &[Function],
+    options: &InlineOptions,
+) -> InlineStats {
+    let callee_names: HashMap<&str, HashSet<String>> = callees
+        .iter()
+        .map(|f| (f.name.as_str(), names(f)))
+        .collect();
+    let callees: HashMap<&str, &Function> = callees.iter().map(|f| (f.name.as_str(), f)).collect();
+    let mut used = names(func);
+    let mut stats = InlineStats::default();
+
+    for _ in 0..options.max_depth {
+        let mut code = Vec::new();
+        // The label of the block being copied, and the label that ends up starting its last part once calls in it are inlined
+        let mut curr_label: Option<String> = None;
+        let mut block_ends: HashMap<String, String> = HashMap::new();
+        let mut changed = false;
+
+        for c in std::mem::take(&mut func.instrs) {
+            let call = match &c {
+                Code::Label { label, .. } => {
+                    curr_label = Some(label.clone());
+                    None
+                }
+                Code::Instruction(Instruction::Value {
+                    op: ValueOps::Call,
+                    dest,
+                    op_type,
+                    funcs,
+                    args,
+                    pos,
+                    ..
+                }) => funcs
+                    .first()
+                    .and_then(|f| callees.get(f.as_str()))
+                    .map(|callee| CallSite {
+                        callee,
+                        dest: Some((dest.clone(), op_type.clone())),
+                        args: args.clone(),
+                        pos: pos.clone(),
+                    }),
+                Code::Instruction(Instruction::Effect {
+                    op: EffectOps::Call,
+                    funcs,
+                    args,
+                    pos,
+                    ..
+                }) => funcs
+                    .first()
+                    .and_then(|f| callees.get(f.as_str()))
+                    .map(|callee| CallSite {
+                        callee,
+                        dest: None,
+                        args: args.clone(),
+                        pos: pos.clone(),
+                    }),
+                Code::Instruction(_) => None,
+            };
+
+            // Calls with the wrong number of arguments are left to fail at runtime
+            match call {
+                Some(call)
+                    if size(call.callee) <= options.max_size
+                        && call.args.len() == call.callee.args.len() =>
+                {
+                    let names = &callee_names[call.callee.name.as_str()];
+                    let prefix = fresh_prefix(call.callee, names, &used);
+                    used.extend(names.iter().map(|n| format!("{prefix}.{n}")));
+                    let (body, ret_label) = expand(&call, &prefix);
+                    used.insert(ret_label.clone());
+                    code.extend(body);
+                    if let Some(label) = &curr_label {
+                        block_ends.insert(label.clone(), ret_label);
+                    }
+                    stats.inlined += 1;
+                    changed = true;
+                }
+                _ => code.push(c),
+            }
+        }
+
+        // Phi nodes refer to the block that was jumped from, which is now the last one created by inlining
+        for c in &mut code {
+            if let Code::Instruction(Instruction::Value {
+                op: ValueOps::Phi,
+                labels,
+                ..
+            }) = c
+            {
+                for label in labels.iter_mut() {
+                    if let Some(end) = block_ends.get(label) {
+                        label.clone_from(end);
+                    }
+                }
+            }
+        }
+
+        func.instrs = code;
+        if !changed {
+            break;
+        }
+    }
+    stats
+}
+
+/// Runs [`inline_function`] over every function of ```program```, inlining the bodies that functions had before this pass
+pub fn inline(program: &mut Program, options: &InlineOptions) -> InlineStats {
+    let callees = program.functions.clone();
+    let mut stats = InlineStats::default();
+    for func in &mut program.functions {
+        stats += inline_function(func, &callees, options);
+    }
+    stats
+}
@@ -0,0 +1,23 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+#![allow(clippy::too_many_lines)]
+// clap and serde pull in different versions of their proc-macro dependencies
+#![allow(clippy::multiple_crate_versions)]
+
+/// Provides the basic block representation that the passes work over
+pub mod cfg;
+#[doc(hidden)]
+pub mod cli;
+/// Provides ```dce::dce``` to perform local and global dead code elimination
+pub mod dce;
+/// Provides ```inline::inline``` to replace calls with the body of the function being called
+pub mod inline;
+/// Provides ```licm::licm``` to move loop-invariant code out of loops
+pub mod licm;
+/// Provides natural loop detection and preheader insertion
+pub mod loops;
+/// Provides ```lvn::lvn``` to perform local value numbering
+pub mod lvn;
+/// Provides ```sccp::sccp``` to perform sparse conditional constant propagation on programs in SSA form
+pub mod sccp;
@@ -0,0 +1,173 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+use std::ops::AddAssign;
+
+use bril_rs::{Function, Instruction, Program, ValueOps};
+
+use crate::cfg::{is_terminator, Cfg, FreshNames};
+use crate::dce::{get_args, get_dest, is_removable, live_out};
+use crate::loops::{find_loops, insert_preheader, NaturalLoop};
+
+/// What loop-invariant code motion changed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LicmStats {
+    /// The number of natural loops that were found
+    pub loops: usize,
+    /// The number of preheader blocks that had to be created
+    pub preheaders: usize,
+    /// The number of instructions that were moved out of a loop
+    pub hoisted: usize,
+}
+
+impl AddAssign for LicmStats {
+    fn add_assign(&mut self, other: Self) {
+        self.loops += other.loops;
+        self.preheaders += other.preheaders;
+        self.hoisted += other.hoisted;
+    }
+}
+
+impl Display for LicmStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "hoisted {} instructions out of {} loops ({} preheaders inserted)",
+            self.hoisted, self.loops, self.preheaders
+        )
+    }
+}
+
+// Phi nodes depend on which block was jumped from so they can not be moved, even though they have no side effects
+const fn is_hoistable(instr: &Instruction) -> bool {
+    is_removable(instr)
+        && !matches!(
+            instr,
+            Instruction::Value {
+                op: ValueOps::Phi,
+                ..
+            }
+        )
+}
+
+/// Finds the instructions of ```lp``` which compute the same value on every iteration, in an order where each one only depends on those before it.
+///
+/// An instruction is hoisted when it can not fail or have side effects, its destination is assigned nowhere else in the loop and is not live on entry to the header, and each of its arguments is either never assigned in the loop or is the destination of another hoisted instruction.
+/// Since the destination is not live on entry to the header, every use of it in the loop, or after leaving the loop, must come after the instruction.
+fn find_invariant(cfg: &Cfg, lp: &NaturalLoop) -> Vec<(usize, usize)> {
+    let mut live = live_out(cfg).swap_remove(lp.header);
+    for instr in cfg.blocks[lp.header].instrs.iter().rev() {
+        if let Some(dest) = get_dest(instr) {
+            live.remove(dest);
+        }
+        live.extend(get_args(instr).iter().cloned());
+    }
+
+    let mut defs_in_loop: HashMap<&str, usize> = HashMap::new();
+    for b in &lp.body {
+        for dest in cfg.blocks[*b].instrs.iter().filter_map(get_dest) {
+            *defs_in_loop.entry(dest).or_default() += 1;
+        }
+    }
+
+    let mut invariant = Vec::new();
+    let mut invariant_vars: HashSet<&str> = HashSet::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for b in &lp.body {
+            for (i, instr) in cfg.blocks[*b].instrs.iter().enumerate() {
+                let dest = match get_dest(instr) {
+                    Some(dest) if is_hoistable(instr) => dest.as_str(),
+                    _ => continue,
+                };
+                if invariant_vars.contains(dest)
+                    || defs_in_loop.get(dest) != Some(&1)
+                    || live.contains(dest)
+                {
+                    continue;
+                }
+                if get_args(instr).iter().all(|a| {
+                    !defs_in_loop.contains_key(a.as_str()) || invariant_vars.contains(a.as_str())
+                }) {
+                    invariant.push((*b, i));
+                    invariant_vars.insert(dest);
+                    changed = true;
+                }
+            }
+        }
+    }
+    invariant
+}
+
+/// Moves loop-invariant computations out of every natural loop of ```func```.
+/// Inner loops are visited before the loops containing them so that code can be hoisted through several levels of nesting.
+pub fn licm_function(func: &mut Function) -> LicmStats {
+    let mut names = FreshNames::new(func, "licm");
+    let mut cfg = Cfg::new(std::mem::take(&mut func.instrs));
+    let mut stats = LicmStats::default();
+
+    let mut loops = find_loops(&cfg);
+    loops.sort_by_key(|lp| lp.body.len());
+    // Block indices change as preheaders are inserted so loops are identified by the label of their header
+    let headers: Vec<String> = loops
+        .iter()
+        .filter_map(|lp| cfg.blocks[lp.header].label.clone())
+        .collect();
+    stats.loops = loops.len();
+
+    for header in headers {
+        let find_loop = |cfg: &Cfg| {
+            find_loops(cfg)
+                .into_iter()
+                .find(|lp| cfg.blocks[lp.header].label.as_ref() == Some(&header))
+        };
+        let Some(lp) = find_loop(&cfg) else { continue };
+        if find_invariant(&cfg, &lp).is_empty() {
+            continue;
+        }
+
+        let num_blocks = cfg.blocks.len();
+        let Some(preheader) = insert_preheader(&mut cfg, &lp, &mut names) else {
+            continue;
+        };
+        if cfg.blocks.len() != num_blocks {
+            stats.preheaders += 1;
+        }
+        let Some(lp) = find_loop(&cfg) else { continue };
+        let invariant = find_invariant(&cfg, &lp);
+
+        let mut hoisted = Vec::new();
+        for (b, i) in &invariant {
+            hoisted.push(cfg.blocks[*b].instrs[*i].clone());
+        }
+        let to_remove: HashSet<(usize, usize)> = invariant.into_iter().collect();
+        for b in &lp.body {
+            let mut i = 0;
+            cfg.blocks[*b].instrs.retain(|_| {
+                i += 1;
+                !to_remove.contains(&(*b, i - 1))
+            });
+        }
+
+        stats.hoisted += hoisted.len();
+        let pre_instrs = &mut cfg.blocks[preheader].instrs;
+        let at = if pre_instrs.last().is_some_and(is_terminator) {
+            pre_instrs.len() - 1
+        } else {
+            pre_instrs.len()
+        };
+        pre_instrs.splice(at..at, hoisted);
+    }
+
+    func.instrs = cfg.into_code();
+    stats
+}
+
+/// Runs [`licm_function`] over every function of ```program```
+pub fn licm(program: &mut Program) -> LicmStats {
+    let mut stats = LicmStats::default();
+    for func in &mut program.functions {
+        stats += licm_function(func);
+    }
+    stats
+}
@@ -0,0 +1,288 @@
+use std::collections::HashSet;
+
+use bril_rs::{EffectOps, Instruction, ValueOps};
+
+use crate::cfg::{flatten_blocks, is_terminator, BasicBlock, Cfg, FreshNames};
+
+/// A natural loop of a [`Cfg`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NaturalLoop {
+    /// The block that every iteration of the loop starts at
+    pub header: usize,
+    /// The blocks inside the loop which have a back edge to the header
+    pub latches: Vec<usize>,
+    /// Every block of the loop, including the header, in increasing order
+    pub body: Vec<usize>,
+    /// The index of the innermost loop which contains this one
+    pub parent: Option<usize>,
+}
+
+impl NaturalLoop {
+    /// Whether block ```b``` is part of this loop
+    #[must_use]
+    pub fn contains(&self, b: usize) -> bool {
+        self.body.binary_search(&b).is_ok()
+    }
+
+    /// The blocks outside of the loop which can be jumped to from inside of it
+    #[must_use]
+    pub fn exits(&self, cfg: &Cfg) -> Vec<usize> {
+        let mut exits: Vec<usize> = self
+            .body
+            .iter()
+            .flat_map(|b| cfg.successors[*b].iter().copied())
+            .filter(|s| !self.contains(*s))
+            .collect();
+        exits.sort_unstable();
+        exits.dedup();
+        exits
+    }
+}
+
+/// Finds the natural loops of the control flow graph. Back edges to the same header are merged into one loop.
+/// Blocks which can not be reached from the entry are never part of a loop.
+#[must_use]
+pub fn find_loops(cfg: &Cfg) -> Vec<NaturalLoop> {
+    let doms = cfg.dominators();
+    let reachable = cfg.reachable();
+
+    let mut loops: Vec<NaturalLoop> = Vec::new();
+    for (latch, succs) in cfg.successors.iter().enumerate() {
+        if !reachable[latch] {
+            continue;
+        }
+        for header in succs {
+            if !doms[latch][*header] {
+                continue;
+            }
+            let idx = loops
+                .iter()
+                .position(|l| l.header == *header)
+                .unwrap_or_else(|| {
+                    loops.push(NaturalLoop {
+                        header: *header,
+                        latches: Vec::new(),
+                        body: vec![*header],
+                        parent: None,
+                    });
+                    loops.len() - 1
+                });
+            let lp = &mut loops[idx];
+            lp.latches.push(latch);
+
+            // Everything that can reach the latch without going through the header
+            let mut work_list = vec![latch];
+            while let Some(b) = work_list.pop() {
+                if reachable[b] && !lp.body.contains(&b) {
+                    lp.body.push(b);
+                    work_list.extend(cfg.predecessors[b].iter().copied());
+                }
+            }
+        }
+    }
+
+    for lp in &mut loops {
+        lp.body.sort_unstable();
+    }
+
+    // The parent of a loop is the smallest other loop whose body contains its header
+    let parents: Vec<Option<usize>> = loops
+        .iter()
+        .map(|lp| {
+            loops
+                .iter()
+                .enumerate()
+                .filter(|(_, other)| other.header != lp.header && other.contains(lp.header))
+                .min_by_key(|(_, other)| other.body.len())
+                .map(|(i, _)| i)
+        })
+        .collect();
+    for (lp, parent) in loops.iter_mut().zip(parents) {
+        lp.parent = parent;
+    }
+
+    loops
+}
+
+/// How many loops enclose loop ```idx```, including itself
+#[must_use]
+pub fn loop_depth(loops: &[NaturalLoop], idx: usize) -> usize {
+    let mut depth = 1;
+    let mut curr = loops[idx].parent;
+    while let Some(p) = curr {
+        depth += 1;
+        curr = loops[p].parent;
+    }
+    depth
+}
+
+fn fresh_label(cfg: &Cfg, base: &str) -> String {
+    let labels: HashSet<&str> = cfg
+        .blocks
+        .iter()
+        .filter_map(|b| b.label.as_deref())
+        .collect();
+    let mut label = format!("{base}.preheader");
+    let mut counter = 0;
+    while labels.contains(label.as_str()) {
+        counter += 1;
+        label = format!("{base}.preheader.{counter}");
+    }
+    label
+}
+
+// The guards and operations from other extensions in ```block``` which can jump to ```label``` from the middle of it
+fn side_exits<'a>(block: &'a BasicBlock, label: &'a str) -> impl Iterator<Item = &'a Instruction> {
+    block.instrs.iter().filter(move |instr| {
+        matches!(
+            instr,
+            Instruction::Effect {
+                op: EffectOps::Guard | EffectOps::Other(_),
+                labels,
+                ..
+            } if labels.iter().any(|l| l == label)
+        )
+    })
+}
+
+/// Makes sure that ```lp``` has a preheader: a block outside of the loop whose only successor is the header and which is the only way into the loop.
+///
+/// If a new block needs to be created, jumps into the loop are redirected to it and any phi nodes in the header are split between the two blocks.
+/// Returns the index of the preheader in the updated ```cfg```, or [`None`] if the loop can not be entered, its header has no label, or an operation from another extension can jump to it from outside of the loop.
+pub fn insert_preheader(cfg: &mut Cfg, lp: &NaturalLoop, names: &mut FreshNames) -> Option<usize> {
+    let header = lp.header;
+    let header_label = cfg.blocks[header].label.clone()?;
+    let outside: Vec<usize> = cfg.predecessors[header]
+        .iter()
+        .copied()
+        .filter(|p| !lp.contains(*p))
+        .collect();
+
+    // Nothing is known about what operations from other extensions do with their labels, so they can not be pointed at a preheader instead
+    if outside
+        .iter()
+        .flat_map(|p| side_exits(&cfg.blocks[*p], &header_label))
+        .any(|instr| {
+            matches!(
+                instr,
+                Instruction::Effect {
+                    op: EffectOps::Other(_),
+                    ..
+                }
+            )
+        })
+    {
+        return None;
+    }
+
+    // The entry block is also entered when the function is called
+    if header != 0 {
+        match outside.as_slice() {
+            [] => return None,
+            // Hoisted code goes before the terminator, which must not read anything, and a guard before it would skip over the hoisted code
+            [p] if cfg.successors[*p] == [header]
+                && !matches!(
+                    cfg.blocks[*p].instrs.last(),
+                    Some(Instruction::Effect {
+                        op: EffectOps::Branch,
+                        ..
+                    })
+                )
+                && side_exits(&cfg.blocks[*p], &header_label).next().is_none() =>
+            {
+                return Some(*p)
+            }
+            _ => {}
+        }
+    }
+
+    let pre_label = fresh_label(cfg, &header_label);
+    let outside_labels: HashSet<String> = outside
+        .iter()
+        .filter_map(|p| cfg.blocks[*p].label.clone())
+        .collect();
+
+    let mut blocks = std::mem::take(&mut cfg.blocks);
+
+    // Jumps and guards from outside of the loop now go to the preheader. A block that falls through to the header will fall through to the preheader instead.
+    for p in &outside {
+        for instr in &mut blocks[*p].instrs {
+            if let Instruction::Effect {
+                op: EffectOps::Jump | EffectOps::Branch | EffectOps::Guard,
+                labels,
+                ..
+            } = instr
+            {
+                labels
+                    .iter_mut()
+                    .filter(|l| **l == header_label)
+                    .for_each(|l| l.clone_from(&pre_label));
+            }
+        }
+    }
+
+    // A block in the loop which falls through to the header needs an explicit jump to skip over the preheader
+    if header > 0 && lp.contains(header - 1) {
+        let prev = &mut blocks[header - 1];
+        if !prev.instrs.last().is_some_and(is_terminator) {
+            prev.instrs.push(Instruction::Effect {
+                op: EffectOps::Jump,
+                args: Vec::new(),
+                funcs: Vec::new(),
+                labels: vec![header_label],
+                pos: None,
+            });
+        }
+    }
+
+    let mut preheader = BasicBlock {
+        label: Some(pre_label.clone()),
+        label_pos: None,
+        instrs: Vec::new(),
+    };
+
+    for instr in &mut blocks[header].instrs {
+        if let Instruction::Value {
+            op: ValueOps::Phi,
+            args,
+            labels,
+            op_type,
+            pos,
+            ..
+        } = instr
+        {
+            let (outside_entries, inside_entries): (Vec<_>, Vec<_>) = args
+                .iter()
+                .cloned()
+                .zip(labels.iter().cloned())
+                .partition(|(_, l)| outside_labels.contains(l));
+            let (mut new_args, mut new_labels): (Vec<_>, Vec<_>) =
+                inside_entries.into_iter().unzip();
+            match outside_entries.len() {
+                0 => continue,
+                1 => new_args.push(outside_entries[0].0.clone()),
+                _ => {
+                    let var = names.fresh();
+                    let (pre_args, pre_labels) = outside_entries.into_iter().unzip();
+                    preheader.instrs.push(Instruction::Value {
+                        op: ValueOps::Phi,
+                        dest: var.clone(),
+                        args: pre_args,
+                        funcs: Vec::new(),
+                        labels: pre_labels,
+                        op_type: op_type.clone(),
+                        pos: pos.clone(),
+                    });
+                    new_args.push(var);
+                }
+            }
+            new_labels.push(pre_label.clone());
+            *args = new_args;
+            *labels = new_labels;
+        }
+    }
+
+    blocks.insert(header, preheader);
+    *cfg = Cfg::new(flatten_blocks(blocks));
+    Some(header)
+}
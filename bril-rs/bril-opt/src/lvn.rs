@@ -0,0 +1,412 @@
+use std::collections::{HashMap, HashSet};
+
+use bril_rs::{ConstOps, Function, Instruction, Literal, Position, Program, Type, ValueOps};
+
+use crate::cfg::{flatten_blocks, form_blocks, is_speculation, FreshNames};
+
+/// A hashable version of [Literal]. Floats are compared by their bit pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum ConstKey {
+    Int(i64),
+    Bool(bool),
+    Float(u64),
+}
+
+impl From<&Literal> for ConstKey {
+    fn from(l: &Literal) -> Self {
+        match l {
+            Literal::Int(i) => Self::Int(*i),
+            Literal::Bool(b) => Self::Bool(*b),
+            Literal::Float(f) => Self::Float(f.to_bits()),
+        }
+    }
+}
+
+/// A computation in terms of the value numbers of its arguments
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Value {
+    Const(Type, ConstKey),
+    Op(ValueOps, Type, Vec<usize>),
+}
+
+// Integer literals can be promoted to floating point so we normalize them up front
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn normalize_literal(const_type: &Type, value: &Literal) -> Literal {
+    match (const_type, value) {
+        (Type::Float, Literal::Int(i)) => Literal::Float(*i as f64),
+        _ => value.clone(),
+    }
+}
+
+// Calls may have side effects, allocations always return a fresh pointer, loads depend on the state of memory, phis depend on the incoming edge, and nothing is known about operations from other extensions, so none of them can be reused
+const fn is_pure(op: ValueOps) -> bool {
+    !matches!(
+        op,
+        ValueOps::Call | ValueOps::Phi | ValueOps::Alloc | ValueOps::Load | ValueOps::Other(_)
+    )
+}
+
+fn canonicalize(op: ValueOps, mut args: Vec<usize>) -> (ValueOps, Vec<usize>) {
+    match op {
+        ValueOps::Add
+        | ValueOps::Mul
+        | ValueOps::Eq
+        | ValueOps::And
+        | ValueOps::Or
+        | ValueOps::Fadd
+        | ValueOps::Fmul
+        | ValueOps::Feq => {
+            args.sort_unstable();
+            (op, args)
+        }
+        ValueOps::Gt | ValueOps::Ge | ValueOps::Fgt | ValueOps::Fge => {
+            args.reverse();
+            let op = match op {
+                ValueOps::Gt => ValueOps::Lt,
+                ValueOps::Ge => ValueOps::Le,
+                ValueOps::Fgt => ValueOps::Flt,
+                _ => ValueOps::Fle,
+            };
+            (op, args)
+        }
+        _ => (op, args),
+    }
+}
+
+const fn fold_int(op: ValueOps, a: i64, b: i64) -> Option<Literal> {
+    Some(match op {
+        ValueOps::Add => Literal::Int(a.wrapping_add(b)),
+        ValueOps::Sub => Literal::Int(a.wrapping_sub(b)),
+        ValueOps::Mul => Literal::Int(a.wrapping_mul(b)),
+        // Dividing by zero is a runtime error which needs to be preserved
+        ValueOps::Div if b != 0 => Literal::Int(a.wrapping_div(b)),
+        ValueOps::Eq => Literal::Bool(a == b),
+        ValueOps::Lt => Literal::Bool(a < b),
+        ValueOps::Gt => Literal::Bool(a > b),
+        ValueOps::Le => Literal::Bool(a <= b),
+        ValueOps::Ge => Literal::Bool(a >= b),
+        _ => return None,
+    })
+}
+
+#[allow(clippy::float_cmp)]
+fn fold_float(op: ValueOps, a: f64, b: f64) -> Option<Literal> {
+    let res = match op {
+        ValueOps::Fadd => a + b,
+        ValueOps::Fsub => a - b,
+        ValueOps::Fmul => a * b,
+        ValueOps::Fdiv => a / b,
+        ValueOps::Feq => return Some(Literal::Bool(a == b)),
+        ValueOps::Flt => return Some(Literal::Bool(a < b)),
+        ValueOps::Fgt => return Some(Literal::Bool(a > b)),
+        ValueOps::Fle => return Some(Literal::Bool(a <= b)),
+        ValueOps::Fge => return Some(Literal::Bool(a >= b)),
+        _ => return None,
+    };
+    // Infinities and NaN have no representation in Bril's JSON or text formats
+    if res.is_finite() {
+        Some(Literal::Float(res))
+    } else {
+        None
+    }
+}
+
+/// Evaluates ```op``` over constant arguments. Returns [`None`] when the result can not be written as a constant or the operation would fail at runtime.
+pub(crate) fn fold_literals(op: ValueOps, args: &[&Literal]) -> Option<Literal> {
+    match args {
+        [Literal::Int(a), Literal::Int(b)] => fold_int(op, *a, *b),
+        [Literal::Float(a), Literal::Float(b)] => fold_float(op, *a, *b),
+        [Literal::Bool(a), Literal::Bool(b)] => match op {
+            ValueOps::And => Some(Literal::Bool(*a && *b)),
+            ValueOps::Or => Some(Literal::Bool(*a || *b)),
+            _ => None,
+        },
+        [Literal::Bool(a)] => match op {
+            ValueOps::Not => Some(Literal::Bool(!*a)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn fold(op: ValueOps, args: &[usize], num2const: &HashMap<usize, Literal>) -> Option<Literal> {
+    let consts: Option<Vec<&Literal>> = args.iter().map(|a| num2const.get(a)).collect();
+    if let Some(consts) = consts {
+        return fold_literals(op, &consts);
+    }
+    match (op, args) {
+        // Comparing a value with itself does not depend on what the value is. This does not hold for floats because of NaN.
+        (ValueOps::Eq | ValueOps::Le | ValueOps::Ge, [a, b]) if a == b => Some(Literal::Bool(true)),
+        (ValueOps::Lt | ValueOps::Gt, [a, b]) if a == b => Some(Literal::Bool(false)),
+        // One constant argument can be enough to short circuit logical operators
+        (ValueOps::And, [a, b]) => [a, b]
+            .iter()
+            .find(|n| num2const.get(n) == Some(&Literal::Bool(false)))
+            .map(|_| Literal::Bool(false)),
+        (ValueOps::Or, [a, b]) => [a, b]
+            .iter()
+            .find(|n| num2const.get(n) == Some(&Literal::Bool(true)))
+            .map(|_| Literal::Bool(true)),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct Numbering {
+    // The current value number of every variable (by its original name)
+    var2num: HashMap<String, usize>,
+    // The value number of every computation seen so far
+    value2num: HashMap<Value, usize>,
+    // The variable which holds the value of this number. There is at most one canonical variable per number and it is removed if that variable gets overwritten.
+    num2var: HashMap<usize, String>,
+    // The numbers which are known to be constants
+    num2const: HashMap<usize, Literal>,
+    next_num: usize,
+}
+
+impl Numbering {
+    const fn fresh_num(&mut self) -> usize {
+        let n = self.next_num;
+        self.next_num += 1;
+        n
+    }
+
+    // If the canonical variable of a number was overwritten, the variable being read still holds the value
+    fn arg_name(&self, num: usize, arg: &str) -> String {
+        self.num2var
+            .get(&num)
+            .cloned()
+            .unwrap_or_else(|| arg.to_string())
+    }
+
+    // `var` now holds the value of `num` so it can no longer be the canonical variable of any other number
+    fn write(&mut self, var: &str, num: usize) {
+        self.num2var.retain(|n, v| *n == num || v != var);
+    }
+}
+
+/// Returns, for each instruction, whether it is the last write to its destination in the block
+fn last_writes(instrs: &[Instruction]) -> Vec<bool> {
+    let mut out = vec![false; instrs.len()];
+    let mut seen = HashSet::new();
+    for (idx, instr) in instrs.iter().enumerate().rev() {
+        if let Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } = instr {
+            out[idx] = seen.insert(dest.as_str());
+        }
+    }
+    out
+}
+
+/// Returns the variables that are read before they are written in the block
+fn read_first(instrs: &[Instruction]) -> Vec<String> {
+    let mut read = Vec::new();
+    let mut written = HashSet::new();
+    for instr in instrs {
+        if let Instruction::Value { args, .. } | Instruction::Effect { args, .. } = instr {
+            for a in args {
+                if !written.contains(a.as_str()) && !read.contains(a) {
+                    read.push(a.clone());
+                }
+            }
+        }
+        if let Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } = instr {
+            written.insert(dest.as_str());
+        }
+    }
+    read
+}
+
+const fn make_const(
+    dest: String,
+    const_type: Type,
+    value: Literal,
+    pos: Option<Position>,
+) -> Instruction {
+    Instruction::Constant {
+        dest,
+        op: ConstOps::Const,
+        pos,
+        const_type,
+        value,
+    }
+}
+
+fn make_id(dest: String, op_type: Type, arg: String, pos: Option<Position>) -> Instruction {
+    Instruction::Value {
+        args: vec![arg],
+        dest,
+        funcs: Vec::new(),
+        labels: Vec::new(),
+        op: ValueOps::Id,
+        pos,
+        op_type,
+    }
+}
+
+fn lvn_instrs(instrs: &mut [Instruction], names: &mut FreshNames) {
+    let mut state = Numbering::default();
+    for var in read_first(instrs) {
+        let num = state.fresh_num();
+        state.var2num.insert(var.clone(), num);
+        state.num2var.insert(num, var);
+    }
+
+    let last_writes = last_writes(instrs);
+    for (instr, last_write) in instrs.iter_mut().zip(last_writes) {
+        let pos = instr.get_pos().cloned();
+        match instr {
+            Instruction::Effect { args, .. } => {
+                *args = args
+                    .iter()
+                    .map(|a| state.arg_name(state.var2num[a], a))
+                    .collect();
+            }
+            Instruction::Value {
+                op: ValueOps::Id,
+                dest,
+                op_type,
+                args,
+                ..
+            } if args.len() == 1 => {
+                // Copy propagation: the destination just gets the number of its argument
+                let num = state.var2num[&args[0]];
+                let dest = dest.clone();
+                let op_type = op_type.clone();
+                let arg = state.arg_name(num, &args[0]);
+                *instr = state.num2const.get(&num).map_or_else(
+                    || make_id(dest.clone(), op_type.clone(), arg, pos.clone()),
+                    |c| make_const(dest.clone(), op_type.clone(), c.clone(), pos.clone()),
+                );
+                state.write(&dest, num);
+                state.var2num.insert(dest, num);
+            }
+            Instruction::Constant {
+                dest,
+                const_type,
+                value,
+                ..
+            } => {
+                let literal = normalize_literal(const_type, value);
+                let val = Value::Const(const_type.clone(), ConstKey::from(&literal));
+                if let Some(num) = state.value2num.get(&val).copied() {
+                    let dest = dest.clone();
+                    state.write(&dest, num);
+                    state.var2num.insert(dest, num);
+                    continue;
+                }
+                let num = state.fresh_num();
+                let original = std::mem::take(dest);
+                *dest = if last_write {
+                    original.clone()
+                } else {
+                    names.fresh()
+                };
+                state.write(dest, num);
+                state.num2var.insert(num, dest.clone());
+                state.var2num.insert(original, num);
+                state.num2const.insert(num, literal);
+                state.value2num.insert(val, num);
+            }
+            Instruction::Value {
+                op,
+                dest,
+                op_type,
+                args,
+                ..
+            } => {
+                let arg_nums: Vec<usize> = if *op == ValueOps::Phi {
+                    Vec::new()
+                } else {
+                    args.iter().map(|a| state.var2num[a]).collect()
+                };
+
+                let val = if is_pure(*op) {
+                    let (op, arg_nums) = canonicalize(*op, arg_nums.clone());
+                    Some((op, Value::Op(op, op_type.clone(), arg_nums)))
+                } else {
+                    None
+                };
+
+                // Is this value already available?
+                if let Some(num) = val.as_ref().and_then(|(_, v)| state.value2num.get(v)) {
+                    let num = *num;
+                    let replacement = match state.num2const.get(&num) {
+                        Some(c) => Some(make_const(
+                            dest.clone(),
+                            op_type.clone(),
+                            c.clone(),
+                            pos.clone(),
+                        )),
+                        None => state.num2var.get(&num).map(|v| {
+                            make_id(dest.clone(), op_type.clone(), v.clone(), pos.clone())
+                        }),
+                    };
+                    if let Some(replacement) = replacement {
+                        let dest = dest.clone();
+                        *instr = replacement;
+                        state.write(&dest, num);
+                        state.var2num.insert(dest, num);
+                        continue;
+                    }
+                }
+
+                // Phi arguments are read along the incoming edge, not in this block
+                if *op != ValueOps::Phi {
+                    *args = args
+                        .iter()
+                        .zip(arg_nums.iter())
+                        .map(|(a, n)| state.arg_name(*n, a))
+                        .collect();
+                }
+
+                let num = state.fresh_num();
+                let original = std::mem::take(dest);
+                // If the variable is overwritten later in the block, the value is put into a new variable so that it can still be reused
+                *dest = if last_write {
+                    original.clone()
+                } else {
+                    names.fresh()
+                };
+                let dest = dest.clone();
+                let op_type = op_type.clone();
+                state.write(&dest, num);
+                state.num2var.insert(num, dest.clone());
+                state.var2num.insert(original, num);
+
+                if let Some((op, val)) = val {
+                    if let Value::Op(_, _, arg_nums) = &val {
+                        if let Some(c) = fold(op, arg_nums, &state.num2const) {
+                            *instr = make_const(dest, op_type.clone(), c.clone(), pos);
+                            state
+                                .value2num
+                                .entry(Value::Const(op_type, ConstKey::from(&c)))
+                                .or_insert(num);
+                            state.num2const.insert(num, c);
+                        }
+                    }
+                    state.value2num.insert(val, num);
+                }
+            }
+        }
+    }
+}
+
+/// Performs local value numbering over each basic block of ```func```.
+///
+/// Redundant computations are replaced with copies, copies are propagated, commutative operations are canonicalized, and operations over constants are folded.
+pub fn lvn_function(func: &mut Function) {
+    let mut names = FreshNames::new(func, "lvn");
+    let mut blocks = form_blocks(std::mem::take(&mut func.instrs));
+    for block in &mut blocks {
+        // Aborting a speculation puts back the variables as they were at `speculate`, so the instructions of a speculation are numbered apart from those around them and the last write before it keeps its name
+        for instrs in block.instrs.split_inclusive_mut(is_speculation) {
+            lvn_instrs(instrs, &mut names);
+        }
+    }
+    func.instrs = flatten_blocks(blocks);
+}
+
+/// Performs local value numbering over every function of ```program```. See [`lvn_function`].
+pub fn lvn(program: &mut Program) {
+    program.functions.iter_mut().for_each(lvn_function);
+}
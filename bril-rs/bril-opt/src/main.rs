@@ -0,0 +1,43 @@
+use bril_opt::cli::{Cli, Pass};
+use bril_opt::inline::InlineOptions;
+use bril_rs::{load_program, output_program};
+use clap::Parser;
+
+fn main() {
+    let args = Cli::parse();
+    let mut program = load_program();
+    for pass in args.passes {
+        match pass {
+            Pass::Lvn => bril_opt::lvn::lvn(&mut program),
+            Pass::Dce => {
+                let stats = bril_opt::dce::dce(&mut program);
+                if args.stats {
+                    eprintln!("dce: {stats}");
+                }
+            }
+            Pass::Licm => {
+                let stats = bril_opt::licm::licm(&mut program);
+                if args.stats {
+                    eprintln!("licm: {stats}");
+                }
+            }
+            Pass::Sccp => {
+                let stats = bril_opt::sccp::sccp(&mut program);
+                if args.stats {
+                    eprintln!("sccp: {stats}");
+                }
+            }
+            Pass::Inline => {
+                let options = InlineOptions {
+                    max_size: args.inline_size,
+                    max_depth: args.inline_depth,
+                };
+                let stats = bril_opt::inline::inline(&mut program, &options);
+                if args.stats {
+                    eprintln!("inline: {stats}");
+                }
+            }
+        }
+    }
+    output_program(&program);
+}
@@ -0,0 +1,451 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+use std::ops::AddAssign;
+
+use bril_rs::{ConstOps, EffectOps, Function, Instruction, Literal, Program, ValueOps};
+
+use crate::cfg::Cfg;
+use crate::dce::{get_args, get_dest};
+use crate::lvn::{fold_literals, normalize_literal, ConstKey};
+
+/// What sparse conditional constant propagation changed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SccpStats {
+    /// Instructions which were replaced by a constant
+    pub constants: usize,
+    /// Branches on a constant condition which were replaced by a jump
+    pub branches: usize,
+    /// Blocks which were removed because they can never be executed
+    pub blocks: usize,
+    /// Phi nodes which had incoming values removed or were replaced by a copy
+    pub phis: usize,
+    /// Functions which were left alone because they are not in SSA form or branch on a variable before it is assigned
+    pub skipped: usize,
+}
+
+impl AddAssign for SccpStats {
+    fn add_assign(&mut self, other: Self) {
+        self.constants += other.constants;
+        self.branches += other.branches;
+        self.blocks += other.blocks;
+        self.phis += other.phis;
+        self.skipped += other.skipped;
+    }
+}
+
+impl Display for SccpStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "replaced {} instructions with constants, folded {} branches, removed {} blocks, simplified {} phis, skipped {} functions not in SSA form",
+            self.constants, self.branches, self.blocks, self.phis, self.skipped
+        )
+    }
+}
+
+/// What is known about the value of a variable
+#[derive(Debug, Clone, PartialEq)]
+enum Lattice {
+    /// No definition of the variable has been executed yet
+    Top,
+    Const(Literal),
+    /// The variable may hold more than one value
+    Bottom,
+}
+
+impl Lattice {
+    fn meet(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Top, x) | (x, Self::Top) => x,
+            (Self::Const(a), Self::Const(b)) if ConstKey::from(&a) == ConstKey::from(&b) => {
+                Self::Const(a)
+            }
+            _ => Self::Bottom,
+        }
+    }
+}
+
+const fn is_phi(instr: &Instruction) -> bool {
+    matches!(
+        instr,
+        Instruction::Value {
+            op: ValueOps::Phi,
+            ..
+        }
+    )
+}
+
+/// Whether every variable of the function is assigned exactly once, and function arguments are never reassigned
+fn is_ssa(func: &Function, cfg: &Cfg) -> bool {
+    let mut defined: HashSet<&str> = func.args.iter().map(|a| a.name.as_str()).collect();
+    cfg.blocks
+        .iter()
+        .flat_map(|b| b.instrs.iter())
+        .filter_map(get_dest)
+        .all(|dest| defined.insert(dest))
+}
+
+struct Sccp<'a> {
+    cfg: &'a Cfg,
+    values: HashMap<&'a str, Lattice>,
+    // Where each variable is used
+    uses: HashMap<&'a str, Vec<(usize, usize)>>,
+    executable: HashSet<(usize, usize)>,
+    visited: Vec<bool>,
+    flow_work_list: Vec<(Option<usize>, usize)>,
+    ssa_work_list: Vec<(usize, usize)>,
+}
+
+impl<'a> Sccp<'a> {
+    fn new(func: &'a Function, cfg: &'a Cfg) -> Self {
+        let mut values: HashMap<&str, Lattice> = func
+            .args
+            .iter()
+            .map(|a| (a.name.as_str(), Lattice::Bottom))
+            .collect();
+        let mut uses: HashMap<&str, Vec<(usize, usize)>> = HashMap::new();
+        for (b, block) in cfg.blocks.iter().enumerate() {
+            for (i, instr) in block.instrs.iter().enumerate() {
+                if let Some(dest) = get_dest(instr) {
+                    values.insert(dest, Lattice::Top);
+                }
+                for arg in get_args(instr) {
+                    uses.entry(arg).or_default().push((b, i));
+                }
+            }
+        }
+
+        Self {
+            cfg,
+            values,
+            uses,
+            executable: HashSet::new(),
+            visited: vec![false; cfg.blocks.len()],
+            flow_work_list: if cfg.blocks.is_empty() {
+                Vec::new()
+            } else {
+                vec![(None, 0)]
+            },
+            ssa_work_list: Vec::new(),
+        }
+    }
+
+    // Variables that are never assigned, like `__undefined` in phi nodes, can not be read without an error so they are only given a value by their uses
+    fn is_defined(&self, var: &str) -> bool {
+        self.values.contains_key(var)
+    }
+
+    fn value(&self, var: &str) -> Lattice {
+        self.values.get(var).cloned().unwrap_or(Lattice::Bottom)
+    }
+
+    // A branch in an executable block on a variable whose definition never executes reads it before it is assigned, so which way it goes is never decided
+    fn has_undecided_branch(&self) -> bool {
+        self.cfg.blocks.iter().enumerate().any(|(b, block)| {
+            self.visited[b]
+                && matches!(
+                    block.instrs.last(),
+                    Some(Instruction::Effect {
+                        op: EffectOps::Branch,
+                        args,
+                        ..
+                    }) if self.value(&args[0]) == Lattice::Top
+                )
+        })
+    }
+
+    fn eval(&self, block: usize, instr: &Instruction) -> Lattice {
+        match instr {
+            Instruction::Constant {
+                const_type, value, ..
+            } => Lattice::Const(normalize_literal(const_type, value)),
+            Instruction::Value {
+                op: ValueOps::Phi,
+                args,
+                labels,
+                ..
+            } => {
+                let incoming: Vec<&String> = args
+                    .iter()
+                    .zip(labels)
+                    .filter(|(_, l)| {
+                        self.cfg
+                            .block_index(l)
+                            .is_some_and(|p| self.executable.contains(&(p, block)))
+                    })
+                    .map(|(a, _)| a)
+                    .collect();
+                let defined: Vec<&&String> =
+                    incoming.iter().filter(|a| self.is_defined(a)).collect();
+                if defined.is_empty() && !incoming.is_empty() {
+                    return Lattice::Bottom;
+                }
+                defined
+                    .into_iter()
+                    .fold(Lattice::Top, |acc, a| acc.meet(self.value(a)))
+            }
+            Instruction::Value {
+                op: ValueOps::Id,
+                args,
+                ..
+            } => args.first().map_or(Lattice::Bottom, |a| self.value(a)),
+            Instruction::Value { op, args, .. } => {
+                let values: Vec<Lattice> = args.iter().map(|a| self.value(a)).collect();
+                // One constant argument can be enough to short circuit logical operators
+                let short_circuit = match op {
+                    ValueOps::And => Some(false),
+                    ValueOps::Or => Some(true),
+                    _ => None,
+                };
+                if let Some(b) = short_circuit {
+                    if values.contains(&Lattice::Const(Literal::Bool(b))) {
+                        return Lattice::Const(Literal::Bool(b));
+                    }
+                }
+                if values.contains(&Lattice::Bottom) {
+                    return Lattice::Bottom;
+                }
+                let consts: Option<Vec<&Literal>> = values
+                    .iter()
+                    .map(|v| match v {
+                        Lattice::Const(l) => Some(l),
+                        _ => None,
+                    })
+                    .collect();
+                // Without every argument having a value yet there is nothing to fold
+                consts.map_or(Lattice::Top, |consts| {
+                    fold_literals(*op, &consts).map_or(Lattice::Bottom, Lattice::Const)
+                })
+            }
+            Instruction::Effect { .. } => Lattice::Bottom,
+        }
+    }
+
+    // The blocks that can be reached from the end of `block` given what is currently known
+    fn eval_successors(&self, block: usize) -> Vec<usize> {
+        match self.cfg.blocks[block].instrs.last() {
+            Some(Instruction::Effect {
+                op: EffectOps::Branch,
+                args,
+                labels,
+                ..
+            }) => match self.value(&args[0]) {
+                Lattice::Top => Vec::new(),
+                Lattice::Const(Literal::Bool(b)) => self
+                    .cfg
+                    .block_index(&labels[usize::from(!b)])
+                    .into_iter()
+                    .collect(),
+                _ => self.cfg.successors[block].clone(),
+            },
+            _ => self.cfg.successors[block].clone(),
+        }
+    }
+
+    fn visit_instr(&mut self, block: usize, idx: usize) {
+        let instr = &self.cfg.blocks[block].instrs[idx];
+        if let Some(dest) = get_dest(instr) {
+            let old = self.value(dest);
+            let new = old.clone().meet(self.eval(block, instr));
+            if new != old {
+                self.values.insert(dest, new);
+                if let Some(uses) = self.uses.get(dest.as_str()) {
+                    self.ssa_work_list.extend(uses.iter().copied());
+                }
+            }
+        } else if idx + 1 == self.cfg.blocks[block].instrs.len() {
+            self.visit_end(block);
+        }
+    }
+
+    fn visit_end(&mut self, block: usize) {
+        for succ in self.eval_successors(block) {
+            if !self.executable.contains(&(block, succ)) {
+                self.flow_work_list.push((Some(block), succ));
+            }
+        }
+    }
+
+    fn run(&mut self) {
+        while !self.flow_work_list.is_empty() || !self.ssa_work_list.is_empty() {
+            while let Some((from, to)) = self.flow_work_list.pop() {
+                if let Some(from) = from {
+                    if !self.executable.insert((from, to)) {
+                        continue;
+                    }
+                }
+                let first_visit = !self.visited[to];
+                self.visited[to] = true;
+                let instrs = &self.cfg.blocks[to].instrs;
+                for (i, instr) in instrs.iter().enumerate() {
+                    if first_visit || is_phi(instr) {
+                        self.visit_instr(to, i);
+                    }
+                }
+                // Blocks which end in a value instruction or nothing at all fall through
+                if first_visit && instrs.last().is_none_or(|i| get_dest(i).is_some()) {
+                    self.visit_end(to);
+                }
+            }
+            while let Some((block, idx)) = self.ssa_work_list.pop() {
+                if self.visited[block] {
+                    self.visit_instr(block, idx);
+                }
+            }
+        }
+    }
+}
+
+/// Runs sparse conditional constant propagation over a function in SSA form.
+///
+/// Variables which always hold the same constant are assigned it directly, branches on constant conditions become jumps, blocks which can never execute are removed, and phi nodes lose the incoming values of edges that are never taken.
+/// Functions which are not in SSA form, or which can branch on a variable before it is assigned, are left unchanged and counted in [`SccpStats::skipped`].
+pub fn sccp_function(func: &mut Function) -> SccpStats {
+    let mut cfg = Cfg::new(std::mem::take(&mut func.instrs));
+    let mut stats = SccpStats::default();
+    let analysis = if is_ssa(func, &cfg) {
+        let mut sccp = Sccp::new(func, &cfg);
+        sccp.run();
+        // The blocks that an undecided branch goes to would be removed out from under it
+        (!sccp.has_undecided_branch()).then(|| {
+            let values: HashMap<String, Literal> = sccp
+                .values
+                .into_iter()
+                .filter_map(|(var, v)| match v {
+                    Lattice::Const(l) => Some((var.to_string(), l)),
+                    _ => None,
+                })
+                .collect();
+            (values, sccp.executable, sccp.visited)
+        })
+    } else {
+        None
+    };
+    let Some((values, executable, visited)) = analysis else {
+        func.instrs = cfg.into_code();
+        stats.skipped = 1;
+        return stats;
+    };
+
+    let defined: HashSet<String> = func
+        .args
+        .iter()
+        .map(|a| a.name.clone())
+        .chain(
+            cfg.blocks
+                .iter()
+                .flat_map(|b| b.instrs.iter())
+                .filter_map(get_dest)
+                .cloned(),
+        )
+        .collect();
+    let label_index: HashMap<String, usize> = cfg
+        .blocks
+        .iter()
+        .enumerate()
+        .filter_map(|(i, b)| b.label.clone().map(|l| (l, i)))
+        .collect();
+
+    for (b, block) in cfg.blocks.iter_mut().enumerate() {
+        if !visited[b] {
+            continue;
+        }
+        let phi_dests: HashSet<String> = block
+            .instrs
+            .iter()
+            .filter(|i| is_phi(i))
+            .filter_map(get_dest)
+            .cloned()
+            .collect();
+
+        for instr in &mut block.instrs {
+            match instr {
+                Instruction::Value {
+                    dest, op_type, pos, ..
+                } if values.contains_key(dest.as_str()) => {
+                    *instr = Instruction::Constant {
+                        dest: dest.clone(),
+                        op: ConstOps::Const,
+                        pos: pos.clone(),
+                        const_type: op_type.clone(),
+                        value: values[dest.as_str()].clone(),
+                    };
+                    stats.constants += 1;
+                }
+                Instruction::Value {
+                    op: ValueOps::Phi,
+                    dest,
+                    args,
+                    labels,
+                    op_type,
+                    pos,
+                    ..
+                } => {
+                    let (new_args, new_labels): (Vec<String>, Vec<String>) = args
+                        .iter()
+                        .cloned()
+                        .zip(labels.iter().cloned())
+                        .filter(|(_, l)| {
+                            label_index
+                                .get(l)
+                                .is_some_and(|p| executable.contains(&(*p, b)))
+                        })
+                        .unzip();
+                    // Phi nodes are evaluated all at once so a copy can not read a variable that another phi in the block assigns
+                    let same_arg = new_args
+                        .first()
+                        .filter(|a| new_args.iter().all(|x| x == *a))
+                        .filter(|a| defined.contains(*a) && !phi_dests.contains(*a));
+                    if let Some(arg) = same_arg {
+                        *instr = Instruction::Value {
+                            args: vec![arg.clone()],
+                            dest: dest.clone(),
+                            funcs: Vec::new(),
+                            labels: Vec::new(),
+                            op: ValueOps::Id,
+                            pos: pos.clone(),
+                            op_type: op_type.clone(),
+                        };
+                        stats.phis += 1;
+                    } else if !new_args.is_empty() && new_args.len() != args.len() {
+                        *args = new_args;
+                        *labels = new_labels;
+                        stats.phis += 1;
+                    }
+                }
+                Instruction::Effect {
+                    op: op @ EffectOps::Branch,
+                    args,
+                    labels,
+                    ..
+                } => {
+                    if let Some(Literal::Bool(cond)) = values.get(args[0].as_str()) {
+                        let target = labels[usize::from(!*cond)].clone();
+                        *op = EffectOps::Jump;
+                        *args = Vec::new();
+                        *labels = vec![target];
+                        stats.branches += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let num_blocks = cfg.blocks.len();
+    let mut visited = visited.into_iter();
+    cfg.blocks.retain(|_| visited.next().unwrap_or(true));
+    stats.blocks = num_blocks - cfg.blocks.len();
+
+    func.instrs = cfg.into_code();
+    stats
+}
+
+/// Runs [`sccp_function`] over every function of ```program```
+pub fn sccp(program: &mut Program) -> SccpStats {
+    let mut stats = SccpStats::default();
+    for func in &mut program.functions {
+        stats += sccp_function(func);
+    }
+    stats
+}
@@ -1,9 +1,36 @@
+use std::path::PathBuf;
+
 use clap::Parser;
 
 #[derive(Parser)]
 #[clap(about, version, author)] // keeps the cli synced with Cargo.toml
+// Each flag is independent of the others
+#[allow(clippy::struct_excessive_bools)]
 pub struct Cli {
     /// Flag for whether position information should be included
     #[clap(short)]
     pub position: bool,
+
+    /// Flag for whether comments and blank lines should be included
+    #[clap(short, conflicts_with = "concrete")]
+    pub comments: bool,
+
+    /// The Bril text files to read instead of stdin. Positions will record them as their `src`. The functions of several files are put together into one program
+    pub files: Vec<PathBuf>,
+
+    /// Write the JSON to this file instead of stdout
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Write the JSON on one line instead of pretty printing it
+    #[clap(long)]
+    pub compact: bool,
+
+    /// Convert the program into the concrete form of `bril_rs::Program` before writing it, which fails on unknown operations and missing types
+    #[clap(long)]
+    pub concrete: bool,
+
+    /// Don't write anything and instead report what would stop each input from being converted into the concrete form, exiting with 1 if anything would
+    #[clap(long, conflicts_with_all = &["output", "concrete"])]
+    pub check: bool,
 }
@@ -0,0 +1,186 @@
+use bril_rs::{AbstractCode, AbstractInstruction, AbstractProgram, Comments};
+
+enum Token<'a> {
+    Word(&'a str),
+    Punct(char),
+    Comment(&'a str),
+    Newline,
+}
+
+const PUNCTUATION: &str = "<>{}()=;:,";
+
+// The grammar skips over comments and whitespace so the text is split up again here to find them
+fn lex(text: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let len = if c == '\n' {
+            tokens.push(Token::Newline);
+            1
+        } else if c.is_whitespace() {
+            c.len_utf8()
+        } else if c == '#' {
+            let len = rest.find(['\n', '\r']).unwrap_or(rest.len());
+            tokens.push(Token::Comment(rest[..len].trim_end()));
+            len
+        } else if c == '"' {
+            // The path of an import, which may have spaces or a `#` in it
+            let len = rest[1..].find('"').map_or(rest.len(), |l| l + 2);
+            tokens.push(Token::Word(&rest[..len]));
+            len
+        } else if PUNCTUATION.contains(c) {
+            tokens.push(Token::Punct(c));
+            1
+        } else {
+            let len = rest
+                .find(|c: char| c.is_whitespace() || c == '#' || PUNCTUATION.contains(c))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Word(&rest[..len]));
+            len
+        };
+        rest = &rest[len..];
+    }
+    tokens
+}
+
+#[derive(Default)]
+struct FunctionComments {
+    comments: Comments,
+    code: Vec<Comments>,
+}
+
+// What a comment at the end of the current line belongs to
+#[derive(Clone, Copy)]
+enum Inline {
+    Import,
+    Function,
+    Code,
+}
+
+// The comments of each import and of each function and each of its labels and instructions, in the order they appear in ```text```
+fn find_comments(text: &str) -> (Vec<Comments>, Vec<FunctionComments>) {
+    let tokens = lex(text);
+    let mut imports: Vec<Comments> = Vec::new();
+    let mut functions: Vec<FunctionComments> = Vec::new();
+    // The lines that go before whatever comes next
+    let mut pending: Vec<String> = Vec::new();
+    let mut inline = None;
+    let mut line_is_empty = true;
+    let mut in_body = false;
+    let mut in_import = false;
+    // Where the label or instruction being read started, if there is one
+    let mut code_start = None;
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Newline => {
+                if line_is_empty {
+                    pending.push(String::new());
+                }
+                line_is_empty = true;
+                inline = None;
+                continue;
+            }
+            Token::Comment(c) => {
+                let comments = match inline {
+                    Some(Inline::Import) => imports.last_mut(),
+                    Some(Inline::Function) => functions.last_mut().map(|f| &mut f.comments),
+                    Some(Inline::Code) => functions.last_mut().and_then(|f| f.code.last_mut()),
+                    None => None,
+                };
+                match comments {
+                    Some(comments) => comments.inline = Some((*c).to_string()),
+                    None => pending.push((*c).to_string()),
+                }
+            }
+            // Imports come before any functions and name functions of their own
+            Token::Word("from") if !in_body && !in_import && functions.is_empty() => {
+                in_import = true;
+                imports.push(Comments {
+                    before: std::mem::take(&mut pending),
+                    ..Comments::default()
+                });
+            }
+            Token::Punct(';') if in_import => {
+                in_import = false;
+                inline = Some(Inline::Import);
+            }
+            _ if in_import => {}
+            Token::Word(w) if !in_body && w.starts_with('@') => functions.push(FunctionComments {
+                comments: Comments {
+                    before: std::mem::take(&mut pending),
+                    ..Comments::default()
+                },
+                code: Vec::new(),
+            }),
+            Token::Punct('{') if !in_body => {
+                in_body = true;
+                inline = Some(Inline::Function);
+            }
+            Token::Punct('}') if in_body && code_start.is_none() => {
+                in_body = false;
+                inline = None;
+                // The lines at the end of the body stay with the last label or instruction
+                if let Some(last) = functions.last_mut().and_then(|f| f.code.last_mut()) {
+                    last.after = std::mem::take(&mut pending);
+                }
+            }
+            _ if in_body => {
+                let start = *code_start.get_or_insert_with(|| {
+                    if let Some(f) = functions.last_mut() {
+                        f.code.push(Comments {
+                            before: std::mem::take(&mut pending),
+                            ..Comments::default()
+                        });
+                    }
+                    i
+                });
+                let is_label = matches!(tokens[start], Token::Word(w) if w.starts_with('.'))
+                    && i == start + 1
+                    && matches!(token, Token::Punct(':'));
+                if is_label || matches!(token, Token::Punct(';')) {
+                    code_start = None;
+                    inline = Some(Inline::Code);
+                }
+            }
+            _ => {}
+        }
+        line_is_empty = false;
+    }
+
+    // Whatever is left over comes after the last function, except for blank lines at the very end
+    while pending.last().is_some_and(String::is_empty) {
+        pending.pop();
+    }
+    if let Some(f) = functions.last_mut() {
+        f.comments.after = pending;
+    } else if let Some(import) = imports.last_mut() {
+        import.after = pending;
+    }
+    (imports, functions)
+}
+
+fn some_if_any(comments: Comments) -> Option<Comments> {
+    (comments != Comments::default()).then_some(comments)
+}
+
+/// Fills in the comments of ```program``` from the Bril text in ```text``` which it was parsed from, so that they are kept when it is printed again
+pub fn attach_comments(program: &mut AbstractProgram, text: &str) {
+    let (imports, functions) = find_comments(text);
+    for (import, found) in program.imports.iter_mut().zip(imports) {
+        import.comments = some_if_any(found);
+    }
+    for (func, found) in program.functions.iter_mut().zip(functions) {
+        func.comments = some_if_any(found.comments);
+        for (code, found) in func.instrs.iter_mut().zip(found.code) {
+            match code {
+                AbstractCode::Label { comments, .. }
+                | AbstractCode::Instruction(
+                    AbstractInstruction::Constant { comments, .. }
+                    | AbstractInstruction::Value { comments, .. }
+                    | AbstractInstruction::Effect { comments, .. },
+                ) => *comments = some_if_any(found),
+            }
+        }
+    }
+}
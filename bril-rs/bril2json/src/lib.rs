@@ -8,20 +8,32 @@
 pub mod bril_grammar;
 #[doc(hidden)]
 pub mod cli;
-use bril_rs::{AbstractProgram, Position};
+mod comments;
+use std::fmt::Display;
+use std::path::Path;
+
+pub use comments::attach_comments;
+
+use bril_rs::{AbstractCode, AbstractFunction, AbstractProgram, ColRow, Position, Span};
+
+// Something that was parsed along with the offsets that it starts and ends at
+#[doc(hidden)]
+pub type Located<T> = (T, usize, usize);
 
 #[doc(hidden)]
 #[derive(Clone)]
 pub struct Lines {
     use_pos: bool,
-    new_lines: Vec<usize>,
+    newlines: Vec<usize>,
+    src: Option<String>,
 }
 
 impl Lines {
     fn new(input: &str, use_pos: bool) -> Self {
         Self {
             use_pos,
-            new_lines: input
+            src: None,
+            newlines: input
                 .as_bytes()
                 .iter()
                 .enumerate()
@@ -30,25 +42,37 @@ impl Lines {
         }
     }
 
-    fn get_position(&self, index: usize) -> Option<Position> {
-        if self.use_pos {
-            Some(self.new_lines.iter().enumerate().fold(
-                Position { col: 1, row: 0 },
-                |current, (line_num, idx)| {
-                    if *idx < index {
-                        Position {
-                            row: (line_num + 2) as u64,
-                            col: (index - idx) as u64,
-                        }
-                    } else {
-                        current
-                    }
-                },
-            ))
+    // Binary searches for the line that ```index``` is on
+    fn get_col_row(&self, index: usize) -> ColRow {
+        let line = self.newlines.partition_point(|idx| *idx < index);
+        let line_start = if line == 0 {
+            0
         } else {
-            None
+            self.newlines[line - 1] + 1
+        };
+        ColRow {
+            row: (line + 1) as u64,
+            col: (index - line_start + 1) as u64,
+        }
+    }
+
+    fn get_span(&self, start: usize, end: usize) -> Span {
+        Span {
+            pos: self.get_col_row(start),
+            pos_end: self.get_col_row(end),
         }
     }
+
+    fn get_position(&self, start: usize, end: usize) -> Option<Position> {
+        self.use_pos.then(|| {
+            let Span { pos, pos_end } = self.get_span(start, end);
+            Position {
+                pos_end: Some(pos_end),
+                src: self.src.clone(),
+                ..Position::new(pos)
+            }
+        })
+    }
 }
 
 /// The entrance point to the bril2json parser. It takes an ```input```:[`std::io::Read`] which should be the Bril text file. You can control whether it includes source code positions with ```use_pos```.
@@ -66,6 +90,130 @@ pub fn parse_abstract_program_from_read<R: std::io::Read>(
         .unwrap()
 }
 
+/// Why some Bril text could not be parsed, along with where in the text the problem is as byte offsets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// The offset of the start of the problem
+    pub start: usize,
+    /// The offset of the end of the problem, which is the same as ```start``` when there is no token to point at
+    pub end: usize,
+    /// What went wrong
+    pub message: String,
+}
+
+impl ParseError {
+    /// The line and column that the problem starts at in ```text```, which it was found in, counting from 1
+    #[must_use]
+    pub fn line_col(&self, text: &str) -> (usize, usize) {
+        let before = &text[..self.start.min(text.len())];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (
+            before.matches('\n').count() + 1,
+            before[line_start..].chars().count() + 1,
+        )
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<lalrpop_util::ParseError<usize, bril_grammar::Token<'_>, &str>> for ParseError {
+    fn from(e: lalrpop_util::ParseError<usize, bril_grammar::Token<'_>, &str>) -> Self {
+        use lalrpop_util::ParseError::{
+            ExtraToken, InvalidToken, UnrecognizedEOF, UnrecognizedToken, User,
+        };
+        let (start, end, message) = match e {
+            InvalidToken { location } => (location, location, "invalid token".to_string()),
+            UnrecognizedEOF { location, .. } => {
+                (location, location, "unexpected end of input".to_string())
+            }
+            UnrecognizedToken {
+                token: (start, token, end),
+                ..
+            }
+            | ExtraToken {
+                token: (start, token, end),
+            } => (start, end, format!("unexpected `{token}`")),
+            User { error } => (0, 0, error.to_string()),
+        };
+        Self {
+            start,
+            end,
+            message,
+        }
+    }
+}
+
+/// Like [`parse_abstract_program_from_read`] but for text which may not be well-formed, such as a file that is being edited
+/// # Errors
+/// The error describes where ```input``` stopped being well-formed Bril text
+pub fn parse_abstract_program_from_str(
+    input: &str,
+    use_pos: bool,
+) -> Result<AbstractProgram, ParseError> {
+    bril_grammar::AbstractProgramParser::new()
+        .parse(&Lines::new(input, use_pos), input)
+        .map_err(ParseError::from)
+}
+
+/// Like [`parse_abstract_program_from_str`] but the positions also record ```src```, the file that ```input``` was read from
+/// # Errors
+/// The error describes where ```input``` stopped being well-formed Bril text
+pub fn parse_abstract_program_from_src(
+    input: &str,
+    use_pos: bool,
+    src: &str,
+) -> Result<AbstractProgram, ParseError> {
+    let lines = Lines {
+        src: Some(src.to_string()),
+        ..Lines::new(input, use_pos)
+    };
+    bril_grammar::AbstractProgramParser::new()
+        .parse(&lines, input)
+        .map_err(ParseError::from)
+}
+
+/// Reads the program in the file at ```path```, which is Bril JSON if its extension is `.json` and Bril text otherwise.
+///
+/// Positions in Bril text record ```path``` as their `src`, and you can control whether it includes them with ```use_pos```.
+/// # Errors
+/// When the file can't be read or is not a well-formed program, with a message saying why
+pub fn load_abstract_program_from_file(
+    path: &Path,
+    use_pos: bool,
+) -> Result<AbstractProgram, String> {
+    let input = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str(&input).map_err(|e| e.to_string())
+    } else {
+        parse_abstract_program_from_src(&input, use_pos, &path.to_string_lossy())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Parses a single function definition out of ```input```, for when a program is built up a piece at a time as in a REPL. You can control whether it includes source code positions with ```use_pos```.
+/// # Errors
+/// The error describes where ```input``` stopped being well-formed Bril text
+pub fn parse_abstract_function(input: &str, use_pos: bool) -> Result<AbstractFunction, ParseError> {
+    bril_grammar::AbstractFunctionParser::new()
+        .parse(&Lines::new(input, use_pos), input)
+        .map_err(ParseError::from)
+}
+
+/// Parses a single instruction or label out of ```input```, for when a program is built up a piece at a time as in a REPL. You can control whether it includes source code positions with ```use_pos```.
+/// # Errors
+/// The error describes where ```input``` stopped being well-formed Bril text
+pub fn parse_abstract_code(input: &str, use_pos: bool) -> Result<AbstractCode, ParseError> {
+    bril_grammar::AbstractCodeParser::new()
+        .parse(&Lines::new(input, use_pos), input)
+        .map_err(ParseError::from)
+}
+
 #[must_use]
 /// A wrapper around [`parse_abstract_program_from_read`] which assumes [`std::io::Stdin`]
 pub fn parse_abstract_program(use_pos: bool) -> AbstractProgram {
@@ -1,9 +1,112 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
 use bril2json::cli::Cli;
-use bril2json::parse_abstract_program;
-use bril_rs::output_abstract_program;
+use bril2json::{
+    attach_comments, parse_abstract_program_from_src, parse_abstract_program_from_str,
+};
+use bril_rs::{write_abstract_program, write_program, AbstractProgram, Code, Instruction, Program};
 use clap::Parser;
 
+// Reads and parses the Bril text in ```path```, or stdin if there is none, exiting with where it is not well-formed if it isn't
+fn parse(path: Option<&Path>, use_pos: bool, comments: bool) -> AbstractProgram {
+    let mut text = String::new();
+    let (name, result) = match path {
+        Some(path) => {
+            let name = path.display().to_string();
+            text = std::fs::read_to_string(path).unwrap_or_else(|e| {
+                eprintln!("error: {name}: {e}");
+                std::process::exit(2)
+            });
+            let result = parse_abstract_program_from_src(&text, use_pos, &name);
+            (name, result)
+        }
+        None => {
+            std::io::stdin().read_to_string(&mut text).unwrap();
+            let result = parse_abstract_program_from_str(&text, use_pos);
+            ("<stdin>".to_string(), result)
+        }
+    };
+    let mut program = result.unwrap_or_else(|e| {
+        let (line, col) = e.line_col(&text);
+        eprintln!("error: {name}:{line}:{col}: {e}");
+        std::process::exit(2)
+    });
+    if comments {
+        attach_comments(&mut program, &text);
+    }
+    program
+}
+
+// Conversion errors can only say where they are with positions, so they are kept until the conversion is done
+fn remove_positions(program: &mut Program) {
+    for func in &mut program.functions {
+        func.pos = None;
+        for code in &mut func.instrs {
+            match code {
+                Code::Label { pos, .. }
+                | Code::Instruction(
+                    Instruction::Constant { pos, .. }
+                    | Instruction::Value { pos, .. }
+                    | Instruction::Effect { pos, .. },
+                ) => *pos = None,
+            }
+        }
+    }
+    for import in &mut program.imports {
+        import.pos = None;
+    }
+}
+
 fn main() {
     let args = Cli::parse();
-    output_abstract_program(&parse_abstract_program(args.position))
+    let paths: Vec<Option<&Path>> = if args.files.is_empty() {
+        vec![None]
+    } else {
+        args.files.iter().map(|f| Some(f.as_path())).collect()
+    };
+    let use_pos = args.position || args.check || args.concrete;
+
+    if args.check {
+        let mut failed = false;
+        for path in paths {
+            if let Err(e) = Program::try_from(parse(path, use_pos, false)) {
+                eprintln!("error: {e}");
+                failed = true;
+            }
+        }
+        std::process::exit(i32::from(failed))
+    }
+
+    let mut program = AbstractProgram {
+        functions: Vec::new(),
+        imports: Vec::new(),
+    };
+    for path in paths {
+        let p = parse(path, use_pos, args.comments);
+        program.imports.extend(p.imports);
+        program.functions.extend(p.functions);
+    }
+
+    let out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(File::create(path).unwrap_or_else(|e| {
+            eprintln!("error: {}: {e}", path.display());
+            std::process::exit(2)
+        })),
+        None => Box::new(std::io::stdout()),
+    };
+    let result = if args.concrete {
+        let mut program = Program::try_from(program).unwrap_or_else(|e| {
+            eprintln!("error: {e}");
+            std::process::exit(1)
+        });
+        if !args.position {
+            remove_positions(&mut program);
+        }
+        write_program(&program, out, args.compact)
+    } else {
+        write_abstract_program(&program, out, args.compact)
+    };
+    result.unwrap();
 }
@@ -0,0 +1,36 @@
+use bril_rs::ops::{Op, OPS};
+use bril_rs::{EffectOps, ValueOps};
+
+// Prints the table of operations in Markdown, which docs/tools/rust.md includes
+fn main() {
+    println!("| Operation | Arguments | Labels | Functions | Types | Extension |");
+    println!("| --- | --- | --- | --- | --- | --- |");
+    for info in OPS {
+        // The name in the table has to be what the operation is printed as and what the operation that has the name is written as in JSON
+        let (text, json) = match &info.op {
+            Op::Const(op) => (op.to_string(), serde_json::to_value(op)),
+            Op::Value(op) => (
+                op.to_string(),
+                serde_json::to_value(ValueOps::from_name(info.name)),
+            ),
+            Op::Effect(op) => (
+                op.to_string(),
+                serde_json::to_value(EffectOps::from_name(info.name)),
+            ),
+        };
+        let json = json.unwrap();
+        let name = if text == info.name && json == info.name {
+            format!("`{}`", info.name)
+        } else {
+            format!("`{}` is printed as `{text}` and read as {json}", info.name)
+        };
+        println!(
+            "| {name} | {} | {} | {} | `{}` | {} |",
+            info.args,
+            info.labels,
+            info.funcs,
+            info.signature,
+            info.feature.unwrap_or("core")
+        );
+    }
+}
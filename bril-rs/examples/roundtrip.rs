@@ -0,0 +1,6 @@
+use bril_rs::load_program;
+
+// Prints the program on stdin back out as text, which gets the name of each operation from the table of operations
+fn main() {
+    print!("{}", load_program());
+}
@@ -3,6 +3,8 @@ use std::marker::PhantomData;
 
 use crate::{program::Literal, ConstOps};
 
+#[cfg(feature = "import")]
+use crate::program::Import;
 #[cfg(feature = "position")]
 use crate::program::Position;
 
@@ -17,10 +19,18 @@ use serde::ser::{SerializeMap, Serializer};
 pub struct AbstractProgram {
     /// A list of functions declared in the program
     pub functions: Vec<AbstractFunction>,
+    /// The functions this program uses from other files
+    #[cfg(feature = "import")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub imports: Vec<Import>,
 }
 
 impl Display for AbstractProgram {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        #[cfg(feature = "import")]
+        for import in &self.imports {
+            writeln!(f, "{import}")?;
+        }
         for func in &self.functions {
             writeln!(f, "{func}")?;
         }
@@ -28,6 +38,54 @@ impl Display for AbstractProgram {
     }
 }
 
+/// The comments and blank lines around a function, label or instruction in Bril text, so that they survive a round trip through JSON. Tools that don't know about them can ignore them.
+#[cfg(feature = "comments")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct Comments {
+    /// The lines just before, each either a comment starting with `#` or an empty string for a blank line
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub before: Vec<String>,
+    /// The comment at the end of the line, starting with `#`. For a function, this is the line with its `{`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inline: Option<String>,
+    /// The lines just after, like ```before```. These are only kept on whatever comes last, which is the last label or instruction of a function or the last function of a program
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub after: Vec<String>,
+}
+
+#[cfg(feature = "comments")]
+impl Comments {
+    pub(crate) fn write_before(&self, f: &mut Formatter<'_>, indent: &str) -> fmt::Result {
+        for line in &self.before {
+            write_line(f, indent, line)?;
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn write_inline(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.inline.as_ref().map_or(Ok(()), |c| write!(f, " {c}"))
+    }
+
+    pub(crate) fn write_after(&self, f: &mut Formatter<'_>, indent: &str) -> fmt::Result {
+        for line in &self.after {
+            writeln!(f)?;
+            write_line(f, indent, line)?;
+        }
+        Ok(())
+    }
+}
+
+// Blank lines are left empty rather than indented
+#[cfg(feature = "comments")]
+fn write_line(f: &mut Formatter<'_>, indent: &str, line: &str) -> fmt::Result {
+    if line.is_empty() {
+        Ok(())
+    } else {
+        write!(f, "{indent}{line}")
+    }
+}
+
 /// <https://capra.cs.cornell.edu/bril/lang/syntax.html#function>
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AbstractFunction {
@@ -41,16 +99,24 @@ pub struct AbstractFunction {
     pub name: String,
     /// The position of this function in the original source code
     #[cfg(feature = "position")]
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
     pub pos: Option<Position>,
     /// The possible return type of this function
     #[serde(rename = "type")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub return_type: Option<AbstractType>,
+    /// The comments around this function in the original source code
+    #[cfg(feature = "comments")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comments: Option<Comments>,
 }
 
 impl Display for AbstractFunction {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        #[cfg(feature = "comments")]
+        if let Some(c) = &self.comments {
+            c.write_before(f, "")?;
+        }
         write!(f, "@{}", self.name)?;
         if !self.args.is_empty() {
             write!(f, "(")?;
@@ -65,11 +131,20 @@ impl Display for AbstractFunction {
         if let Some(tpe) = self.return_type.as_ref() {
             write!(f, ": {tpe}")?;
         }
-        writeln!(f, " {{")?;
+        write!(f, " {{")?;
+        #[cfg(feature = "comments")]
+        if let Some(c) = &self.comments {
+            c.write_inline(f)?;
+        }
+        writeln!(f)?;
         for instr in &self.instrs {
             writeln!(f, "{instr}")?;
         }
         write!(f, "}}")?;
+        #[cfg(feature = "comments")]
+        if let Some(c) = &self.comments {
+            c.write_after(f, "")?;
+        }
         Ok(())
     }
 }
@@ -103,23 +178,55 @@ pub enum AbstractCode {
         label: String,
         /// Where the label is located in source code
         #[cfg(feature = "position")]
-        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(flatten, skip_serializing_if = "Option::is_none")]
         pos: Option<Position>,
+        /// The comments around the label in source code
+        #[cfg(feature = "comments")]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        comments: Option<Comments>,
     },
     /// <https://capra.cs.cornell.edu/bril/lang/syntax.html#instruction>
     Instruction(AbstractInstruction),
 }
 
+impl AbstractCode {
+    /// The comments around this label or instruction in source code
+    #[cfg(feature = "comments")]
+    #[must_use]
+    pub const fn comments(&self) -> Option<&Comments> {
+        match self {
+            Self::Label { comments, .. }
+            | Self::Instruction(
+                AbstractInstruction::Constant { comments, .. }
+                | AbstractInstruction::Value { comments, .. }
+                | AbstractInstruction::Effect { comments, .. },
+            ) => comments.as_ref(),
+        }
+    }
+}
+
 impl Display for AbstractCode {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // Comments line up with the label or instruction they come before
+        #[cfg(feature = "comments")]
+        if let Some(c) = self.comments() {
+            let indent = match self {
+                Self::Label { .. } => "",
+                Self::Instruction(_) => "  ",
+            };
+            c.write_before(f, indent)?;
+        }
         match self {
-            AbstractCode::Label {
-                label,
-                #[cfg(feature = "position")]
-                    pos: _,
-            } => write!(f, ".{label}:"),
-            AbstractCode::Instruction(instr) => write!(f, "  {instr}"),
+            Self::Label { label, .. } => write!(f, ".{label}:")?,
+            Self::Instruction(instr) => write!(f, "  {instr}")?,
         }
+        // The lines after the last label or instruction are still inside of the function
+        #[cfg(feature = "comments")]
+        if let Some(c) = self.comments() {
+            c.write_inline(f)?;
+            c.write_after(f, "  ")?;
+        }
+        Ok(())
     }
 }
 
@@ -135,8 +242,12 @@ pub enum AbstractInstruction {
         op: ConstOps,
         /// The source position of the instruction if provided
         #[cfg(feature = "position")]
-        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(flatten, skip_serializing_if = "Option::is_none")]
         pos: Option<Position>,
+        /// The comments around the instruction in source code
+        #[cfg(feature = "comments")]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        comments: Option<Comments>,
         /// Type of variable
         #[serde(rename = "type")]
         const_type: Option<AbstractType>,
@@ -160,8 +271,12 @@ pub enum AbstractInstruction {
         op: String,
         /// The source position of the instruction if provided
         #[cfg(feature = "position")]
-        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(flatten, skip_serializing_if = "Option::is_none")]
         pos: Option<Position>,
+        /// The comments around the instruction in source code
+        #[cfg(feature = "comments")]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        comments: Option<Comments>,
         /// Type of variable
         #[serde(rename = "type")]
         op_type: Option<AbstractType>,
@@ -181,8 +296,12 @@ pub enum AbstractInstruction {
         op: String,
         /// The source position of the instruction if provided
         #[cfg(feature = "position")]
-        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(flatten, skip_serializing_if = "Option::is_none")]
         pos: Option<Position>,
+        /// The comments around the instruction in source code
+        #[cfg(feature = "comments")]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        comments: Option<Comments>,
     },
 }
 
@@ -194,8 +313,7 @@ impl Display for AbstractInstruction {
                 dest,
                 const_type,
                 value,
-                #[cfg(feature = "position")]
-                    pos: _,
+                ..
             } => match const_type {
                 Some(const_type) => write!(f, "{dest}: {const_type} = {op} {value};"),
                 None => write!(f, "{dest} = {op} {value};"),
@@ -207,8 +325,7 @@ impl Display for AbstractInstruction {
                 args,
                 funcs,
                 labels,
-                #[cfg(feature = "position")]
-                    pos: _,
+                ..
             } => {
                 match op_type {
                     Some(op_type) => write!(f, "{dest}: {op_type} = {op}")?,
@@ -230,8 +347,7 @@ impl Display for AbstractInstruction {
                 args,
                 funcs,
                 labels,
-                #[cfg(feature = "position")]
-                    pos: _,
+                ..
             } => {
                 write!(f, "{op}")?;
                 for func in funcs {
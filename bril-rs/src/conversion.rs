@@ -48,7 +48,7 @@ impl ConversionError {
             //Self::PositionalConversionErrorConversion(e) => e,
             _ => PositionalConversionError {
                 e: Box::new(self),
-                pos: pos_var,
+                pos: pos_var.map(Box::new),
             },
         }
     }
@@ -58,7 +58,7 @@ impl ConversionError {
 #[derive(Error, Debug)]
 pub struct PositionalConversionError {
     e: Box<ConversionError>,
-    pos: Option<Position>,
+    pos: Option<Box<Position>>,
 }
 
 impl PositionalConversionError {
@@ -70,6 +70,18 @@ impl PositionalConversionError {
             pos: None,
         }
     }
+
+    /// The error without its position
+    #[must_use]
+    pub fn error(&self) -> &ConversionError {
+        &self.e
+    }
+
+    /// Where in the source the error is, if it is known
+    #[must_use]
+    pub fn pos(&self) -> Option<&Position> {
+        self.pos.as_deref()
+    }
 }
 
 impl Display for PositionalConversionError {
@@ -77,7 +89,7 @@ impl Display for PositionalConversionError {
         match self {
             #[cfg(feature = "position")]
             PositionalConversionError { e, pos: Some(pos) } => {
-                write!(f, "Line {}, Column {}: {e}", pos.row, pos.col)
+                write!(f, "{pos}: {e}")
             }
             #[cfg(not(feature = "position"))]
             PositionalConversionError { e: _, pos: Some(_) } => {
@@ -90,12 +102,20 @@ impl Display for PositionalConversionError {
 
 impl TryFrom<AbstractProgram> for Program {
     type Error = PositionalConversionError;
-    fn try_from(AbstractProgram { functions }: AbstractProgram) -> Result<Self, Self::Error> {
+    fn try_from(
+        AbstractProgram {
+            functions,
+            #[cfg(feature = "import")]
+            imports,
+        }: AbstractProgram,
+    ) -> Result<Self, Self::Error> {
         Ok(Self {
             functions: functions
                 .into_iter()
                 .map(std::convert::TryInto::try_into)
                 .collect::<Result<Vec<Function>, _>>()?,
+            #[cfg(feature = "import")]
+            imports,
         })
     }
 }
@@ -110,14 +130,19 @@ impl TryFrom<AbstractFunction> for Function {
             return_type,
             #[cfg(feature = "position")]
             pos,
+            #[cfg(feature = "comments")]
+                comments: _,
         }: AbstractFunction,
     ) -> Result<Self, Self::Error> {
         Ok(Self {
             args: args
                 .into_iter()
-                .map(std::convert::TryInto::try_into)
-                .collect::<Result<Vec<Argument>, _>>()
-                .map_err(|e| e.add_pos(pos))?,
+                .enumerate()
+                .map(|(i, a)| {
+                    a.try_into()
+                        .map_err(|e: ConversionError| e.add_pos(pos.as_ref().map(|p| p.arg(i))))
+                })
+                .collect::<Result<Vec<Argument>, _>>()?,
             instrs: instrs
                 .into_iter()
                 .map(std::convert::TryInto::try_into)
@@ -125,7 +150,10 @@ impl TryFrom<AbstractFunction> for Function {
             name,
             return_type: match return_type {
                 None => None,
-                Some(t) => Some(t.try_into().map_err(|e: ConversionError| e.add_pos(pos))?),
+                Some(t) => Some(
+                    t.try_into()
+                        .map_err(|e: ConversionError| e.add_pos(pos.as_ref().map(Position::typ)))?,
+                ),
             },
             #[cfg(feature = "position")]
             pos,
@@ -153,6 +181,8 @@ impl TryFrom<AbstractCode> for Code {
                 label,
                 #[cfg(feature = "position")]
                 pos,
+                #[cfg(feature = "comments")]
+                    comments: _,
             } => Self::Label {
                 label,
                 #[cfg(feature = "position")]
@@ -174,12 +204,14 @@ impl TryFrom<AbstractInstruction> for Instruction {
                 value,
                 #[cfg(feature = "position")]
                 pos,
+                #[cfg(feature = "comments")]
+                    comments: _,
             } => Self::Constant {
                 dest,
                 op,
                 const_type: const_type
                     .try_into()
-                    .map_err(|e: ConversionError| e.add_pos(pos))?,
+                    .map_err(|e: ConversionError| e.add_pos(pos.as_ref().map(Position::typ)))?,
                 value,
                 #[cfg(feature = "position")]
                 pos,
@@ -193,6 +225,8 @@ impl TryFrom<AbstractInstruction> for Instruction {
                 op_type,
                 #[cfg(feature = "position")]
                 pos,
+                #[cfg(feature = "comments")]
+                    comments: _,
             } => Self::Value {
                 args,
                 dest,
@@ -200,55 +234,19 @@ impl TryFrom<AbstractInstruction> for Instruction {
                 labels,
                 op_type: op_type
                     .try_into()
-                    .map_err(|e: ConversionError| e.add_pos(pos))?,
+                    .map_err(|e: ConversionError| e.add_pos(pos.as_ref().map(Position::typ)))?,
+                op: {
+                    // Operations that aren't known are kept by name when passing them through
+                    #[cfg(feature = "passthrough")]
+                    let op = ValueOps::from_name(&op)
+                        .unwrap_or_else(|| ValueOps::Other(crate::OpName::new(&op)));
+                    #[cfg(not(feature = "passthrough"))]
+                    let op = ValueOps::from_name(&op)
+                        .ok_or_else(|| ConversionError::InvalidValueOps(op).add_pos(pos.clone()))?;
+                    op
+                },
                 #[cfg(feature = "position")]
                 pos,
-                op: match op.as_ref() {
-                    "add" => ValueOps::Add,
-                    "mul" => ValueOps::Mul,
-                    "div" => ValueOps::Div,
-                    "eq" => ValueOps::Eq,
-                    "lt" => ValueOps::Lt,
-                    "gt" => ValueOps::Gt,
-                    "le" => ValueOps::Le,
-                    "ge" => ValueOps::Ge,
-                    "not" => ValueOps::Not,
-                    "and" => ValueOps::And,
-                    "or" => ValueOps::Or,
-                    "call" => ValueOps::Call,
-                    "id" => ValueOps::Id,
-                    "sub" => ValueOps::Sub,
-                    #[cfg(feature = "ssa")]
-                    "phi" => ValueOps::Phi,
-                    #[cfg(feature = "float")]
-                    "fadd" => ValueOps::Fadd,
-                    #[cfg(feature = "float")]
-                    "fsub" => ValueOps::Fsub,
-                    #[cfg(feature = "float")]
-                    "fmul" => ValueOps::Fmul,
-                    #[cfg(feature = "float")]
-                    "fdiv" => ValueOps::Fdiv,
-                    #[cfg(feature = "float")]
-                    "feq" => ValueOps::Feq,
-                    #[cfg(feature = "float")]
-                    "flt" => ValueOps::Flt,
-                    #[cfg(feature = "float")]
-                    "fgt" => ValueOps::Fgt,
-                    #[cfg(feature = "float")]
-                    "fle" => ValueOps::Fle,
-                    #[cfg(feature = "float")]
-                    "fge" => ValueOps::Fge,
-                    #[cfg(feature = "memory")]
-                    "alloc" => ValueOps::Alloc,
-                    #[cfg(feature = "memory")]
-                    "load" => ValueOps::Load,
-                    #[cfg(feature = "memory")]
-                    "ptradd" => ValueOps::PtrAdd,
-                    v => {
-                        return Err(ConversionError::InvalidValueOps(v.to_string()))
-                            .map_err(|e| e.add_pos(pos))
-                    }
-                },
             },
             AbstractInstruction::Effect {
                 args,
@@ -257,34 +255,25 @@ impl TryFrom<AbstractInstruction> for Instruction {
                 op,
                 #[cfg(feature = "position")]
                 pos,
+                #[cfg(feature = "comments")]
+                    comments: _,
             } => Self::Effect {
                 args,
                 funcs,
                 labels,
+                op: {
+                    // Operations that aren't known are kept by name when passing them through
+                    #[cfg(feature = "passthrough")]
+                    let op = EffectOps::from_name(&op)
+                        .unwrap_or_else(|| EffectOps::Other(crate::OpName::new(&op)));
+                    #[cfg(not(feature = "passthrough"))]
+                    let op = EffectOps::from_name(&op).ok_or_else(|| {
+                        ConversionError::InvalidEffectOps(op).add_pos(pos.clone())
+                    })?;
+                    op
+                },
                 #[cfg(feature = "position")]
                 pos,
-                op: match op.as_ref() {
-                    "jmp" => EffectOps::Jump,
-                    "br" => EffectOps::Branch,
-                    "call" => EffectOps::Call,
-                    "ret" => EffectOps::Return,
-                    "print" => EffectOps::Print,
-                    "nop" => EffectOps::Nop,
-                    #[cfg(feature = "memory")]
-                    "store" => EffectOps::Store,
-                    #[cfg(feature = "memory")]
-                    "free" => EffectOps::Free,
-                    #[cfg(feature = "speculate")]
-                    "speculate" => EffectOps::Speculate,
-                    #[cfg(feature = "speculate")]
-                    "commit" => EffectOps::Commit,
-                    #[cfg(feature = "speculate")]
-                    "guard" => EffectOps::Guard,
-                    e => {
-                        return Err(ConversionError::InvalidEffectOps(e.to_string()))
-                            .map_err(|e| e.add_pos(pos))
-                    }
-                },
             },
         })
     }
@@ -0,0 +1,33 @@
+/// Finds which nodes of a graph dominate each other
+///
+/// Node `0` is the entry and ```predecessors[b]``` are the nodes with an edge to `b`. Then `dominators(predecessors)[b][d]` is true when every path from the entry to `b` goes through `d`. Nodes that are unreachable from the entry are treated as being dominated by every node.
+#[must_use]
+pub fn dominators(predecessors: &[Vec<usize>]) -> Vec<Vec<bool>> {
+    let num_blocks = predecessors.len();
+    let mut doms = vec![vec![true; num_blocks]; num_blocks];
+    if num_blocks == 0 {
+        return doms;
+    }
+    doms[0] = vec![false; num_blocks];
+    doms[0][0] = true;
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for b in 1..num_blocks {
+            let mut new_dom = vec![true; num_blocks];
+            for p in &predecessors[b] {
+                new_dom
+                    .iter_mut()
+                    .zip(doms[*p].iter())
+                    .for_each(|(d, p_d)| *d &= *p_d);
+            }
+            new_dom[b] = true;
+            if new_dom != doms[b] {
+                doms[b] = new_dom;
+                changed = true;
+            }
+        }
+    }
+    doms
+}
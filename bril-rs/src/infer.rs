@@ -0,0 +1,425 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use thiserror::Error;
+
+use crate::ops::{self, Ty, OPS};
+use crate::{
+    AbstractCode, AbstractFunction, AbstractInstruction, AbstractProgram, AbstractType, Literal,
+    Position,
+};
+
+/// The errors from filling in types with [`infer_types`]
+// Having the #[error(...)] for all variants derives the Display trait as well
+#[derive(Error, Debug)]
+#[allow(clippy::module_name_repetitions)]
+pub enum InferError {
+    /// Expected `{0}` to have type {1}, found {2}
+    #[error("Expected `{0}` to have type {1}, found {2}")]
+    Conflict(String, AbstractType, AbstractType),
+
+    /// Could not infer the type of `{0}`
+    #[error("Could not infer the type of `{0}`")]
+    Unknown(String),
+}
+
+impl InferError {
+    #[doc(hidden)]
+    #[must_use]
+    pub fn add_pos(self, pos_var: Option<Position>) -> PositionalInferError {
+        PositionalInferError {
+            e: Box::new(self),
+            pos: pos_var.map(Box::new),
+        }
+    }
+}
+
+/// Wraps [`InferError`] to optionally provide source code positions if they are available.
+#[derive(Error, Debug)]
+#[allow(clippy::module_name_repetitions)]
+pub struct PositionalInferError {
+    e: Box<InferError>,
+    pos: Option<Box<Position>>,
+}
+
+impl PositionalInferError {
+    /// The error without its position
+    #[must_use]
+    pub fn error(&self) -> &InferError {
+        &self.e
+    }
+
+    /// Where in the source the error is, if it is known
+    #[must_use]
+    pub fn pos(&self) -> Option<&Position> {
+        self.pos.as_deref()
+    }
+}
+
+impl Display for PositionalInferError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.pos {
+            Some(pos) => write!(f, "{pos}: {}", self.e),
+            None => write!(f, "{}", self.e),
+        }
+    }
+}
+
+fn primitive(name: &str) -> AbstractType {
+    AbstractType::Primitive(name.to_string())
+}
+
+fn ptr(t: &AbstractType) -> AbstractType {
+    AbstractType::Parameterized("ptr".to_string(), Box::new(t.clone()))
+}
+
+fn pointee(t: &AbstractType) -> Option<&AbstractType> {
+    match t {
+        AbstractType::Parameterized(p, t) if p == "ptr" => Some(t),
+        _ => None,
+    }
+}
+
+// The type that a literal has, except for integers which can also be floats
+fn literal_type(value: &Literal) -> Option<AbstractType> {
+    match value {
+        Literal::Int(_) => None,
+        Literal::Bool(_) => Some(primitive("bool")),
+        #[cfg(feature = "float")]
+        Literal::Float(_) => Some(primitive("float")),
+    }
+}
+
+// A variable of an instruction
+#[derive(Clone, Copy)]
+enum Slot {
+    Dest,
+    Arg(usize),
+}
+
+// What an instruction says about the types of its variables
+enum Rule {
+    Is(Slot, AbstractType),
+    Same(Slot, Slot),
+    // The first is a pointer to the second
+    PtrTo(Slot, Slot),
+}
+
+struct Signature {
+    args: Vec<AbstractType>,
+    return_type: Option<AbstractType>,
+}
+
+// The rules of a value or effect operation, which come from its signature in the table of operations and from the signatures of the functions in the program for calls
+fn rules(
+    op: &str,
+    args: &[String],
+    funcs: &[String],
+    signatures: &HashMap<String, Signature>,
+    return_type: Option<&AbstractType>,
+) -> Vec<Rule> {
+    let Some(info) = OPS.iter().find(|info| info.name == op) else {
+        return Vec::new();
+    };
+    match info.signature {
+        ops::Signature::Fixed {
+            args: types,
+            rest,
+            result,
+        } => {
+            let slots = types
+                .iter()
+                .chain(rest.iter().cycle())
+                .zip(0..args.len())
+                .map(|(ty, i)| (Slot::Arg(i), *ty))
+                .chain(result.map(|ty| (Slot::Dest, ty)));
+            // Every `T` is the same type as the first one, and likewise for `ptr<T>`
+            let mut rules = Vec::new();
+            let (mut t, mut ptr_t) = (None, None);
+            for (slot, ty) in slots {
+                let first = match ty {
+                    Ty::T => &mut t,
+                    Ty::PtrT => &mut ptr_t,
+                    Ty::Any => continue,
+                    _ => {
+                        rules.push(Rule::Is(slot, primitive(&ty.to_string())));
+                        continue;
+                    }
+                };
+                match *first {
+                    Some(first) => rules.push(Rule::Same(first, slot)),
+                    None => *first = Some(slot),
+                }
+            }
+            if let (Some(ptr_t), Some(t)) = (ptr_t, t) {
+                rules.push(Rule::PtrTo(ptr_t, t));
+            }
+            rules
+        }
+        ops::Signature::Call { .. } => funcs
+            .first()
+            .and_then(|f| signatures.get(f))
+            .map(|sig| {
+                sig.args
+                    .iter()
+                    .enumerate()
+                    .map(|(i, t)| Rule::Is(Slot::Arg(i), t.clone()))
+                    .chain(
+                        sig.return_type
+                            .iter()
+                            .map(|t| Rule::Is(Slot::Dest, t.clone())),
+                    )
+                    .collect()
+            })
+            .unwrap_or_default(),
+        ops::Signature::Return => return_type
+            .map(|t| Rule::Is(Slot::Arg(0), t.clone()))
+            .into_iter()
+            .collect(),
+    }
+}
+
+// The types of the variables of a function. Variables have one type throughout a function, so what is learnt about them in one block flows into every other block that uses them
+#[derive(Default)]
+struct Env {
+    types: HashMap<String, AbstractType>,
+    changed: bool,
+}
+
+impl Env {
+    // Records that ```var``` has type ```typ```, which it must not already have a different type than
+    fn learn(&mut self, var: &str, typ: &AbstractType) -> Result<(), InferError> {
+        match self.types.get(var) {
+            Some(t) if t == typ => Ok(()),
+            Some(t) => Err(InferError::Conflict(
+                var.to_string(),
+                typ.clone(),
+                t.clone(),
+            )),
+            None => {
+                self.types.insert(var.to_string(), typ.clone());
+                self.changed = true;
+                Ok(())
+            }
+        }
+    }
+
+    fn apply(
+        &mut self,
+        rule: &Rule,
+        dest: Option<&str>,
+        args: &[String],
+        pos_var: Option<&Position>,
+    ) -> Result<(), PositionalInferError> {
+        let var = |slot| match slot {
+            Slot::Dest => dest,
+            Slot::Arg(i) => args.get(i).map(String::as_str),
+        };
+        // Errors point at the argument they are about
+        let learn = |env: &mut Self, slot, typ: &AbstractType| {
+            var(slot).map_or(Ok(()), |v| {
+                env.learn(v, typ).map_err(|e| {
+                    e.add_pos(match slot {
+                        Slot::Dest => pos_var.cloned(),
+                        Slot::Arg(i) => pos_var.map(|p| p.arg(i)),
+                    })
+                })
+            })
+        };
+        let known = |env: &Self, slot| var(slot).and_then(|v| env.types.get(v)).cloned();
+        match rule {
+            Rule::Is(slot, typ) => learn(self, *slot, typ),
+            Rule::Same(a, b) => match (known(self, *a), known(self, *b)) {
+                (Some(t), _) => learn(self, *b, &t),
+                (None, Some(t)) => learn(self, *a, &t),
+                (None, None) => Ok(()),
+            },
+            Rule::PtrTo(p, v) => {
+                if let Some(t) = known(self, *p).as_ref().and_then(pointee) {
+                    learn(self, *v, t)?;
+                }
+                known(self, *v).map_or(Ok(()), |t| learn(self, *p, &ptr(&t)))
+            }
+        }
+    }
+
+    // Goes over the instructions of ```func``` until nothing more can be learnt from them
+    fn propagate(
+        &mut self,
+        func: &AbstractFunction,
+        signatures: &HashMap<String, Signature>,
+    ) -> Result<(), PositionalInferError> {
+        loop {
+            self.changed = false;
+            for code in &func.instrs {
+                if let AbstractCode::Instruction(instr) = code {
+                    let (dest, args, funcs, op) = match instr {
+                        AbstractInstruction::Value {
+                            dest,
+                            args,
+                            funcs,
+                            op,
+                            ..
+                        } => (Some(dest.as_str()), args, funcs, op),
+                        AbstractInstruction::Effect {
+                            args, funcs, op, ..
+                        } => (None, args, funcs, op),
+                        AbstractInstruction::Constant { .. } => continue,
+                    };
+                    let pos_var = instr_pos(instr);
+                    for rule in rules(op, args, funcs, signatures, func.return_type.as_ref()) {
+                        self.apply(&rule, dest, args, pos_var.as_ref())?;
+                    }
+                }
+            }
+            if !self.changed {
+                return Ok(());
+            }
+        }
+    }
+}
+
+// The type of each instruction's destination, from its annotation or else from its literal when that can only have one type
+fn dest_type(instr: &AbstractInstruction) -> Option<(&str, AbstractType)> {
+    match instr {
+        AbstractInstruction::Constant {
+            dest,
+            const_type,
+            value,
+            ..
+        } => const_type
+            .clone()
+            .or_else(|| literal_type(value))
+            .map(|t| (dest.as_str(), t)),
+        AbstractInstruction::Value { dest, op_type, .. } => {
+            op_type.clone().map(|t| (dest.as_str(), t))
+        }
+        AbstractInstruction::Effect { .. } => None,
+    }
+}
+
+#[cfg(feature = "position")]
+fn instr_pos(instr: &AbstractInstruction) -> Option<Position> {
+    match instr {
+        AbstractInstruction::Constant { pos, .. }
+        | AbstractInstruction::Value { pos, .. }
+        | AbstractInstruction::Effect { pos, .. } => pos.clone(),
+    }
+}
+
+#[cfg(not(feature = "position"))]
+const fn instr_pos(_: &AbstractInstruction) -> Option<Position> {
+    None
+}
+
+#[cfg(feature = "position")]
+fn arg_pos(func: &AbstractFunction, i: usize) -> Option<Position> {
+    func.pos.as_ref().map(|p| p.arg(i))
+}
+
+#[cfg(not(feature = "position"))]
+const fn arg_pos(_: &AbstractFunction, _: usize) -> Option<Position> {
+    None
+}
+
+fn is_missing_types(func: &AbstractFunction) -> bool {
+    func.instrs.iter().any(|code| {
+        matches!(
+            code,
+            AbstractCode::Instruction(
+                AbstractInstruction::Constant {
+                    const_type: None,
+                    ..
+                } | AbstractInstruction::Value { op_type: None, .. }
+            )
+        )
+    })
+}
+
+fn infer_function(
+    func: &mut AbstractFunction,
+    signatures: &HashMap<String, Signature>,
+) -> Result<(), PositionalInferError> {
+    let mut env = Env::default();
+    for (i, arg) in func.args.iter().enumerate() {
+        env.learn(&arg.name, &arg.arg_type)
+            .map_err(|e| e.add_pos(arg_pos(func, i)))?;
+    }
+    // What is written down comes first so that conflicts are blamed on what was inferred
+    let instrs = func.instrs.iter().filter_map(|code| match code {
+        AbstractCode::Instruction(instr) => Some(instr),
+        AbstractCode::Label { .. } => None,
+    });
+    for instr in instrs.clone() {
+        if let Some((dest, t)) = dest_type(instr) {
+            env.learn(dest, &t)
+                .map_err(|e| e.add_pos(instr_pos(instr)))?;
+        }
+    }
+    env.propagate(func, signatures)?;
+
+    // Integer constants are ints unless they are used as floats
+    for instr in instrs {
+        if let AbstractInstruction::Constant {
+            dest,
+            const_type: None,
+            value: Literal::Int(_),
+            ..
+        } = instr
+        {
+            if !env.types.contains_key(dest) {
+                env.learn(dest, &primitive("int"))
+                    .map_err(|e| e.add_pos(instr_pos(instr)))?;
+            }
+        }
+    }
+    env.propagate(func, signatures)?;
+
+    for code in &mut func.instrs {
+        if let AbstractCode::Instruction(instr) = code {
+            let pos_var = instr_pos(instr);
+            if let AbstractInstruction::Constant {
+                dest,
+                const_type: typ @ None,
+                ..
+            }
+            | AbstractInstruction::Value {
+                dest,
+                op_type: typ @ None,
+                ..
+            } = instr
+            {
+                match env.types.get(dest.as_str()) {
+                    Some(t) => *typ = Some(t.clone()),
+                    None => return Err(InferError::Unknown(dest.clone()).add_pos(pos_var)),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fills in the types that are missing from the instructions of ```program```, so that it can be converted into a [`crate::Program`].
+///
+/// Types are learnt from the signatures of functions, the types that are written down, the literals of constants, and the operations that use each variable, and are passed between blocks and through phi nodes until nothing more can be learnt. Like the type checker of brilirs, each variable has one type throughout its function. Functions that already have all of their types are left for the type checker.
+/// # Errors
+/// When a variable would need two different types, or the type of a variable can't be worked out
+pub fn infer_types(program: &mut AbstractProgram) -> Result<(), PositionalInferError> {
+    let signatures: HashMap<String, Signature> = program
+        .functions
+        .iter()
+        .map(|f| {
+            (
+                f.name.clone(),
+                Signature {
+                    args: f.args.iter().map(|a| a.arg_type.clone()).collect(),
+                    return_type: f.return_type.clone(),
+                },
+            )
+        })
+        .collect();
+    for func in program.functions.iter_mut().filter(|f| is_missing_types(f)) {
+        infer_function(func, &signatures)?;
+    }
+    Ok(())
+}
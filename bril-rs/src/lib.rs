@@ -7,15 +7,25 @@
 pub mod abstract_program;
 /// Provides the Error handling and conversion between [`AbstractProgram`] and [Program]
 pub mod conversion;
+/// Provides [`graph::dominators`] for the control flow graphs that tools build out of functions
+pub mod graph;
+/// Provides [`infer::infer_types`] to fill in the types that are missing from an [`AbstractProgram`]
+pub mod infer;
+/// Provides [`link::link`] to put programs that import functions from other files together into one
+#[cfg(feature = "import")]
+pub mod link;
+/// Provides [`ops::OPS`], the table of every operation along with how many arguments, labels, and functions it takes
+pub mod ops;
 /// Provides the structured representation of Bril programs
 pub mod program;
+/// Provides [`wellformed::check_well_formed`] to find every way that a [Program] breaks the rules of well-formed Bril
+pub mod wellformed;
 // todo maybe not reexport the program structs? I don't know the most rust way to provide these especially since abstract_program relies on Literal in program
 pub use abstract_program::*;
 pub use program::*;
 
 use std::io::{self, Write};
 
-// todo Have versions of the output_* functions that take a [std::io::Write]
 // todo possible deprecate/remove the wrapper functions to make the code base cleaner
 // todo Wrap the outputs of serde in an error instead of panic-ing
 
@@ -42,6 +52,37 @@ pub fn output_program(p: &Program) {
     io::stdout().write_all(b"\n").unwrap();
 }
 
+/// Writes a [Program] to ```out``` as JSON, all on one line if ```compact``` and pretty printed otherwise
+/// # Errors
+/// When writing to ```out``` fails
+pub fn write_program<W: Write>(p: &Program, out: W, compact: bool) -> io::Result<()> {
+    write_json(p, out, compact)
+}
+
+/// Like [`write_program`] but for an [`AbstractProgram`]
+/// # Errors
+/// When writing to ```out``` fails
+pub fn write_abstract_program<W: Write>(
+    p: &AbstractProgram,
+    out: W,
+    compact: bool,
+) -> io::Result<()> {
+    write_json(p, out, compact)
+}
+
+fn write_json<T: serde::Serialize, W: Write>(
+    value: &T,
+    mut out: W,
+    compact: bool,
+) -> io::Result<()> {
+    if compact {
+        serde_json::to_writer(&mut out, value)?;
+    } else {
+        serde_json::to_writer_pretty(&mut out, value)?;
+    }
+    out.write_all(b"\n")
+}
+
 /// A helper function for parsing a Bril program from ```input``` in JSON format to [`AbstractProgram`]
 /// # Panics
 /// Will panic if the input JSON is not well-formed bril JSON
@@ -0,0 +1,297 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::{
+    AbstractCode, AbstractFunction, AbstractInstruction, AbstractProgram, Import, Position,
+};
+
+/// The errors from putting programs together with [`link`]
+// Having the #[error(...)] for all variants derives the Display trait as well
+#[derive(Error, Debug)]
+#[allow(clippy::module_name_repetitions)]
+pub enum LinkError {
+    /// Could not load {0}: {1}
+    #[error("Could not load {0}: {1}")]
+    CannotLoad(String, String),
+
+    /// Function @{0} is defined more than once
+    #[error("Function @{0} is defined more than once")]
+    DuplicateFunction(String),
+
+    /// {0} does not define a function @{1}
+    #[error("{0} does not define a function @{1}")]
+    MissingFunction(String, String),
+}
+
+impl LinkError {
+    #[doc(hidden)]
+    #[must_use]
+    pub fn add_pos(self, pos_var: Option<Position>) -> PositionalLinkError {
+        PositionalLinkError {
+            e: Box::new(self),
+            pos: pos_var.map(Box::new),
+        }
+    }
+}
+
+/// Wraps [`LinkError`] to optionally provide source code positions if they are available.
+#[derive(Error, Debug)]
+#[allow(clippy::module_name_repetitions)]
+pub struct PositionalLinkError {
+    e: Box<LinkError>,
+    pos: Option<Box<Position>>,
+}
+
+impl PositionalLinkError {
+    /// The error without its position
+    #[must_use]
+    pub fn error(&self) -> &LinkError {
+        &self.e
+    }
+
+    /// Where in the source the error is, if it is known
+    #[must_use]
+    pub fn pos(&self) -> Option<&Position> {
+        self.pos.as_deref()
+    }
+}
+
+impl Display for PositionalLinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.pos {
+            Some(pos) => write!(f, "{pos}: {}", self.e),
+            None => write!(f, "{}", self.e),
+        }
+    }
+}
+
+#[cfg(feature = "position")]
+fn function_pos(func: &AbstractFunction) -> Option<Position> {
+    func.pos.clone()
+}
+
+#[cfg(not(feature = "position"))]
+const fn function_pos(_: &AbstractFunction) -> Option<Position> {
+    None
+}
+
+#[cfg(feature = "position")]
+fn path_pos(import: &Import) -> Option<Position> {
+    import.pos.clone()
+}
+
+#[cfg(not(feature = "position"))]
+const fn path_pos(_: &Import) -> Option<Position> {
+    None
+}
+
+// Where the ```i```th function named by ```import``` is
+#[cfg(feature = "position")]
+fn import_pos(import: &Import, i: usize) -> Option<Position> {
+    import.pos.as_ref().map(|p| p.func(i))
+}
+
+#[cfg(not(feature = "position"))]
+const fn import_pos(_: &Import, _: usize) -> Option<Position> {
+    None
+}
+
+struct Module {
+    // What the names of its functions start with once they are linked, which is nothing for the programs given to [`link`]
+    prefix: Option<String>,
+    // The directory that the paths of its imports are relative to
+    dir: PathBuf,
+    program: AbstractProgram,
+    // The module that each of its imports is from
+    imports: Vec<usize>,
+}
+
+impl Module {
+    fn linked_name(&self, name: &str) -> String {
+        self.prefix
+            .as_ref()
+            .map_or_else(|| name.to_string(), |prefix| format!("{prefix}.{name}"))
+    }
+}
+
+// A name for the functions of ```path``` to start with which no other file has
+fn prefix_for(path: &Path, taken: &mut HashSet<String>) -> String {
+    let stem: String = path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '%' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    // Names have to start with a letter, `_` or `%`
+    let stem = if stem.starts_with(|c: char| c.is_ascii_alphabetic() || matches!(c, '_' | '%')) {
+        stem
+    } else {
+        format!("_{stem}")
+    };
+    let mut prefix = stem.clone();
+    let mut n = 1;
+    while !taken.insert(prefix.clone()) {
+        n += 1;
+        prefix = format!("{stem}{n}");
+    }
+    prefix
+}
+
+/// Puts ```programs``` together into one program along with every file that they import, which are read with ```load```.
+///
+/// Each program is given with the file it is from, which the paths of its imports are relative to, or [None] when it is from stdin and its imports are relative to the current directory.
+///
+/// The functions of ```programs``` keep their names and share them with each other, like object files being linked. The functions of an imported file are renamed to start with the name of the file, like `@lib.f` for `@f` from `lib.bril`, so that they can't clash with anything else. Calls to imported functions are changed to match, and a file that is imported more than once is only included once.
+/// # Errors
+/// When a file can't be loaded, a function is defined or imported twice under the same name, or an import names a function that its file doesn't define
+pub fn link(
+    programs: Vec<(Option<PathBuf>, AbstractProgram)>,
+    mut load: impl FnMut(&Path) -> Result<AbstractProgram, String>,
+) -> Result<AbstractProgram, PositionalLinkError> {
+    let mut modules = Vec::new();
+    // Files are told apart by their canonical paths
+    let mut loaded: HashMap<PathBuf, usize> = HashMap::new();
+    for (file, program) in programs {
+        if let Some(canonical) = file.as_ref().and_then(|f| f.canonicalize().ok()) {
+            loaded.insert(canonical, modules.len());
+        }
+        modules.push(Module {
+            prefix: None,
+            dir: file
+                .as_deref()
+                .and_then(Path::parent)
+                .map_or_else(PathBuf::new, Path::to_path_buf),
+            program,
+            imports: Vec::new(),
+        });
+    }
+
+    // Load every file that is imported, which may import more files in turn
+    let mut prefixes = HashSet::new();
+    let mut m = 0;
+    while m < modules.len() {
+        let mut imports = Vec::new();
+        for import in &modules[m].program.imports.clone() {
+            let path = modules[m].dir.join(&import.path);
+            let cannot_load = |e: String| {
+                LinkError::CannotLoad(path.display().to_string(), e).add_pos(path_pos(import))
+            };
+            let canonical = path
+                .canonicalize()
+                .map_err(|e| cannot_load(e.to_string()))?;
+            let index = if let Some(index) = loaded.get(&canonical) {
+                *index
+            } else {
+                let program = load(&path).map_err(cannot_load)?;
+                loaded.insert(canonical, modules.len());
+                modules.push(Module {
+                    prefix: Some(prefix_for(&path, &mut prefixes)),
+                    dir: path.parent().map_or_else(PathBuf::new, Path::to_path_buf),
+                    program,
+                    imports: Vec::new(),
+                });
+                modules.len() - 1
+            };
+            imports.push(index);
+        }
+        modules[m].imports = imports;
+        m += 1;
+    }
+
+    // The names of the functions that each file defines
+    let defines: Vec<HashSet<String>> = modules
+        .iter()
+        .map(|m| m.program.functions.iter().map(|f| f.name.clone()).collect())
+        .collect();
+    // The programs that were given share their names
+    let shared: HashSet<&String> = modules
+        .iter()
+        .zip(&defines)
+        .filter(|(m, _)| m.prefix.is_none())
+        .flat_map(|(_, d)| d)
+        .collect();
+
+    let mut functions = Vec::new();
+    let mut linked_names = HashSet::new();
+    for (m, module) in modules.iter().enumerate() {
+        // What the calls in this file should be changed to
+        let mut names: HashMap<&str, String> = HashMap::new();
+        if module.prefix.is_some() {
+            for name in &defines[m] {
+                names.insert(name, module.linked_name(name));
+            }
+        }
+        for (import, target) in module.program.imports.iter().zip(&module.imports) {
+            for (i, func) in import.functions.iter().enumerate() {
+                if !defines[*target].contains(&func.name) {
+                    return Err(
+                        LinkError::MissingFunction(import.path.clone(), func.name.clone())
+                            .add_pos(import_pos(import, i)),
+                    );
+                }
+                let local = func.local_name();
+                let visible = if module.prefix.is_some() {
+                    defines[m].contains(local)
+                } else {
+                    shared.contains(&local.to_string())
+                };
+                if visible
+                    || names
+                        .insert(local, modules[*target].linked_name(&func.name))
+                        .is_some()
+                {
+                    return Err(LinkError::DuplicateFunction(local.to_string())
+                        .add_pos(import_pos(import, i)));
+                }
+            }
+        }
+
+        for func in &module.program.functions {
+            let func = rename(func, &names, module);
+            if !linked_names.insert(func.name.clone()) {
+                return Err(
+                    LinkError::DuplicateFunction(func.name.clone()).add_pos(function_pos(&func))
+                );
+            }
+            functions.push(func);
+        }
+    }
+
+    Ok(AbstractProgram {
+        functions,
+        imports: Vec::new(),
+    })
+}
+
+// Copies ```func``` with its name and the functions it calls changed to their linked names
+fn rename(
+    func: &AbstractFunction,
+    names: &HashMap<&str, String>,
+    module: &Module,
+) -> AbstractFunction {
+    let mut func = func.clone();
+    func.name = module.linked_name(&func.name);
+    for code in &mut func.instrs {
+        if let AbstractCode::Instruction(
+            AbstractInstruction::Value { funcs, .. } | AbstractInstruction::Effect { funcs, .. },
+        ) = code
+        {
+            for f in funcs {
+                if let Some(name) = names.get(f.as_str()) {
+                    f.clone_from(name);
+                }
+            }
+        }
+    }
+    func
+}
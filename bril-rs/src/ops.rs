@@ -0,0 +1,508 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::{ConstOps, EffectOps, Type, ValueOps};
+
+/// How many arguments, labels, or functions an operation takes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    /// Exactly this many
+    Exactly(usize),
+    /// Up to this many, like the argument of `ret`
+    AtMost(usize),
+    /// Any number, like the arguments of `print`
+    Any,
+}
+
+impl Arity {
+    /// Whether an operation can be given ```n``` of these
+    #[must_use]
+    pub const fn allows(self, n: usize) -> bool {
+        match self {
+            Self::Exactly(m) => n == m,
+            Self::AtMost(m) => n <= m,
+            Self::Any => true,
+        }
+    }
+
+    /// The most of these that an operation can be given, if there is a limit
+    #[must_use]
+    pub const fn max(self) -> Option<usize> {
+        match self {
+            Self::Exactly(m) | Self::AtMost(m) => Some(m),
+            Self::Any => None,
+        }
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exactly(n) => write!(f, "{n}"),
+            Self::AtMost(n) => write!(f, "at most {n}"),
+            Self::Any => write!(f, "any"),
+        }
+    }
+}
+
+/// A type in the [`Signature`] of an operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ty {
+    /// `int`
+    Int,
+    /// `bool`
+    Bool,
+    /// `float`
+    #[cfg(feature = "float")]
+    Float,
+    /// Any type, which is the same type everywhere that `T` appears in the signature
+    T,
+    /// A pointer to `T`
+    PtrT,
+    /// Any type at all, like each of the arguments of `print`
+    Any,
+}
+
+impl Ty {
+    /// The type that this is when it doesn't depend on the rest of the signature
+    #[must_use]
+    pub const fn primitive(self) -> Option<Type> {
+        match self {
+            Self::Int => Some(Type::Int),
+            Self::Bool => Some(Type::Bool),
+            #[cfg(feature = "float")]
+            Self::Float => Some(Type::Float),
+            Self::T | Self::PtrT | Self::Any => None,
+        }
+    }
+}
+
+impl Display for Ty {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int => write!(f, "int"),
+            Self::Bool => write!(f, "bool"),
+            #[cfg(feature = "float")]
+            Self::Float => write!(f, "float"),
+            Self::T => write!(f, "T"),
+            Self::PtrT => write!(f, "ptr<T>"),
+            Self::Any => write!(f, "any"),
+        }
+    }
+}
+
+/// The types that an operation takes and gives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signature {
+    /// Takes arguments of the types in `args` followed by any number of `rest` if there is one, and gives a `result` if there is one
+    Fixed {
+        /// The types of the arguments that it always takes
+        args: &'static [Ty],
+        /// The type of each of any number of arguments after `args`
+        rest: Option<Ty>,
+        /// The type of its result
+        result: Option<Ty>,
+    },
+    /// Takes the arguments of the function that it calls, and gives the return type of that function if `result`
+    Call {
+        /// Whether it gives a result
+        result: bool,
+    },
+    /// Takes one argument of the return type of the function that it is in, or none if the function doesn't return anything
+    Return,
+}
+
+impl Signature {
+    const fn value(args: &'static [Ty], result: Ty) -> Self {
+        Self::Fixed {
+            args,
+            rest: None,
+            result: Some(result),
+        }
+    }
+
+    const fn effect(args: &'static [Ty]) -> Self {
+        Self::Fixed {
+            args,
+            rest: None,
+            result: None,
+        }
+    }
+
+    #[cfg(feature = "ssa")]
+    const fn variadic_value(rest: Ty, result: Ty) -> Self {
+        Self::Fixed {
+            args: &[],
+            rest: Some(rest),
+            result: Some(result),
+        }
+    }
+
+    const fn variadic_effect(rest: Ty) -> Self {
+        Self::Fixed {
+            args: &[],
+            rest: Some(rest),
+            result: None,
+        }
+    }
+
+    /// How many arguments an operation with this signature takes
+    #[must_use]
+    pub const fn arity(self) -> Arity {
+        match self {
+            Self::Fixed {
+                args, rest: None, ..
+            } => Arity::Exactly(args.len()),
+            Self::Fixed { rest: Some(_), .. } | Self::Call { .. } => Arity::Any,
+            Self::Return => Arity::AtMost(1),
+        }
+    }
+}
+
+impl Display for Signature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fixed { args, rest, result } => {
+                let mut types: Vec<String> = args.iter().map(ToString::to_string).collect();
+                if let Some(rest) = rest {
+                    types.push(format!("{rest}, ..."));
+                }
+                write!(f, "({})", types.join(", "))?;
+                result.map_or(Ok(()), |result| write!(f, " -> {result}"))
+            }
+            Self::Call { result: true } => {
+                write!(f, "(the arguments of the function) -> its return type")
+            }
+            Self::Call { result: false } => write!(f, "(the arguments of the function)"),
+            Self::Return => write!(f, "() or (the return type)"),
+        }
+    }
+}
+
+/// An operation along with the kind of instruction that it is used in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Op {
+    /// <https://capra.cs.cornell.edu/bril/lang/syntax.html#constant>
+    Const(ConstOps),
+    /// <https://capra.cs.cornell.edu/bril/lang/syntax.html#value-operation>
+    Value(ValueOps),
+    /// <https://capra.cs.cornell.edu/bril/lang/syntax.html#effect-operation>
+    Effect(EffectOps),
+}
+
+/// What there is to know about an operation, which is an entry of [`OPS`]
+#[derive(Debug, Clone, Copy)]
+pub struct OpInfo {
+    /// The name of the operation in Bril JSON and text
+    pub name: &'static str,
+    /// The operation itself
+    pub op: Op,
+    /// How many arguments it takes, which is what its signature allows
+    pub args: Arity,
+    /// How many labels it takes
+    pub labels: Arity,
+    /// How many functions it takes
+    pub funcs: Arity,
+    /// The types of its arguments and of its result if it has one
+    pub signature: Signature,
+    /// The feature of this crate and extension of Bril that it belongs to, or [None] for core Bril
+    pub feature: Option<&'static str>,
+}
+
+impl OpInfo {
+    const fn new(name: &'static str, op: Op, signature: Signature) -> Self {
+        Self {
+            name,
+            op,
+            args: signature.arity(),
+            labels: Arity::Exactly(0),
+            funcs: Arity::Exactly(0),
+            signature,
+            feature: None,
+        }
+    }
+
+    const fn value(name: &'static str, op: ValueOps, signature: Signature) -> Self {
+        Self::new(name, Op::Value(op), signature)
+    }
+
+    const fn effect(name: &'static str, op: EffectOps, signature: Signature) -> Self {
+        Self::new(name, Op::Effect(op), signature)
+    }
+
+    const fn labels(self, labels: Arity) -> Self {
+        Self { labels, ..self }
+    }
+
+    const fn funcs(self, funcs: Arity) -> Self {
+        Self { funcs, ..self }
+    }
+
+    #[cfg(any(
+        feature = "ssa",
+        feature = "memory",
+        feature = "float",
+        feature = "speculate"
+    ))]
+    const fn feature(self, feature: &'static str) -> Self {
+        Self {
+            feature: Some(feature),
+            ..self
+        }
+    }
+}
+
+/// Every operation that is enabled, in the order of the extensions of Bril they come from. Converting from an [`crate::AbstractProgram`], printing operations, checking how many arguments they have, checking and inferring their types, and the list of operations in the documentation all go through this table, so an operation is added by giving it a variant and an entry here
+pub const OPS: &[OpInfo] = &[
+    OpInfo::new(
+        "const",
+        Op::Const(ConstOps::Const),
+        Signature::value(&[], Ty::T),
+    ),
+    OpInfo::value(
+        "add",
+        ValueOps::Add,
+        Signature::value(&[Ty::Int, Ty::Int], Ty::Int),
+    ),
+    OpInfo::value(
+        "sub",
+        ValueOps::Sub,
+        Signature::value(&[Ty::Int, Ty::Int], Ty::Int),
+    ),
+    OpInfo::value(
+        "mul",
+        ValueOps::Mul,
+        Signature::value(&[Ty::Int, Ty::Int], Ty::Int),
+    ),
+    OpInfo::value(
+        "div",
+        ValueOps::Div,
+        Signature::value(&[Ty::Int, Ty::Int], Ty::Int),
+    ),
+    OpInfo::value(
+        "eq",
+        ValueOps::Eq,
+        Signature::value(&[Ty::Int, Ty::Int], Ty::Bool),
+    ),
+    OpInfo::value(
+        "lt",
+        ValueOps::Lt,
+        Signature::value(&[Ty::Int, Ty::Int], Ty::Bool),
+    ),
+    OpInfo::value(
+        "gt",
+        ValueOps::Gt,
+        Signature::value(&[Ty::Int, Ty::Int], Ty::Bool),
+    ),
+    OpInfo::value(
+        "le",
+        ValueOps::Le,
+        Signature::value(&[Ty::Int, Ty::Int], Ty::Bool),
+    ),
+    OpInfo::value(
+        "ge",
+        ValueOps::Ge,
+        Signature::value(&[Ty::Int, Ty::Int], Ty::Bool),
+    ),
+    OpInfo::value(
+        "not",
+        ValueOps::Not,
+        Signature::value(&[Ty::Bool], Ty::Bool),
+    ),
+    OpInfo::value(
+        "and",
+        ValueOps::And,
+        Signature::value(&[Ty::Bool, Ty::Bool], Ty::Bool),
+    ),
+    OpInfo::value(
+        "or",
+        ValueOps::Or,
+        Signature::value(&[Ty::Bool, Ty::Bool], Ty::Bool),
+    ),
+    OpInfo::value("call", ValueOps::Call, Signature::Call { result: true })
+        .funcs(Arity::Exactly(1)),
+    OpInfo::value("id", ValueOps::Id, Signature::value(&[Ty::T], Ty::T)),
+    OpInfo::effect("jmp", EffectOps::Jump, Signature::effect(&[])).labels(Arity::Exactly(1)),
+    OpInfo::effect("br", EffectOps::Branch, Signature::effect(&[Ty::Bool]))
+        .labels(Arity::Exactly(2)),
+    OpInfo::effect("call", EffectOps::Call, Signature::Call { result: false })
+        .funcs(Arity::Exactly(1)),
+    OpInfo::effect("ret", EffectOps::Return, Signature::Return),
+    OpInfo::effect(
+        "print",
+        EffectOps::Print,
+        Signature::variadic_effect(Ty::Any),
+    ),
+    OpInfo::effect("nop", EffectOps::Nop, Signature::effect(&[])),
+    #[cfg(feature = "ssa")]
+    OpInfo::value(
+        "phi",
+        ValueOps::Phi,
+        Signature::variadic_value(Ty::T, Ty::T),
+    )
+    .labels(Arity::Any)
+    .feature("ssa"),
+    #[cfg(feature = "memory")]
+    OpInfo::value(
+        "alloc",
+        ValueOps::Alloc,
+        Signature::value(&[Ty::Int], Ty::PtrT),
+    )
+    .feature("memory"),
+    #[cfg(feature = "memory")]
+    OpInfo::effect("free", EffectOps::Free, Signature::effect(&[Ty::PtrT])).feature("memory"),
+    #[cfg(feature = "memory")]
+    OpInfo::effect(
+        "store",
+        EffectOps::Store,
+        Signature::effect(&[Ty::PtrT, Ty::T]),
+    )
+    .feature("memory"),
+    #[cfg(feature = "memory")]
+    OpInfo::value("load", ValueOps::Load, Signature::value(&[Ty::PtrT], Ty::T)).feature("memory"),
+    #[cfg(feature = "memory")]
+    OpInfo::value(
+        "ptradd",
+        ValueOps::PtrAdd,
+        Signature::value(&[Ty::PtrT, Ty::Int], Ty::PtrT),
+    )
+    .feature("memory"),
+    #[cfg(feature = "float")]
+    OpInfo::value(
+        "fadd",
+        ValueOps::Fadd,
+        Signature::value(&[Ty::Float, Ty::Float], Ty::Float),
+    )
+    .feature("float"),
+    #[cfg(feature = "float")]
+    OpInfo::value(
+        "fsub",
+        ValueOps::Fsub,
+        Signature::value(&[Ty::Float, Ty::Float], Ty::Float),
+    )
+    .feature("float"),
+    #[cfg(feature = "float")]
+    OpInfo::value(
+        "fmul",
+        ValueOps::Fmul,
+        Signature::value(&[Ty::Float, Ty::Float], Ty::Float),
+    )
+    .feature("float"),
+    #[cfg(feature = "float")]
+    OpInfo::value(
+        "fdiv",
+        ValueOps::Fdiv,
+        Signature::value(&[Ty::Float, Ty::Float], Ty::Float),
+    )
+    .feature("float"),
+    #[cfg(feature = "float")]
+    OpInfo::value(
+        "feq",
+        ValueOps::Feq,
+        Signature::value(&[Ty::Float, Ty::Float], Ty::Bool),
+    )
+    .feature("float"),
+    #[cfg(feature = "float")]
+    OpInfo::value(
+        "flt",
+        ValueOps::Flt,
+        Signature::value(&[Ty::Float, Ty::Float], Ty::Bool),
+    )
+    .feature("float"),
+    #[cfg(feature = "float")]
+    OpInfo::value(
+        "fgt",
+        ValueOps::Fgt,
+        Signature::value(&[Ty::Float, Ty::Float], Ty::Bool),
+    )
+    .feature("float"),
+    #[cfg(feature = "float")]
+    OpInfo::value(
+        "fle",
+        ValueOps::Fle,
+        Signature::value(&[Ty::Float, Ty::Float], Ty::Bool),
+    )
+    .feature("float"),
+    #[cfg(feature = "float")]
+    OpInfo::value(
+        "fge",
+        ValueOps::Fge,
+        Signature::value(&[Ty::Float, Ty::Float], Ty::Bool),
+    )
+    .feature("float"),
+    #[cfg(feature = "speculate")]
+    OpInfo::effect("speculate", EffectOps::Speculate, Signature::effect(&[])).feature("speculate"),
+    #[cfg(feature = "speculate")]
+    OpInfo::effect("commit", EffectOps::Commit, Signature::effect(&[])).feature("speculate"),
+    #[cfg(feature = "speculate")]
+    OpInfo::effect("guard", EffectOps::Guard, Signature::effect(&[Ty::Bool]))
+        .labels(Arity::Exactly(1))
+        .feature("speculate"),
+];
+
+impl ConstOps {
+    /// The entry of this operation in [`OPS`]
+    #[must_use]
+    pub const fn info(self) -> &'static OpInfo {
+        match self {
+            // `const` is the first entry
+            Self::Const => &OPS[0],
+        }
+    }
+}
+
+impl ValueOps {
+    /// The entry of this operation in [`OPS`], which every operation but `Other` has
+    #[must_use]
+    pub fn info(self) -> Option<&'static OpInfo> {
+        OPS.iter()
+            .find(|info| matches!(info.op, Op::Value(op) if op == self))
+    }
+
+    /// The name of this operation in Bril JSON and text
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "passthrough")]
+            Self::Other(name) => name.as_str(),
+            // Every variant has an entry in the table, which the round trip test in test/ops checks
+            _ => self.info().map_or("", |info| info.name),
+        }
+    }
+
+    /// The value operation called ```name```, if it is one that is known
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        OPS.iter().find_map(|info| match info.op {
+            Op::Value(op) if info.name == name => Some(op),
+            _ => None,
+        })
+    }
+}
+
+impl EffectOps {
+    /// The entry of this operation in [`OPS`], which every operation but `Other` has
+    #[must_use]
+    pub fn info(self) -> Option<&'static OpInfo> {
+        OPS.iter()
+            .find(|info| matches!(info.op, Op::Effect(op) if op == self))
+    }
+
+    /// The name of this operation in Bril JSON and text
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "passthrough")]
+            Self::Other(name) => name.as_str(),
+            _ => self.info().map_or("", |info| info.name),
+        }
+    }
+
+    /// The effect operation called ```name```, if it is one that is known
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        OPS.iter().find_map(|info| match info.op {
+            Op::Effect(op) if info.name == name => Some(op),
+            _ => None,
+        })
+    }
+}
@@ -7,10 +7,18 @@ use serde::{Deserialize, Serialize};
 pub struct Program {
     /// A list of functions declared in the program
     pub functions: Vec<Function>,
+    /// The functions this program uses from other files
+    #[cfg(feature = "import")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub imports: Vec<Import>,
 }
 
 impl Display for Program {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        #[cfg(feature = "import")]
+        for import in &self.imports {
+            writeln!(f, "{import}")?;
+        }
         for func in &self.functions {
             writeln!(f, "{func}")?;
         }
@@ -18,6 +26,80 @@ impl Display for Program {
     }
 }
 
+/// <https://capra.cs.cornell.edu/bril/lang/import.html>
+#[cfg(feature = "import")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    /// The file to import from, relative to the file with the import
+    pub path: String,
+    /// The functions to import
+    pub functions: Vec<ImportedFunction>,
+    /// The position of this import in the original source code. Its `funcs_pos` are where each of the functions are
+    #[cfg(feature = "position")]
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub pos: Option<Position>,
+    /// The comments around this import in the original source code
+    #[cfg(feature = "comments")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comments: Option<crate::Comments>,
+}
+
+#[cfg(feature = "import")]
+impl Display for Import {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        #[cfg(feature = "comments")]
+        if let Some(c) = &self.comments {
+            c.write_before(f, "")?;
+        }
+        write!(f, "from {:?} import ", self.path)?;
+        for (i, func) in self.functions.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{func}")?;
+        }
+        write!(f, ";")?;
+        #[cfg(feature = "comments")]
+        if let Some(c) = &self.comments {
+            c.write_inline(f)?;
+            c.write_after(f, "")?;
+        }
+        Ok(())
+    }
+}
+
+/// A function named by an [`Import`]
+/// Example: @f as @g
+#[cfg(feature = "import")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ImportedFunction {
+    /// f
+    pub name: String,
+    /// g, the name that the function goes by in the importing file if it is not f
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+}
+
+#[cfg(feature = "import")]
+impl ImportedFunction {
+    /// The name that the function goes by in the importing file
+    #[must_use]
+    pub fn local_name(&self) -> &str {
+        self.alias.as_ref().unwrap_or(&self.name)
+    }
+}
+
+#[cfg(feature = "import")]
+impl Display for ImportedFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "@{}", self.name)?;
+        if let Some(alias) = &self.alias {
+            write!(f, " as @{alias}")?;
+        }
+        Ok(())
+    }
+}
+
 /// <https://capra.cs.cornell.edu/bril/lang/syntax.html#function>
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Function {
@@ -31,7 +113,7 @@ pub struct Function {
     pub name: String,
     /// The position of this function in the original source code
     #[cfg(feature = "position")]
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
     pub pos: Option<Position>,
     /// The possible return type of this function
     #[serde(rename = "type")]
@@ -93,7 +175,7 @@ pub enum Code {
         label: String,
         /// Where the label is located in source code
         #[cfg(feature = "position")]
-        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(flatten, skip_serializing_if = "Option::is_none")]
         pos: Option<Position>,
     },
     /// <https://capra.cs.cornell.edu/bril/lang/syntax.html#instruction>
@@ -125,7 +207,7 @@ pub enum Instruction {
         op: ConstOps,
         #[cfg(feature = "position")]
         /// The source position of the instruction if provided
-        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(flatten, skip_serializing_if = "Option::is_none")]
         pos: Option<Position>,
         /// Type of variable
         #[serde(rename = "type")]
@@ -150,7 +232,7 @@ pub enum Instruction {
         op: ValueOps,
         /// The source position of the instruction if provided
         #[cfg(feature = "position")]
-        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(flatten, skip_serializing_if = "Option::is_none")]
         pos: Option<Position>,
         /// Type of variable
         #[serde(rename = "type")]
@@ -171,7 +253,7 @@ pub enum Instruction {
         op: EffectOps,
         /// The source position of the instruction if provided
         #[cfg(feature = "position")]
-        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(flatten, skip_serializing_if = "Option::is_none")]
         pos: Option<Position>,
     },
 }
@@ -180,11 +262,11 @@ pub enum Instruction {
 impl Instruction {
     /// A helper function to extract the position value if it exists from an instruction
     #[must_use]
-    pub const fn get_pos(&self) -> Option<Position> {
+    pub const fn get_pos(&self) -> Option<&Position> {
         match self {
             Instruction::Constant { pos, .. }
             | Instruction::Value { pos, .. }
-            | Instruction::Effect { pos, .. } => *pos,
+            | Instruction::Effect { pos, .. } => pos.as_ref(),
         }
     }
 }
@@ -258,9 +340,7 @@ pub enum ConstOps {
 
 impl Display for ConstOps {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        match self {
-            ConstOps::Const => write!(f, "const"),
-        }
+        f.write_str(self.info().name)
     }
 }
 
@@ -298,28 +378,15 @@ pub enum EffectOps {
     /// <https://capra.cs.cornell.edu/bril/lang/spec.html#operations>
     #[cfg(feature = "speculate")]
     Guard,
+    /// An operation that isn't one of the above, which is kept by name so that programs using new extensions of Bril can still be processed
+    #[cfg(feature = "passthrough")]
+    #[serde(untagged)]
+    Other(OpName),
 }
 
 impl Display for EffectOps {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        match self {
-            EffectOps::Jump => write!(f, "jmp"),
-            EffectOps::Branch => write!(f, "br"),
-            EffectOps::Call => write!(f, "call"),
-            EffectOps::Return => write!(f, "ret"),
-            EffectOps::Print => write!(f, "print"),
-            EffectOps::Nop => write!(f, "nop"),
-            #[cfg(feature = "memory")]
-            EffectOps::Store => write!(f, "store"),
-            #[cfg(feature = "memory")]
-            EffectOps::Free => write!(f, "free"),
-            #[cfg(feature = "speculate")]
-            EffectOps::Speculate => write!(f, "speculate"),
-            #[cfg(feature = "speculate")]
-            EffectOps::Commit => write!(f, "commit"),
-            #[cfg(feature = "speculate")]
-            EffectOps::Guard => write!(f, "guard"),
-        }
+        f.write_str(self.name())
     }
 }
 
@@ -394,52 +461,69 @@ pub enum ValueOps {
     /// <https://capra.cs.cornell.edu/bril/lang/memory.html#operations>
     #[cfg(feature = "memory")]
     PtrAdd,
+    /// An operation that isn't one of the above, which is kept by name so that programs using new extensions of Bril can still be processed
+    #[cfg(feature = "passthrough")]
+    #[serde(untagged)]
+    Other(OpName),
 }
 
 impl Display for ValueOps {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        match self {
-            ValueOps::Add => write!(f, "add"),
-            ValueOps::Sub => write!(f, "sub"),
-            ValueOps::Mul => write!(f, "mul"),
-            ValueOps::Div => write!(f, "div"),
-            ValueOps::Eq => write!(f, "eq"),
-            ValueOps::Lt => write!(f, "lt"),
-            ValueOps::Gt => write!(f, "gt"),
-            ValueOps::Le => write!(f, "le"),
-            ValueOps::Ge => write!(f, "ge"),
-            ValueOps::Not => write!(f, "not"),
-            ValueOps::And => write!(f, "and"),
-            ValueOps::Or => write!(f, "or"),
-            ValueOps::Call => write!(f, "call"),
-            ValueOps::Id => write!(f, "id"),
-            #[cfg(feature = "ssa")]
-            ValueOps::Phi => write!(f, "phi"),
-            #[cfg(feature = "float")]
-            ValueOps::Fadd => write!(f, "fadd"),
-            #[cfg(feature = "float")]
-            ValueOps::Fsub => write!(f, "fsub"),
-            #[cfg(feature = "float")]
-            ValueOps::Fmul => write!(f, "fmul"),
-            #[cfg(feature = "float")]
-            ValueOps::Fdiv => write!(f, "fdiv"),
-            #[cfg(feature = "float")]
-            ValueOps::Feq => write!(f, "feq"),
-            #[cfg(feature = "float")]
-            ValueOps::Flt => write!(f, "flt"),
-            #[cfg(feature = "float")]
-            ValueOps::Fgt => write!(f, "fgt"),
-            #[cfg(feature = "float")]
-            ValueOps::Fle => write!(f, "fle"),
-            #[cfg(feature = "float")]
-            ValueOps::Fge => write!(f, "fge"),
-            #[cfg(feature = "memory")]
-            ValueOps::Alloc => write!(f, "alloc"),
-            #[cfg(feature = "memory")]
-            ValueOps::Load => write!(f, "load"),
-            #[cfg(feature = "memory")]
-            ValueOps::PtrAdd => write!(f, "ptradd"),
-        }
+        f.write_str(self.name())
+    }
+}
+
+/// The name of an operation that isn't in [`crate::ops::OPS`]
+///
+/// Names are interned for the life of the program so that operations stay `Copy`
+#[cfg(feature = "passthrough")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct OpName(&'static str);
+
+#[cfg(feature = "passthrough")]
+impl OpName {
+    /// Interns `name`, leaking it the first time that it is seen
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        static NAMES: std::sync::Mutex<std::collections::BTreeSet<&'static str>> =
+            std::sync::Mutex::new(std::collections::BTreeSet::new());
+        let mut names = NAMES
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let interned = names.get(name).copied().unwrap_or_else(|| {
+            let interned: &'static str = Box::leak(name.into());
+            names.insert(interned);
+            interned
+        });
+        drop(names);
+        Self(interned)
+    }
+
+    /// The name of the operation
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        self.0
+    }
+}
+
+#[cfg(feature = "passthrough")]
+impl Display for OpName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+#[cfg(feature = "passthrough")]
+impl Serialize for OpName {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0)
+    }
+}
+
+#[cfg(feature = "passthrough")]
+impl<'de> Deserialize<'de> for OpName {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(|name| Self::new(&name))
     }
 }
 
@@ -511,10 +595,105 @@ impl Literal {
 }
 
 /// <https://capra.cs.cornell.edu/bril/lang/syntax.html#source-positions>
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+///
+/// Along with where a syntax object starts and ends, this can record where the parts inside of it are so that errors can point at them
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Position {
-    /// Column
+    /// Where the syntax object starts
+    pub pos: ColRow,
+    /// Where the syntax object ends, just past its last character
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pos_end: Option<ColRow>,
+    /// The file that the syntax object is from, for when a program is put together from several files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub src: Option<String>,
+    /// Where each of the args of an instruction, or the arguments of a function, are
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args_pos: Vec<Span>,
+    /// Where each of the funcs of an instruction are
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub funcs_pos: Vec<Span>,
+    /// Where each of the labels of an instruction are
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels_pos: Vec<Span>,
+    /// Where the type of an instruction, or the return type of a function, is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub type_pos: Option<Span>,
+}
+
+impl Position {
+    /// A position which only knows where the syntax object starts
+    #[must_use]
+    pub const fn new(pos: ColRow) -> Self {
+        Self {
+            pos,
+            pos_end: None,
+            src: None,
+            args_pos: Vec::new(),
+            funcs_pos: Vec::new(),
+            labels_pos: Vec::new(),
+            type_pos: None,
+        }
+    }
+
+    // Only the part of the syntax object that ```span``` covers, or all of it when there is no span
+    fn part(&self, span: Option<&Span>) -> Self {
+        let (pos, pos_end) = span.map_or((self.pos, self.pos_end), |s| (s.pos, Some(s.pos_end)));
+        Self {
+            pos_end,
+            src: self.src.clone(),
+            ..Self::new(pos)
+        }
+    }
+
+    /// Where the ```i```th arg is, or the whole syntax object if that is not known
+    #[must_use]
+    pub fn arg(&self, i: usize) -> Self {
+        self.part(self.args_pos.get(i))
+    }
+
+    /// Where the ```i```th func is, or the whole syntax object if that is not known
+    #[must_use]
+    pub fn func(&self, i: usize) -> Self {
+        self.part(self.funcs_pos.get(i))
+    }
+
+    /// Where the ```i```th label is, or the whole syntax object if that is not known
+    #[must_use]
+    pub fn label(&self, i: usize) -> Self {
+        self.part(self.labels_pos.get(i))
+    }
+
+    /// Where the type is, or the whole syntax object if that is not known
+    #[must_use]
+    pub fn typ(&self) -> Self {
+        self.part(self.type_pos.as_ref())
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(src) = &self.src {
+            write!(f, "{src}: ")?;
+        }
+        write!(f, "Line {}, Column {}", self.pos.row, self.pos.col)
+    }
+}
+
+/// A place in the source code
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColRow {
+    /// Column, counting from 1
     pub col: u64,
-    /// Row
+    /// Row, counting from 1
     pub row: u64,
 }
+
+/// Where a part of a syntax object starts and ends
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    /// Where the part starts
+    pub pos: ColRow,
+    /// Where the part ends, just past its last character
+    pub pos_end: ColRow,
+}
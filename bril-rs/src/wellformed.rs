@@ -0,0 +1,347 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+use thiserror::Error;
+
+use crate::ops::{Arity, OpInfo};
+use crate::{Code, Function, Instruction, Position, Program, Type};
+
+/// The rules of <https://capra.cs.cornell.edu/bril/lang/wellformed.html> that a program can break, which [`check_well_formed`] looks for
+// Having the #[error(...)] for all variants derives the Display trait as well
+#[derive(Error, Debug)]
+#[allow(clippy::module_name_repetitions)]
+pub enum WellFormedError {
+    /// Function @{0} is defined more than once
+    #[error("Function @{0} is defined more than once")]
+    DuplicateFunction(String),
+
+    /// Argument `{0}` is named more than once
+    #[error("Argument `{0}` is named more than once")]
+    DuplicateArgument(String),
+
+    /// Label .{0} is defined more than once
+    #[error("Label .{0} is defined more than once")]
+    DuplicateLabel(String),
+
+    /// Function @{0} is not defined
+    #[error("Function @{0} is not defined")]
+    MissingFunction(String),
+
+    /// Label .{0} does not exist in this function
+    #[error("Label .{0} does not exist in this function")]
+    MissingLabel(String),
+
+    /// `{0}` is never defined in this function
+    #[error("`{0}` is never defined in this function")]
+    UndefinedVariable(String),
+
+    /// Expected `{0}` to have type {1}, found {2}
+    #[error("Expected `{0}` to have type {1}, found {2}")]
+    TypeConflict(String, Type, Type),
+
+    /// Expected {1} args for `{0}`, found {2}
+    #[error("Expected {1} args for `{0}`, found {2}")]
+    BadNumArgs(String, Arity, usize),
+
+    /// Expected {1} labels for `{0}`, found {2}
+    #[error("Expected {1} labels for `{0}`, found {2}")]
+    BadNumLabels(String, Arity, usize),
+
+    /// Expected {1} funcs for `{0}`, found {2}
+    #[error("Expected {1} funcs for `{0}`, found {2}")]
+    BadNumFuncs(String, Arity, usize),
+}
+
+impl WellFormedError {
+    #[doc(hidden)]
+    #[must_use]
+    pub fn add_pos(self, pos_var: Option<Position>) -> PositionalWellFormedError {
+        PositionalWellFormedError {
+            e: Box::new(self),
+            pos: pos_var.map(Box::new),
+        }
+    }
+}
+
+/// Wraps [`WellFormedError`] to optionally provide source code positions if they are available.
+#[derive(Error, Debug)]
+#[allow(clippy::module_name_repetitions)]
+pub struct PositionalWellFormedError {
+    e: Box<WellFormedError>,
+    pos: Option<Box<Position>>,
+}
+
+impl PositionalWellFormedError {
+    /// The error without its position
+    #[must_use]
+    pub fn error(&self) -> &WellFormedError {
+        &self.e
+    }
+
+    /// Where in the source the error is, if it is known
+    #[must_use]
+    pub fn pos(&self) -> Option<&Position> {
+        self.pos.as_deref()
+    }
+}
+
+impl Display for PositionalWellFormedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.pos {
+            Some(pos) => write!(f, "{pos}: {}", self.e),
+            None => write!(f, "{}", self.e),
+        }
+    }
+}
+
+/// Every rule that a program breaks, in the order that they come up in the program. Each is displayed on its own line.
+#[derive(Error, Debug)]
+#[allow(clippy::module_name_repetitions)]
+pub struct WellFormedErrors(Vec<PositionalWellFormedError>);
+
+impl WellFormedErrors {
+    /// Each of the rules that are broken
+    #[must_use]
+    pub fn errors(&self) -> &[PositionalWellFormedError] {
+        &self.0
+    }
+}
+
+impl Display for WellFormedErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, e) in self.0.iter().enumerate() {
+            if i != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{e}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "position")]
+fn function_pos(func: &Function) -> Option<Position> {
+    func.pos.clone()
+}
+
+#[cfg(not(feature = "position"))]
+const fn function_pos(_: &Function) -> Option<Position> {
+    None
+}
+
+#[cfg(feature = "position")]
+fn arg_pos(func: &Function, i: usize) -> Option<Position> {
+    func.pos.as_ref().map(|p| p.arg(i))
+}
+
+#[cfg(not(feature = "position"))]
+const fn arg_pos(_: &Function, _: usize) -> Option<Position> {
+    None
+}
+
+#[cfg(feature = "position")]
+const fn code_pos(code: &Code) -> Option<&Position> {
+    match code {
+        Code::Label { pos, .. } => pos.as_ref(),
+        Code::Instruction(instr) => instr.get_pos(),
+    }
+}
+
+#[cfg(not(feature = "position"))]
+const fn code_pos(_: &Code) -> Option<&Position> {
+    None
+}
+
+// The args, funcs, and labels of an instruction along with its entry in the table of operations, if it has one
+fn parts(instr: &Instruction) -> (&[String], &[String], &[String], Option<&'static OpInfo>) {
+    match instr {
+        Instruction::Constant { .. } => (&[], &[], &[], None),
+        Instruction::Value {
+            args,
+            funcs,
+            labels,
+            op,
+            ..
+        } => (args, funcs, labels, op.info()),
+        Instruction::Effect {
+            args,
+            funcs,
+            labels,
+            op,
+            ..
+        } => (args, funcs, labels, op.info()),
+    }
+}
+
+// The args of a phi only have to be defined along their own edge, and to_ssa.py names ones that aren't `__undefined`
+#[cfg(feature = "ssa")]
+const fn is_phi(instr: &Instruction) -> bool {
+    matches!(
+        instr,
+        Instruction::Value {
+            op: crate::ValueOps::Phi,
+            ..
+        }
+    )
+}
+
+#[cfg(not(feature = "ssa"))]
+const fn is_phi(_: &Instruction) -> bool {
+    false
+}
+
+const fn dest_type(instr: &Instruction) -> Option<(&String, &Type)> {
+    match instr {
+        Instruction::Constant {
+            dest, const_type, ..
+        } => Some((dest, const_type)),
+        Instruction::Value { dest, op_type, .. } => Some((dest, op_type)),
+        Instruction::Effect { .. } => None,
+    }
+}
+
+fn check_arity(
+    info: &OpInfo,
+    (args, funcs, labels): (&[String], &[String], &[String]),
+    pos: Option<&Position>,
+    errors: &mut Vec<PositionalWellFormedError>,
+) {
+    let name = || info.name.to_string();
+    if !info.args.allows(args.len()) {
+        errors.push(
+            WellFormedError::BadNumArgs(name(), info.args, args.len())
+                .add_pos(pos.map(|p| p.arg(info.args.max().unwrap_or(0)))),
+        );
+    }
+    if !info.labels.allows(labels.len()) {
+        errors.push(
+            WellFormedError::BadNumLabels(name(), info.labels, labels.len()).add_pos(pos.cloned()),
+        );
+    }
+    if !info.funcs.allows(funcs.len()) {
+        errors.push(
+            WellFormedError::BadNumFuncs(name(), info.funcs, funcs.len()).add_pos(pos.cloned()),
+        );
+    }
+}
+
+fn check_function(
+    func: &Function,
+    functions: &HashSet<&str>,
+    errors: &mut Vec<PositionalWellFormedError>,
+) {
+    // Variables and labels can be used anywhere in the function that they are defined, even before they are
+    let mut defined: HashSet<&str> = func.args.iter().map(|a| a.name.as_str()).collect();
+    let mut labels = HashSet::new();
+    for code in &func.instrs {
+        match code {
+            Code::Label { label, .. } => {
+                labels.insert(label.as_str());
+            }
+            Code::Instruction(instr) => {
+                if let Some((dest, _)) = dest_type(instr) {
+                    defined.insert(dest);
+                }
+            }
+        }
+    }
+
+    let mut types: HashMap<&str, &Type> = HashMap::new();
+    for (i, arg) in func.args.iter().enumerate() {
+        if types.insert(&arg.name, &arg.arg_type).is_some() {
+            errors.push(
+                WellFormedError::DuplicateArgument(arg.name.clone()).add_pos(arg_pos(func, i)),
+            );
+        }
+    }
+
+    let mut seen_labels = HashSet::new();
+    for code in &func.instrs {
+        let pos = code_pos(code);
+        let instr = match code {
+            Code::Label { label, .. } => {
+                if !seen_labels.insert(label.as_str()) {
+                    errors
+                        .push(WellFormedError::DuplicateLabel(label.clone()).add_pos(pos.cloned()));
+                }
+                continue;
+            }
+            Code::Instruction(instr) => instr,
+        };
+
+        let (args, funcs, instr_labels, info) = parts(instr);
+        if let Some(info) = info {
+            check_arity(info, (args, funcs, instr_labels), pos, errors);
+        }
+        for (i, arg) in args.iter().enumerate() {
+            if !defined.contains(arg.as_str()) && !is_phi(instr) {
+                errors.push(
+                    WellFormedError::UndefinedVariable(arg.clone()).add_pos(pos.map(|p| p.arg(i))),
+                );
+            }
+        }
+        for (i, f) in funcs.iter().enumerate() {
+            if !functions.contains(f.as_str()) {
+                errors.push(
+                    WellFormedError::MissingFunction(f.clone()).add_pos(pos.map(|p| p.func(i))),
+                );
+            }
+        }
+        for (i, l) in instr_labels.iter().enumerate() {
+            if !labels.contains(l.as_str()) {
+                errors.push(
+                    WellFormedError::MissingLabel(l.clone()).add_pos(pos.map(|p| p.label(i))),
+                );
+            }
+        }
+
+        // Every assignment to a variable has to agree with the first one, or with the argument of the same name
+        if let Some((dest, typ)) = dest_type(instr) {
+            match types.get(dest.as_str()) {
+                Some(&expected) if expected != typ => errors.push(
+                    WellFormedError::TypeConflict(dest.clone(), expected.clone(), typ.clone())
+                        .add_pos(pos.map(Position::typ)),
+                ),
+                Some(_) => {}
+                None => {
+                    types.insert(dest, typ);
+                }
+            }
+        }
+    }
+}
+
+/// Checks ```prog``` against the rules of <https://capra.cs.cornell.edu/bril/lang/wellformed.html> that can be checked without running it
+///
+/// Names are unique, the variables, labels, and functions that are used exist, other than the args of phis, each variable has one type, and each operation has as many args, labels, and funcs as it takes. The types that operations take are left to type checkers like brilirs.
+/// # Errors
+/// Every rule that is broken, rather than only the first
+pub fn check_well_formed(prog: &Program) -> Result<(), WellFormedErrors> {
+    let mut errors = Vec::new();
+
+    let mut functions: HashSet<&str> = HashSet::new();
+    // Imported functions can be called before the program is linked
+    #[cfg(feature = "import")]
+    functions.extend(
+        prog.imports
+            .iter()
+            .flat_map(|i| i.functions.iter().map(crate::ImportedFunction::local_name)),
+    );
+    for func in &prog.functions {
+        if !functions.insert(&func.name) {
+            errors.push(
+                WellFormedError::DuplicateFunction(func.name.clone()).add_pos(function_pos(func)),
+            );
+        }
+    }
+
+    for func in &prog.functions {
+        check_function(func, &functions, &mut errors);
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(WellFormedErrors(errors))
+    }
+}
@@ -1,3 +1,5 @@
+use bril_rs::graph::dominators;
+use bril_rs::wellformed::check_well_formed;
 use bril_rs::{Function, Instruction, Position, Program};
 use error::InterpError;
 use fxhash::FxHashMap;
@@ -22,6 +24,8 @@ impl TryFrom<Program> for BBProgram {
 impl BBProgram {
   /// Converts a [`Program`] into a [`BBProgram`]
   pub fn new(prog: Program) -> Result<Self, InterpError> {
+    // Every mistake like jumping to a label that doesn't exist is reported at once, and the basic blocks can be built knowing that there are none
+    check_well_formed(&prog)?;
     let num_funcs = prog.functions.len();
     let bb = Self {
       func_index: prog
@@ -130,6 +134,8 @@ pub struct BBFunction {
   // These replacements are found for function args and for code in the BasicBlocks
   pub num_of_vars: u32,
   pub args_as_nums: Vec<u32>,
+  // Kept so that the debugger can show variables by name
+  pub num_var_map: FxHashMap<String, u32>,
   pub pos: Option<Position>,
 }
 
@@ -221,14 +227,33 @@ impl BBFunction {
         blocks,
         args_as_nums,
         num_of_vars,
+        num_var_map,
         pos: func.pos,
       },
       label_map,
     )
   }
 
+  /// For each block, the indices of the blocks which may jump to it
+  pub fn predecessors(&self) -> Vec<Vec<usize>> {
+    let mut preds = vec![Vec::new(); self.blocks.len()];
+    for (i, block) in self.blocks.iter().enumerate() {
+      for e in &block.exit {
+        if !preds[*e].contains(&i) {
+          preds[*e].push(i);
+        }
+      }
+    }
+    preds
+  }
+
+  /// For each block `b`, `dominators()[b][d]` is true when every path from the entry block to `b` goes through `d`. See [`bril_rs::graph::dominators`].
+  pub fn dominators(&self) -> Vec<Vec<bool>> {
+    dominators(&self.predecessors())
+  }
+
   fn build_cfg(&mut self, label_map: FxHashMap<String, usize>) {
-    let last_idx = self.blocks.len() - 1;
+    let last_idx = self.blocks.len().saturating_sub(1);
     for (i, block) in self.blocks.iter_mut().enumerate() {
       // If we're before the last block
       if i < last_idx {
@@ -2,11 +2,12 @@ use crate::{
   basic_block::{BBFunction, BBProgram},
   error::PositionalInterpError,
 };
-use bril_rs::{ConstOps, EffectOps, Instruction, Type, ValueOps};
+use bril_rs::ops::{OpInfo, Signature, Ty};
+use bril_rs::{ConstOps, EffectOps, Instruction, Position, Type, ValueOps};
 
 use crate::error::InterpError;
 
-use fxhash::FxHashMap;
+use fxhash::{FxHashMap, FxHashSet};
 
 #[inline(always)]
 const fn check_num_args(expected: usize, args: &[String]) -> Result<(), InterpError> {
@@ -17,19 +18,29 @@ const fn check_num_args(expected: usize, args: &[String]) -> Result<(), InterpEr
   }
 }
 
+// How many arguments, functions, and labels an operation takes comes from the table of operations in bril_rs
 #[inline(always)]
-const fn check_num_funcs(expected: usize, funcs: &[String]) -> Result<(), InterpError> {
-  if expected != funcs.len() {
-    Err(InterpError::BadNumFuncs(expected, funcs.len()))
-  } else {
-    Ok(())
-  }
-}
-
-#[inline(always)]
-const fn check_num_labels(expected: usize, labels: &[String]) -> Result<(), InterpError> {
-  if expected != labels.len() {
-    Err(InterpError::BadNumLabels(expected, labels.len()))
+fn check_arity(
+  info: &OpInfo,
+  args: &[String],
+  funcs: &[String],
+  labels: &[String],
+) -> Result<(), InterpError> {
+  if !info.args.allows(args.len()) {
+    Err(InterpError::BadNumArgs(
+      info.args.max().unwrap_or_default(),
+      args.len(),
+    ))
+  } else if !info.funcs.allows(funcs.len()) {
+    Err(InterpError::BadNumFuncs(
+      info.funcs.max().unwrap_or_default(),
+      funcs.len(),
+    ))
+  } else if !info.labels.allows(labels.len()) {
+    Err(InterpError::BadNumLabels(
+      info.labels.max().unwrap_or_default(),
+      labels.len(),
+    ))
   } else {
     Ok(())
   }
@@ -74,6 +85,39 @@ fn get_type<'a>(
     .ok_or_else(|| InterpError::VarUndefined(args[index].to_string()))
 }
 
+// Narrows the position of an error in ```instr``` down to the arg, func, or label that it is about, if the position says where that is
+fn error_pos(e: &InterpError, instr: &Instruction) -> Option<Position> {
+  let pos = instr.get_pos()?.clone();
+  let (args, funcs, labels) = match instr {
+    Instruction::Constant { .. } => return Some(pos),
+    Instruction::Value {
+      args,
+      funcs,
+      labels,
+      ..
+    }
+    | Instruction::Effect {
+      args,
+      funcs,
+      labels,
+      ..
+    } => (args, funcs, labels),
+  };
+  let find = |names: &[String], name: &str| names.iter().position(|n| n == name);
+  Some(match e {
+    InterpError::VarUndefined(v)
+    | InterpError::UseNotDominated(v)
+    | InterpError::PhiArgNotDefinedOnEdge(v, _) => {
+      find(args, v).map_or_else(|| pos.clone(), |i| pos.arg(i))
+    }
+    InterpError::FuncNotFound(f) => find(funcs, f).map_or_else(|| pos.clone(), |i| pos.func(i)),
+    InterpError::PhiMissingLabel(l) | InterpError::PhiLabelNotPredecessor(l) => {
+      find(labels, l).map_or_else(|| pos.clone(), |i| pos.label(i))
+    }
+    _ => pos,
+  })
+}
+
 #[inline(always)]
 fn get_ptr_type(typ: &bril_rs::Type) -> Result<&bril_rs::Type, InterpError> {
   match typ {
@@ -82,13 +126,36 @@ fn get_ptr_type(typ: &bril_rs::Type) -> Result<&bril_rs::Type, InterpError> {
   }
 }
 
+// Checks that a variable of type ```typ``` can be where ```ty``` is in the signature of an operation, given what `T` has turned out to be so far
+#[inline(always)]
+fn check_ty<'a>(ty: Ty, typ: &'a Type, t: &mut Option<&'a Type>) -> Result<(), InterpError> {
+  if let Some(expected) = ty.primitive() {
+    return check_asmt_type(&expected, typ);
+  }
+  match (ty, *t) {
+    (Ty::T, Some(t)) => check_asmt_type(t, typ),
+    (Ty::PtrT, Some(t)) => check_asmt_type(&Type::Pointer(Box::new(t.clone())), typ),
+    (Ty::T, None) => {
+      *t = Some(typ);
+      Ok(())
+    }
+    (Ty::PtrT, None) => {
+      *t = Some(get_ptr_type(typ)?);
+      Ok(())
+    }
+    _ => Ok(()),
+  }
+}
+
 fn type_check_instruction<'a>(
   instr: &'a Instruction,
   func: &BBFunction,
   prog: &BBProgram,
   env: &mut FxHashMap<&'a str, &'a Type>,
+  defined: &FxHashSet<&str>,
 ) -> Result<(), InterpError> {
-  match instr {
+  // Programs can use operations from extensions that brilirs doesn't implement, which are only caught here when they have no entry in the table of operations
+  let (info, dest, args, funcs, labels) = match instr {
     Instruction::Constant {
       op: ConstOps::Const,
       dest,
@@ -99,313 +166,89 @@ fn type_check_instruction<'a>(
       if !(const_type == &Type::Float && value.get_type() == Type::Int) {
         check_asmt_type(const_type, &value.get_type())?;
       }
-      update_env(env, dest, const_type)
-    }
-    Instruction::Value {
-      op: ValueOps::Add | ValueOps::Sub | ValueOps::Mul | ValueOps::Div,
-      dest,
-      op_type,
-      args,
-      funcs,
-      labels,
-      pos: _,
-    } => {
-      check_num_args(2, args)?;
-      check_num_funcs(0, funcs)?;
-      check_num_labels(0, labels)?;
-      check_asmt_type(&Type::Int, get_type(env, 0, args)?)?;
-      check_asmt_type(&Type::Int, get_type(env, 1, args)?)?;
-      check_asmt_type(&Type::Int, op_type)?;
-      update_env(env, dest, op_type)
-    }
-    Instruction::Value {
-      op: ValueOps::Eq | ValueOps::Lt | ValueOps::Gt | ValueOps::Le | ValueOps::Ge,
-      dest,
-      op_type,
-      args,
-      funcs,
-      labels,
-      pos: _,
-    } => {
-      check_num_args(2, args)?;
-      check_num_funcs(0, funcs)?;
-      check_num_labels(0, labels)?;
-      check_asmt_type(&Type::Int, get_type(env, 0, args)?)?;
-      check_asmt_type(&Type::Int, get_type(env, 1, args)?)?;
-      check_asmt_type(&Type::Bool, op_type)?;
-      update_env(env, dest, op_type)
-    }
-    Instruction::Value {
-      op: ValueOps::Not,
-      dest,
-      op_type,
-      args,
-      funcs,
-      labels,
-      pos: _,
-    } => {
-      check_num_args(1, args)?;
-      check_num_funcs(0, funcs)?;
-      check_num_labels(0, labels)?;
-      check_asmt_type(&Type::Bool, get_type(env, 0, args)?)?;
-      check_asmt_type(&Type::Bool, op_type)?;
-      update_env(env, dest, op_type)
-    }
-    Instruction::Value {
-      op: ValueOps::And | ValueOps::Or,
-      dest,
-      op_type,
-      args,
-      funcs,
-      labels,
-      pos: _,
-    } => {
-      check_num_args(2, args)?;
-      check_num_funcs(0, funcs)?;
-      check_num_labels(0, labels)?;
-      check_asmt_type(&Type::Bool, get_type(env, 0, args)?)?;
-      check_asmt_type(&Type::Bool, get_type(env, 1, args)?)?;
-      check_asmt_type(&Type::Bool, op_type)?;
-      update_env(env, dest, op_type)
-    }
-    Instruction::Value {
-      op: ValueOps::Id,
-      dest,
-      op_type,
-      args,
-      funcs,
-      labels,
-      pos: _,
-    } => {
-      check_num_args(1, args)?;
-      check_num_funcs(0, funcs)?;
-      check_num_labels(0, labels)?;
-      check_asmt_type(op_type, get_type(env, 0, args)?)?;
-      update_env(env, dest, op_type)
-    }
-    Instruction::Value {
-      op: ValueOps::Fadd | ValueOps::Fsub | ValueOps::Fmul | ValueOps::Fdiv,
-      dest,
-      op_type,
-      args,
-      funcs,
-      labels,
-      pos: _,
-    } => {
-      check_num_args(2, args)?;
-      check_num_funcs(0, funcs)?;
-      check_num_labels(0, labels)?;
-      check_asmt_type(&Type::Float, get_type(env, 0, args)?)?;
-      check_asmt_type(&Type::Float, get_type(env, 1, args)?)?;
-      check_asmt_type(&Type::Float, op_type)?;
-      update_env(env, dest, op_type)
-    }
-    Instruction::Value {
-      op: ValueOps::Feq | ValueOps::Flt | ValueOps::Fgt | ValueOps::Fle | ValueOps::Fge,
-      dest,
-      op_type,
-      args,
-      funcs,
-      labels,
-      pos: _,
-    } => {
-      check_num_args(2, args)?;
-      check_num_funcs(0, funcs)?;
-      check_num_labels(0, labels)?;
-      check_asmt_type(&Type::Float, get_type(env, 0, args)?)?;
-      check_asmt_type(&Type::Float, get_type(env, 1, args)?)?;
-      check_asmt_type(&Type::Bool, op_type)?;
-      update_env(env, dest, op_type)
-    }
-    Instruction::Value {
-      op: ValueOps::Call,
-      dest,
-      op_type,
-      args,
-      funcs,
-      labels,
-      pos: _,
-    } => {
-      check_num_funcs(1, funcs)?;
-      check_num_labels(0, labels)?;
-      let callee_func = prog
-        .func_index
-        .get(&funcs[0])
-        .ok_or_else(|| InterpError::FuncNotFound(funcs[0].clone()))?;
-
-      if args.len() != callee_func.args.len() {
-        return Err(InterpError::BadNumArgs(callee_func.args.len(), args.len()));
-      }
-      args
-        .iter()
-        .zip(callee_func.args.iter())
-        .try_for_each(|(arg_name, expected_arg)| {
-          let ty = env
-            .get(arg_name as &str)
-            .ok_or_else(|| InterpError::VarUndefined(arg_name.to_string()))?;
-
-          check_asmt_type(ty, &expected_arg.arg_type)
-        })?;
-
-      match &callee_func.return_type {
-        None => Err(InterpError::NonEmptyRetForFunc(callee_func.name.clone())),
-        Some(t) => check_asmt_type(op_type, t),
-      }?;
-      update_env(env, dest, op_type)
+      return update_env(env, dest, const_type);
     }
     Instruction::Value {
       op: ValueOps::Phi,
       dest,
       op_type,
       args,
-      funcs,
+      funcs: _,
       labels,
       pos: _,
     } => {
       if args.len() != labels.len() {
         return Err(InterpError::UnequalPhiNode);
       }
-      check_num_funcs(0, funcs)?;
       // Phi nodes are a little weird with their args and there has been some discussion on an _undefined var name in #108
       // Instead, we are going to assign the type we expect to all of the args and this will trigger an error if any of these args ends up being a different type.
-      args.iter().try_for_each(|a| update_env(env, a, op_type))?;
+      // Args that are never defined in the function, like the `__undefined` of to_ssa.py, have no type to agree with
+      args
+        .iter()
+        .filter(|a| defined.contains(a.as_str()))
+        .try_for_each(|a| update_env(env, a, op_type))?;
 
-      update_env(env, dest, op_type)
-    }
-    Instruction::Value {
-      op: ValueOps::Alloc,
-      dest,
-      op_type,
-      args,
-      funcs,
-      labels,
-      pos: _,
-    } => {
-      check_num_args(1, args)?;
-      check_num_funcs(0, funcs)?;
-      check_num_labels(0, labels)?;
-      check_asmt_type(&Type::Int, get_type(env, 0, args)?)?;
-      get_ptr_type(op_type)?;
-      update_env(env, dest, op_type)
+      return update_env(env, dest, op_type);
     }
-    Instruction::Value {
-      op: ValueOps::Load,
-      dest,
-      op_type,
-      args,
-      funcs,
-      labels,
-      pos: _,
+    Instruction::Effect {
+      op: EffectOps::Speculate | EffectOps::Guard | EffectOps::Commit,
+      ..
     } => {
-      check_num_args(1, args)?;
-      check_num_funcs(0, funcs)?;
-      check_num_labels(0, labels)?;
-      let ptr_type = get_ptr_type(get_type(env, 0, args)?)?;
-      check_asmt_type(ptr_type, op_type)?;
-      update_env(env, dest, op_type)
+      unimplemented!()
     }
     Instruction::Value {
-      op: ValueOps::PtrAdd,
+      op,
       dest,
       op_type,
       args,
       funcs,
       labels,
       pos: _,
-    } => {
-      check_num_args(2, args)?;
-      check_num_funcs(0, funcs)?;
-      check_num_labels(0, labels)?;
-      let ty0 = get_type(env, 0, args)?;
-      get_ptr_type(ty0)?;
-      check_asmt_type(&Type::Int, get_type(env, 1, args)?)?;
-      check_asmt_type(ty0, op_type)?;
-      update_env(env, dest, op_type)
-    }
-    Instruction::Effect {
-      op: EffectOps::Jump,
+    } => (
+      op.info()
+        .ok_or_else(|| InterpError::UnknownOp(op.to_string()))?,
+      Some((dest, op_type)),
       args,
       funcs,
       labels,
-      pos: _,
-    } => {
-      check_num_args(0, args)?;
-      check_num_funcs(0, funcs)?;
-      check_num_labels(1, labels)?;
-      Ok(())
-    }
+    ),
     Instruction::Effect {
-      op: EffectOps::Branch,
+      op,
       args,
       funcs,
       labels,
       pos: _,
-    } => {
-      check_num_args(1, args)?;
-      check_asmt_type(&Type::Bool, get_type(env, 0, args)?)?;
-      check_num_funcs(0, funcs)?;
-      check_num_labels(2, labels)?;
-      Ok(())
-    }
-    Instruction::Effect {
-      op: EffectOps::Return,
+    } => (
+      op.info()
+        .ok_or_else(|| InterpError::UnknownOp(op.to_string()))?,
+      None,
       args,
       funcs,
       labels,
-      pos: _,
+    ),
+  };
+  check_arity(info, args, funcs, labels)?;
+
+  // Everything but calls and returns is checked against the types in the table of operations in bril_rs
+  match info.signature {
+    Signature::Fixed {
+      args: types,
+      rest,
+      result,
     } => {
-      check_num_funcs(0, funcs)?;
-      check_num_labels(0, labels)?;
-      match &func.return_type {
-        Some(t) => {
-          check_num_args(1, args)?;
-          let ty0 = get_type(env, 0, args)?;
-          check_asmt_type(t, ty0)
-        }
-        None => {
-          if args.is_empty() {
-            Ok(())
-          } else {
-            Err(InterpError::NonEmptyRetForFunc(func.name.clone()))
-          }
-        }
+      let mut t = None;
+      types
+        .iter()
+        .chain(rest.iter().cycle())
+        .take(args.len())
+        .enumerate()
+        .try_for_each(|(i, ty)| check_ty(*ty, get_type(env, i, args)?, &mut t))?;
+      match (result, dest) {
+        (Some(ty), Some((_, op_type))) => check_ty(ty, op_type, &mut t),
+        _ => Ok(()),
       }
     }
-    Instruction::Effect {
-      op: EffectOps::Print,
-      args,
-      funcs,
-      labels,
-      pos: _,
-    } => {
-      check_num_funcs(0, funcs)?;
-      check_num_labels(0, labels)?;
-      args.iter().enumerate().try_for_each(|(i, _)| {
-        get_type(env, i, args)?;
-        Ok(())
-      })
-    }
-    Instruction::Effect {
-      op: EffectOps::Nop,
-      args,
-      funcs,
-      labels,
-      pos: _,
-    } => {
-      check_num_args(0, args)?;
-      check_num_funcs(0, funcs)?;
-      check_num_labels(0, labels)?;
-      Ok(())
-    }
-    Instruction::Effect {
-      op: EffectOps::Call,
-      args,
-      funcs,
-      labels,
-      pos: _,
-    } => {
-      check_num_funcs(1, funcs)?;
-      check_num_labels(0, labels)?;
+    Signature::Call { .. } => {
       let callee_func = prog
         .func_index
         .get(&funcs[0])
@@ -425,48 +268,30 @@ fn type_check_instruction<'a>(
           check_asmt_type(ty, &expected_arg.arg_type)
         })?;
 
-      if callee_func.return_type.is_some() {
-        Err(InterpError::NonEmptyRetForFunc(callee_func.name.clone()))
-      } else {
-        Ok(())
+      match (dest, &callee_func.return_type) {
+        (Some((_, op_type)), Some(t)) => check_asmt_type(op_type, t),
+        (None, None) => Ok(()),
+        _ => Err(InterpError::NonEmptyRetForFunc(callee_func.name.clone())),
       }
     }
-    Instruction::Effect {
-      op: EffectOps::Store,
-      args,
-      funcs,
-      labels,
-      pos: _,
-    } => {
-      check_num_args(2, args)?;
-      check_num_funcs(0, funcs)?;
-      check_num_labels(0, labels)?;
-      let ty0 = get_type(env, 0, args)?;
-      let ty1 = get_type(env, 1, args)?;
-      check_asmt_type(get_ptr_type(ty0)?, ty1)
-    }
-    Instruction::Effect {
-      op: EffectOps::Free,
-      args,
-      funcs,
-      labels,
-      pos: _,
-    } => {
-      check_num_args(1, args)?;
-      check_num_funcs(0, funcs)?;
-      check_num_labels(0, labels)?;
-      get_ptr_type(get_type(env, 0, args)?)?;
-      Ok(())
-    }
-    Instruction::Effect {
-      op: EffectOps::Speculate | EffectOps::Guard | EffectOps::Commit,
-      args: _,
-      funcs: _,
-      labels: _,
-      pos: _,
-    } => {
-      unimplemented!()
-    }
+    Signature::Return => match &func.return_type {
+      Some(t) => {
+        check_num_args(1, args)?;
+        let ty0 = get_type(env, 0, args)?;
+        check_asmt_type(t, ty0)
+      }
+      None => {
+        if args.is_empty() {
+          Ok(())
+        } else {
+          Err(InterpError::NonEmptyRetForFunc(func.name.clone()))
+        }
+      }
+    },
+  }?;
+  match dest {
+    Some((dest, op_type)) => update_env(env, dest, op_type),
+    None => Ok(()),
   }
 }
 
@@ -476,14 +301,39 @@ fn type_check_func(bbfunc: &BBFunction, bbprog: &BBProgram) -> Result<(), Positi
   bbfunc.args.iter().for_each(|a| {
     env.insert(&a.name, &a.arg_type);
   });
+  let defined: FxHashSet<&str> = bbfunc
+    .args
+    .iter()
+    .map(|a| a.name.as_str())
+    .chain(
+      bbfunc
+        .blocks
+        .iter()
+        .flat_map(|b| &b.instrs)
+        .filter_map(|i| match i {
+          Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } => {
+            Some(dest.as_str())
+          }
+          Instruction::Effect { .. } => None,
+        }),
+    )
+    .collect();
 
-  let mut work_list = vec![0];
+  // A function with no instructions has no blocks to check
+  let mut work_list = if bbfunc.blocks.is_empty() {
+    vec![]
+  } else {
+    vec![0]
+  };
   let mut done_list = Vec::new();
 
   while let Some(b) = work_list.pop() {
     let block = bbfunc.blocks.get(b).unwrap();
     block.instrs.iter().try_for_each(|i| {
-      type_check_instruction(i, bbfunc, bbprog, &mut env).map_err(|e| e.add_pos(i.get_pos()))
+      type_check_instruction(i, bbfunc, bbprog, &mut env, &defined).map_err(|e| {
+        let pos = error_pos(&e, i);
+        e.add_pos(pos)
+      })
     })?;
     done_list.push(b);
     block.exit.iter().for_each(|e| {
@@ -505,3 +355,134 @@ pub fn type_check(bbprog: &BBProgram) -> Result<(), PositionalInterpError> {
     .iter()
     .try_for_each(|(_, bbfunc)| type_check_func(bbfunc, bbprog))
 }
+
+#[derive(Clone, Copy)]
+enum Definition {
+  Argument,
+  Instruction { block: usize, index: usize },
+}
+
+fn ssa_check_func(bbfunc: &BBFunction) -> Result<(), PositionalInterpError> {
+  let mut defs: FxHashMap<&str, Definition> =
+    FxHashMap::with_capacity_and_hasher(20, fxhash::FxBuildHasher::default());
+  for (i, a) in bbfunc.args.iter().enumerate() {
+    if defs.insert(&a.name, Definition::Argument).is_some() {
+      return Err(
+        InterpError::MultipleAssignment(a.name.clone())
+          .add_pos(bbfunc.pos.as_ref().map(|p| p.arg(i))),
+      );
+    }
+  }
+
+  let mut label_map: FxHashMap<&str, usize> =
+    FxHashMap::with_capacity_and_hasher(20, fxhash::FxBuildHasher::default());
+  for (b, block) in bbfunc.blocks.iter().enumerate() {
+    if let Some(label) = &block.label {
+      label_map.insert(label, b);
+    }
+    for (index, instr) in block.instrs.iter().enumerate() {
+      if let Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } = instr {
+        if defs
+          .insert(dest, Definition::Instruction { block: b, index })
+          .is_some()
+        {
+          return Err(
+            InterpError::MultipleAssignment(dest.clone()).add_pos(instr.get_pos().cloned()),
+          );
+        }
+      }
+    }
+  }
+
+  let preds = bbfunc.predecessors();
+  let doms = bbfunc.dominators();
+
+  // A definition is available at the end of `block` if it happened somewhere in a block that dominates it
+  let defined_at_end = |def: Definition, block: usize| match def {
+    Definition::Argument => true,
+    Definition::Instruction {
+      block: def_block, ..
+    } => doms[block][def_block],
+  };
+  // A definition is available before `index` of `block` if it came earlier in the same block or from a dominating block
+  let defined_before = |def: Definition, block: usize, index: usize| match def {
+    Definition::Argument => true,
+    Definition::Instruction {
+      block: def_block,
+      index: def_index,
+    } if def_block == block => def_index < index,
+    Definition::Instruction {
+      block: def_block, ..
+    } => doms[block][def_block],
+  };
+
+  for (b, block) in bbfunc.blocks.iter().enumerate() {
+    for (index, instr) in block.instrs.iter().enumerate() {
+      match instr {
+        Instruction::Constant { .. } => Ok(()),
+        // Zipping the args with the labels would quietly drop the extra ones
+        Instruction::Value {
+          op: ValueOps::Phi,
+          args,
+          labels,
+          ..
+        } if args.len() != labels.len() => Err(InterpError::UnequalPhiNode),
+        Instruction::Value {
+          op: ValueOps::Phi,
+          args,
+          labels,
+          ..
+        } => args.iter().zip(labels.iter()).try_for_each(|(arg, label)| {
+          let pred = *label_map
+            .get(label as &str)
+            .ok_or_else(|| InterpError::PhiMissingLabel(label.clone()))?;
+          if !preds[b].contains(&pred) {
+            return Err(InterpError::PhiLabelNotPredecessor(label.clone()));
+          }
+          let def = *defs
+            .get(arg as &str)
+            .ok_or_else(|| InterpError::VarUndefined(arg.clone()))?;
+          if defined_at_end(def, pred) {
+            Ok(())
+          } else {
+            Err(InterpError::PhiArgNotDefinedOnEdge(
+              arg.clone(),
+              label.clone(),
+            ))
+          }
+        }),
+        Instruction::Value { args, .. } | Instruction::Effect { args, .. } => {
+          args.iter().try_for_each(|arg| {
+            let def = *defs
+              .get(arg as &str)
+              .ok_or_else(|| InterpError::VarUndefined(arg.clone()))?;
+            if defined_before(def, b, index) {
+              Ok(())
+            } else {
+              Err(InterpError::UseNotDominated(arg.clone()))
+            }
+          })
+        }
+      }
+      .map_err(|e| {
+        let pos = error_pos(&e, instr);
+        e.add_pos(pos)
+      })?;
+    }
+  }
+
+  Ok(())
+}
+
+/// Provides validation that a Bril program is in SSA form.
+///
+/// Every variable must be assigned exactly once, every `phi` label must name a
+/// predecessor of the `phi`'s block, `phi` arguments must be defined along
+/// their corresponding edge, and every other use of a variable must be
+/// dominated by its definition.
+pub fn ssa_check(bbprog: &BBProgram) -> Result<(), PositionalInterpError> {
+  bbprog
+    .func_index
+    .iter()
+    .try_for_each(|(_, bbfunc)| ssa_check_func(bbfunc))
+}
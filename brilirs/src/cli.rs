@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 #[derive(Parser)]
 #[clap(about, version, author)] // keeps the cli synced with Cargo.toml
@@ -8,18 +8,92 @@ pub struct Cli {
   #[clap(short, long)]
   pub profile: bool,
 
-  /// The bril file to run. stdin is assumed if file is not provided
-  #[clap(short, long)]
-  pub file: Option<String>,
+  /// Write a breakdown of the dynamic instructions by function, basic block, opcode and line to this file
+  #[clap(long)]
+  pub profile_file: Option<String>,
+
+  /// The format of the file written by --profile-file. collapsed is for flamegraph tools
+  #[clap(long, possible_values = ["json", "collapsed"], default_value = "json")]
+  pub profile_format: String,
+
+  /// Write each instruction to stderr as it is run, along with the value it assigned
+  #[clap(long)]
+  pub trace: bool,
+
+  /// The format of the trace. jsonl writes one JSON object per instruction
+  #[clap(long, possible_values = ["text", "jsonl"], default_value = "text")]
+  pub trace_format: String,
+
+  /// Only trace instructions in this function. Can be given more than once
+  #[clap(long, multiple_occurrences(true))]
+  pub trace_func: Vec<String>,
+
+  /// Skip tracing instructions numbered before this. Instructions are numbered from 0 in the order they are run
+  #[clap(long, default_value_t = 0)]
+  pub trace_start: u64,
+
+  /// Stop tracing instructions numbered from this on
+  #[clap(long)]
+  pub trace_end: Option<u64>,
+
+  /// Write the trace to this file instead of stderr
+  #[clap(long)]
+  pub trace_file: Option<String>,
+
+  /// The bril file to run. Given more than once, the files are linked together like with bril-link. stdin is assumed if file is not provided
+  #[clap(short, long, global = true, multiple_occurrences = true)]
+  pub file: Vec<String>,
 
   /// Flag to only typecheck/validate the bril program
   #[clap(short, long)]
   pub check: bool,
 
-  /// Flag for when the bril program is in text form
+  /// Flag to also check that the bril program is in SSA form
   #[clap(short, long)]
+  pub ssa: bool,
+
+  /// Flag for when the bril program is in text form
+  #[clap(short, long, global = true)]
   pub text: bool,
 
+  /// Stop with an error after running this many dynamic instructions
+  #[clap(long)]
+  pub max_instructions: Option<u64>,
+
+  /// Stop with a stack overflow error when more than this many function calls are active at once [default: 100000]
+  #[clap(long)]
+  pub max_call_depth: Option<usize>,
+
+  /// Stop with an error when more than this many memory cells are allocated at once
+  #[clap(long)]
+  pub max_heap: Option<usize>,
+
+  /// Stop with an error when the program prints more than this many bytes
+  #[clap(long)]
+  pub max_output: Option<usize>,
+
   /// Arguments for the main function
   pub args: Vec<String>,
+
+  #[clap(subcommand)]
+  pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+  /// Run the program under a debugger which reads commands from stdin. The program must be given with --file
+  Debug {
+    /// The most instructions to record for stepping backwards, after which the oldest are forgotten. 0 turns recording off [default: 200000]
+    #[clap(long)]
+    history: Option<usize>,
+
+    /// Arguments for the main function
+    args: Vec<String>,
+  },
+  /// Serve the Debug Adapter Protocol over stdin and stdout so that editors can debug Bril programs
+  Dap,
+  /// Read Bril instructions and functions from stdin and run each instruction as soon as it is typed. The functions in --file are defined to start with
+  Repl,
+  /// Serve the Language Server Protocol over stdin and stdout so that editors can show errors in Bril text and navigate it
+  Lsp,
 }
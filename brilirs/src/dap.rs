@@ -0,0 +1,513 @@
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use serde_json::{json, Value};
+
+use crate::basic_block::BBProgram;
+use crate::debug::{Breakpoint, Debugger, StopReason};
+use crate::interp::{Interpreter, Limits, DEFAULT_HISTORY_LEN};
+use crate::rpc::{read_message, write_message};
+
+// There is only ever one thread
+const THREAD_ID: u64 = 1;
+
+// Reads and writes the messages of the Debug Adapter Protocol
+struct Connection<R: BufRead, W: Write> {
+  input: R,
+  output: W,
+  seq: u64,
+}
+
+impl<R: BufRead, W: Write> Connection<R, W> {
+  const fn new(input: R, output: W) -> Self {
+    Self {
+      input,
+      output,
+      seq: 1,
+    }
+  }
+
+  // The next message from the client, or None once the input has been closed
+  fn recv(&mut self) -> io::Result<Option<Value>> {
+    read_message(&mut self.input)
+  }
+
+  fn send(&mut self, mut msg: Value) -> io::Result<()> {
+    msg["seq"] = json!(self.seq);
+    self.seq += 1;
+    write_message(&mut self.output, &msg)
+  }
+
+  fn respond(&mut self, request: &Value, body: Value) -> io::Result<()> {
+    self.send(json!({
+      "type": "response",
+      "request_seq": request["seq"],
+      "success": true,
+      "command": request["command"],
+      "body": body,
+    }))
+  }
+
+  fn fail(&mut self, request: &Value, message: &str) -> io::Result<()> {
+    self.send(json!({
+      "type": "response",
+      "request_seq": request["seq"],
+      "success": false,
+      "command": request["command"],
+      "message": message,
+    }))
+  }
+
+  fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+    self.send(json!({
+      "type": "event",
+      "event": event,
+      "body": body,
+    }))
+  }
+}
+
+// Collects what the program prints so that it can be sent to the client as output events instead of going to stdout
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.0.borrow_mut().extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+// The arguments of a launch request
+struct Launch {
+  prog: BBProgram,
+  args: Vec<String>,
+  // The text file that source positions refer to
+  source: String,
+  stop_on_entry: bool,
+}
+
+impl Launch {
+  fn new(request: &Value) -> Result<Self, String> {
+    let args = &request["arguments"];
+    let program = args["program"]
+      .as_str()
+      .ok_or("the launch request has no `program` to debug")?;
+    let file = File::open(program).map_err(|e| format!("could not open `{program}`: {e}"))?;
+    // Programs in the text format have positions, those in JSON only do if they were made with `bril2json -p`
+    let text = Path::new(program)
+      .extension()
+      .is_some_and(|ext| ext == "bril");
+    let prog = crate::load_program(vec![(Some(program.into()), Box::new(file))], text)
+      .map_err(|e| e.to_string())?;
+    Ok(Self {
+      prog,
+      args: args["args"]
+        .as_array()
+        .map(|a| {
+          a.iter()
+            .map(|v| v.as_str().map_or_else(|| v.to_string(), str::to_string))
+            .collect()
+        })
+        .unwrap_or_default(),
+      source: args["source"].as_str().unwrap_or(program).to_string(),
+      stop_on_entry: args["stopOnEntry"].as_bool().unwrap_or(false),
+    })
+  }
+}
+
+// Something that the client can ask for the variables of
+#[derive(Clone, Copy)]
+enum Reference {
+  // The variables of the function this many frames out from the innermost one
+  Locals(usize),
+  // The cells of the allocation with this base
+  Allocation(usize),
+}
+
+struct Session<'a, 'r, R: BufRead, W: Write> {
+  conn: Connection<R, W>,
+  debugger: Debugger<'a, 'r, SharedBuffer>,
+  out: SharedBuffer,
+  prog: &'a BBProgram,
+  source: Value,
+  // The ids that the debugger gave to the breakpoints from each kind of request, which replaces all of them
+  line_breakpoints: Vec<usize>,
+  function_breakpoints: Vec<usize>,
+  // Handed out as `variablesReference`s, which are only good until the program is next resumed
+  references: Vec<Reference>,
+}
+
+impl<'a, 'r, R: BufRead, W: Write> Session<'a, 'r, R, W> {
+  fn reference(&mut self, r: Reference) -> usize {
+    self.references.push(r);
+    self.references.len()
+  }
+
+  fn flush_output(&mut self) -> io::Result<()> {
+    let printed = std::mem::take(&mut *self.out.0.borrow_mut());
+    if printed.is_empty() {
+      return Ok(());
+    }
+    self.conn.event(
+      "output",
+      json!({ "category": "stdout", "output": String::from_utf8_lossy(&printed) }),
+    )
+  }
+
+  fn stopped(&mut self, mut body: Value) -> io::Result<()> {
+    body["threadId"] = json!(THREAD_ID);
+    body["allThreadsStopped"] = json!(true);
+    self.conn.event("stopped", body)
+  }
+
+  fn exit(&mut self, code: i32) -> io::Result<()> {
+    self.conn.event("exited", json!({ "exitCode": code }))?;
+    self.conn.event("terminated", json!({}))
+  }
+
+  fn report(&mut self, stop: StopReason) -> io::Result<()> {
+    self.flush_output()?;
+    match (stop, self.debugger.error()) {
+      (StopReason::Finished, _) => self.exit(0),
+      (StopReason::Error, Some(e)) => {
+        let text = e.to_string();
+        self.conn.event(
+          "output",
+          json!({ "category": "stderr", "output": format!("error: {text}\n") }),
+        )?;
+        self.stopped(json!({ "reason": "exception", "description": "Error", "text": text }))
+      }
+      (StopReason::Breakpoint(id), _) => {
+        self.stopped(json!({ "reason": "breakpoint", "hitBreakpointIds": [id] }))
+      }
+      (StopReason::Start, _) => self.stopped(json!({ "reason": "entry" })),
+      _ => self.stopped(json!({ "reason": "step" })),
+    }
+  }
+
+  // Answers a request to run some of the program and then says where it stopped
+  fn resume(
+    &mut self,
+    request: &Value,
+    run: impl FnOnce(&mut Debugger<'a, 'r, SharedBuffer>) -> StopReason,
+  ) -> io::Result<()> {
+    self.references.clear();
+    let body = if request["command"] == "continue" {
+      json!({ "allThreadsContinued": true })
+    } else {
+      json!({})
+    };
+    self.conn.respond(request, body)?;
+    // A program that has stopped with an error ends when it is resumed, though it can still be stepped backwards
+    if let Some(e) = self.debugger.error() {
+      if !matches!(
+        request["command"].as_str(),
+        Some("stepBack" | "reverseContinue")
+      ) {
+        return self.exit(e.exit_code());
+      }
+    }
+    let stop = run(&mut self.debugger);
+    self.report(stop)
+  }
+
+  fn has_line(&self, line: u64) -> bool {
+    self.prog.func_index.values().any(|f| {
+      f.blocks
+        .iter()
+        .flat_map(|b| &b.instrs)
+        .any(|i| i.get_pos().is_some_and(|p| p.pos.row == line))
+    })
+  }
+
+  fn exists(&self, bp: &Breakpoint) -> bool {
+    match bp {
+      Breakpoint::Function(name) => self.prog.get(name).is_some(),
+      Breakpoint::Label(label) => self
+        .prog
+        .func_index
+        .values()
+        .any(|f| f.blocks.iter().any(|b| b.label.as_ref() == Some(label))),
+      Breakpoint::Line(line) => self.has_line(*line),
+    }
+  }
+
+  // Swaps the breakpoints in ```ids``` for ```bps``` and describes the new ones for the client
+  fn replace_breakpoints(
+    &mut self,
+    ids: Vec<usize>,
+    bps: Vec<Breakpoint>,
+  ) -> (Vec<usize>, Vec<Value>) {
+    for id in ids {
+      self.debugger.remove_breakpoint(id);
+    }
+    bps
+      .into_iter()
+      .map(|bp| {
+        let verified = self.exists(&bp);
+        let mut desc = json!({ "verified": verified });
+        if let Breakpoint::Line(line) = bp {
+          desc["line"] = json!(line);
+        }
+        if !verified {
+          desc["message"] = json!(format!("There is nothing at {bp}"));
+        }
+        let id = self.debugger.add_breakpoint(bp);
+        desc["id"] = json!(id);
+        (id, desc)
+      })
+      .unzip()
+  }
+
+  fn stack_trace(&self) -> Value {
+    let frames: Vec<Value> = self
+      .debugger
+      .interp()
+      .locations()
+      .iter()
+      .enumerate()
+      .map(|(depth, loc)| {
+        let pos = loc.instr.get_pos();
+        json!({
+          "id": depth + 1,
+          "name": loc.func.name,
+          "source": self.source,
+          "line": pos.map_or(0, |p| p.pos.row),
+          "column": pos.map_or(0, |p| p.pos.col),
+        })
+      })
+      .collect();
+    json!({ "totalFrames": frames.len(), "stackFrames": frames })
+  }
+
+  // A variable for the client, which can be expanded to show the allocation it points to
+  fn variable(&mut self, depth: usize, name: &str, value: String) -> Value {
+    let interp = self.debugger.interp();
+    let base = interp
+      .pointer_base(depth, name)
+      .filter(|base| interp.allocation(*base).is_some());
+    let child = base.map_or(0, |base| self.reference(Reference::Allocation(base)));
+    json!({ "name": name, "value": value, "variablesReference": child })
+  }
+
+  fn variables(&mut self, reference: usize) -> Option<Value> {
+    let vars: Vec<Value> = match *self.references.get(reference.checked_sub(1)?)? {
+      Reference::Locals(depth) => self
+        .debugger
+        .interp()
+        .variables(depth)
+        .into_iter()
+        .map(|(name, value)| self.variable(depth, name, value))
+        .collect(),
+      Reference::Allocation(base) => self
+        .debugger
+        .interp()
+        .allocation(base)?
+        .into_iter()
+        .enumerate()
+        .map(|(i, cell)| {
+          json!({
+            "name": format!("[{i}]"),
+            "value": cell.unwrap_or_else(|| "uninitialized".to_string()),
+            "variablesReference": 0,
+          })
+        })
+        .collect(),
+    };
+    Some(json!({ "variables": vars }))
+  }
+
+  fn evaluate(&mut self, request: &Value) -> Option<Value> {
+    let args = &request["arguments"];
+    let name = args["expression"].as_str()?.trim();
+    let depth = args["frameId"]
+      .as_u64()
+      .map_or(0, |id| id.saturating_sub(1) as usize);
+    let (name, value) = self
+      .debugger
+      .interp()
+      .variables(depth)
+      .into_iter()
+      .find(|(n, _)| *n == name)?;
+    let var = self.variable(depth, name, value);
+    Some(json!({ "result": var["value"], "variablesReference": var["variablesReference"] }))
+  }
+
+  fn handle(&mut self, request: &Value, stop_on_entry: bool) -> io::Result<bool> {
+    let args = &request["arguments"];
+    match request["command"].as_str().unwrap_or_default() {
+      "setBreakpoints" => {
+        let lines: Vec<Breakpoint> = args["breakpoints"]
+          .as_array()
+          .map(|bps| {
+            bps
+              .iter()
+              .filter_map(|bp| bp["line"].as_u64().map(Breakpoint::Line))
+              .collect()
+          })
+          .unwrap_or_default();
+        let old = std::mem::take(&mut self.line_breakpoints);
+        let (ids, bps) = self.replace_breakpoints(old, lines);
+        self.line_breakpoints = ids;
+        self.conn.respond(request, json!({ "breakpoints": bps }))?;
+      }
+      "setFunctionBreakpoints" => {
+        // Labels can be given as `.label`, and functions with or without the `@`
+        let funcs: Vec<Breakpoint> = args["breakpoints"]
+          .as_array()
+          .map(|bps| {
+            bps
+              .iter()
+              .filter_map(|bp| bp["name"].as_str())
+              .map(|name| match name.parse() {
+                Ok(bp @ (Breakpoint::Function(_) | Breakpoint::Label(_))) => bp,
+                _ => Breakpoint::Function(name.to_string()),
+              })
+              .collect()
+          })
+          .unwrap_or_default();
+        let old = std::mem::take(&mut self.function_breakpoints);
+        let (ids, bps) = self.replace_breakpoints(old, funcs);
+        self.function_breakpoints = ids;
+        self.conn.respond(request, json!({ "breakpoints": bps }))?;
+      }
+      "setExceptionBreakpoints" => self.conn.respond(request, json!({}))?,
+      "configurationDone" => {
+        self.conn.respond(request, json!({}))?;
+        if stop_on_entry {
+          self.stopped(json!({ "reason": "entry" }))?;
+        } else if let Some(id) = self.debugger.hit_breakpoint() {
+          self.report(StopReason::Breakpoint(id))?;
+        } else {
+          let stop = self.debugger.resume();
+          self.report(stop)?;
+        }
+      }
+      "threads" => self.conn.respond(
+        request,
+        json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] }),
+      )?,
+      "stackTrace" => {
+        let trace = self.stack_trace();
+        self.conn.respond(request, trace)?;
+      }
+      "scopes" => {
+        let depth = args["frameId"].as_u64().unwrap_or(1).saturating_sub(1) as usize;
+        let locals = self.reference(Reference::Locals(depth));
+        self.conn.respond(
+          request,
+          json!({ "scopes": [{
+            "name": "Locals",
+            "presentationHint": "locals",
+            "variablesReference": locals,
+            "expensive": false,
+          }] }),
+        )?;
+      }
+      "variables" => {
+        let reference = args["variablesReference"].as_u64().unwrap_or_default() as usize;
+        match self.variables(reference) {
+          Some(vars) => self.conn.respond(request, vars)?,
+          None => self
+            .conn
+            .fail(request, "Those variables are no longer available")?,
+        }
+      }
+      "evaluate" => match self.evaluate(request) {
+        Some(result) => self.conn.respond(request, result)?,
+        None => self
+          .conn
+          .fail(request, "There is no variable by that name")?,
+      },
+      "continue" => self.resume(request, Debugger::resume)?,
+      "next" => self.resume(request, Debugger::step_over)?,
+      "stepIn" => self.resume(request, Debugger::step_in)?,
+      "stepOut" => self.resume(request, Debugger::step_out)?,
+      "stepBack" => self.resume(request, Debugger::step_back_over)?,
+      "reverseContinue" => self.resume(request, Debugger::reverse)?,
+      "disconnect" | "terminate" => {
+        self.conn.respond(request, json!({}))?;
+        return Ok(false);
+      }
+      command => self
+        .conn
+        .fail(request, &format!("Unsupported request `{command}`"))?,
+    }
+    Ok(true)
+  }
+}
+
+/// Serves the Debug Adapter Protocol over ```input``` and ```output``` until the client disconnects. One program is launched, given by the `program` argument of the launch request, along with `args` for main, `stopOnEntry`, and `source` for the text file that a JSON program's positions refer to.
+pub fn serve(input: impl BufRead, output: impl Write) -> io::Result<()> {
+  let mut conn = Connection::new(input, output);
+
+  let (launch_request, launch) = loop {
+    let Some(request) = conn.recv()? else {
+      return Ok(());
+    };
+    match request["command"].as_str().unwrap_or_default() {
+      "initialize" => conn.respond(
+        &request,
+        json!({
+          "supportsConfigurationDoneRequest": true,
+          "supportsFunctionBreakpoints": true,
+          "supportsEvaluateForHovers": true,
+          "supportsTerminateRequest": true,
+          "supportsStepBack": true,
+        }),
+      )?,
+      "launch" => match Launch::new(&request) {
+        Ok(launch) => break (request, launch),
+        Err(e) => conn.fail(&request, &e)?,
+      },
+      "disconnect" => return conn.respond(&request, json!({})),
+      _ => conn.fail(&request, "The program has not been launched")?,
+    }
+  };
+
+  let out = SharedBuffer::default();
+  let mut interp = match Interpreter::new(
+    &launch.prog,
+    out.clone(),
+    &launch.args,
+    &Limits::default(),
+    None,
+    None,
+  ) {
+    Ok(interp) => interp,
+    Err(e) => return conn.fail(&launch_request, &e.to_string()),
+  };
+  interp.record_history(DEFAULT_HISTORY_LEN);
+  conn.respond(&launch_request, json!({}))?;
+  // Breakpoints are only asked for once the program is there to check them against
+  conn.event("initialized", json!({}))?;
+
+  let name = Path::new(&launch.source).file_name().map_or_else(
+    || launch.source.clone(),
+    |n| n.to_string_lossy().to_string(),
+  );
+  let mut session = Session {
+    conn,
+    debugger: Debugger::new(interp),
+    out,
+    prog: &launch.prog,
+    source: json!({ "name": name, "path": launch.source }),
+    line_breakpoints: Vec::new(),
+    function_breakpoints: Vec::new(),
+    references: Vec::new(),
+  };
+  while let Some(request) = session.conn.recv()? {
+    if !session.handle(&request, launch.stop_on_entry)? {
+      break;
+    }
+  }
+  Ok(())
+}
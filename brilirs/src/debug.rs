@@ -0,0 +1,421 @@
+use std::fmt::{self, Display};
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+
+use bril_rs::Position;
+
+use crate::error::PositionalInterpError;
+use crate::interp::{Interpreter, Location};
+
+/// A place for the debugger to stop before running an instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+  /// Written as `@name`, stops when the function is called
+  Function(String),
+  /// Written as `.name`, stops at the start of the block with this label in any function
+  Label(String),
+  /// Stops at the instruction on this line of the source file
+  Line(u64),
+}
+
+impl FromStr for Breakpoint {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.split_at(s.chars().next().map_or(0, char::len_utf8)) {
+      ("@", func) => Ok(Self::Function(func.to_string())),
+      (".", label) => Ok(Self::Label(label.to_string())),
+      _ => s
+        .parse()
+        .map(Self::Line)
+        .map_err(|_| format!("expected `@function`, `.label` or a line number, found `{s}`")),
+    }
+  }
+}
+
+impl Display for Breakpoint {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Function(func) => write!(f, "@{func}"),
+      Self::Label(label) => write!(f, ".{label}"),
+      Self::Line(line) => write!(f, "line {line}"),
+    }
+  }
+}
+
+impl Breakpoint {
+  fn matches(&self, loc: &Location, at_function_entry: bool) -> bool {
+    match self {
+      Self::Function(func) => at_function_entry && loc.func.name == *func,
+      Self::Label(label) => loc.block_start && loc.label == Some(label),
+      Self::Line(line) => loc.instr.get_pos().is_some_and(|p| p.pos.row == *line),
+    }
+  }
+}
+
+/// Why the debugger handed control back to the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+  /// The step that was asked for is done
+  Step,
+  /// The breakpoint with this id is at the next instruction
+  Breakpoint(usize),
+  /// The program has returned from "main"
+  Finished,
+  /// The program has stopped because of [`Debugger::error`]
+  Error,
+  /// Stepping backwards has reached the first instruction that was recorded
+  Start,
+}
+
+/// Runs an [`Interpreter`] a bit at a time, stopping at breakpoints
+pub struct Debugger<'a, 'r, T: Write> {
+  interp: Interpreter<'a, 'r, T>,
+  breakpoints: Vec<(usize, Breakpoint)>,
+  next_id: usize,
+  // Set once the program fails, after which it can't be run any further
+  error: Option<PositionalInterpError>,
+}
+
+impl<'a, 'r, T: Write> Debugger<'a, 'r, T> {
+  /// Starts out paused at the first instruction of "main"
+  pub const fn new(interp: Interpreter<'a, 'r, T>) -> Self {
+    Self {
+      interp,
+      breakpoints: Vec::new(),
+      next_id: 1,
+      error: None,
+    }
+  }
+
+  pub const fn interp(&self) -> &Interpreter<'a, 'r, T> {
+    &self.interp
+  }
+
+  /// The error that stopped the program, if there was one
+  pub const fn error(&self) -> Option<&PositionalInterpError> {
+    self.error.as_ref()
+  }
+
+  /// Adds a breakpoint and returns its id
+  pub fn add_breakpoint(&mut self, bp: Breakpoint) -> usize {
+    let id = self.next_id;
+    self.next_id += 1;
+    self.breakpoints.push((id, bp));
+    id
+  }
+
+  /// Removes the breakpoint ```id```, returning whether there was one
+  pub fn remove_breakpoint(&mut self, id: usize) -> bool {
+    let len = self.breakpoints.len();
+    self.breakpoints.retain(|(i, _)| *i != id);
+    self.breakpoints.len() != len
+  }
+
+  pub fn clear_breakpoints(&mut self) {
+    self.breakpoints.clear();
+  }
+
+  pub fn breakpoints(&self) -> &[(usize, Breakpoint)] {
+    &self.breakpoints
+  }
+
+  /// The breakpoint at the next instruction, if there is one
+  pub fn hit_breakpoint(&self) -> Option<usize> {
+    let locations = self.interp.locations();
+    let loc = locations.first()?;
+    let at_function_entry = self.interp.at_function_entry();
+    self
+      .breakpoints
+      .iter()
+      .find(|(_, bp)| bp.matches(loc, at_function_entry))
+      .map(|(id, _)| *id)
+  }
+
+  // Runs at least one instruction and then stops at a breakpoint or once ```done``` holds
+  fn run_until(&mut self, done: impl Fn(&Interpreter<'a, 'r, T>) -> bool) -> StopReason {
+    if self.error.is_some() {
+      return StopReason::Error;
+    }
+    loop {
+      if self.interp.is_finished() {
+        return StopReason::Finished;
+      }
+      if let Err(e) = self.interp.step() {
+        self.error = Some(e);
+        return StopReason::Error;
+      }
+      if self.interp.is_finished() {
+        return StopReason::Finished;
+      }
+      if let Some(id) = self.hit_breakpoint() {
+        return StopReason::Breakpoint(id);
+      }
+      if done(&self.interp) {
+        return StopReason::Step;
+      }
+    }
+  }
+
+  /// Runs one instruction, going into any function that it calls
+  pub fn step_in(&mut self) -> StopReason {
+    self.run_until(|_| true)
+  }
+
+  /// Runs one instruction, including the whole of any function that it calls
+  pub fn step_over(&mut self) -> StopReason {
+    let depth = self.interp.depth();
+    self.run_until(|i| i.depth() <= depth)
+  }
+
+  /// Runs until the current function returns
+  pub fn step_out(&mut self) -> StopReason {
+    let depth = self.interp.depth();
+    self.run_until(|i| i.depth() < depth)
+  }
+
+  /// Runs until a breakpoint is hit or the program finishes
+  pub fn resume(&mut self) -> StopReason {
+    self.run_until(|_| false)
+  }
+
+  // Undoes at least one instruction and then stops at a breakpoint or once ```done``` holds
+  fn run_back_until(&mut self, done: impl Fn(&Interpreter<'a, 'r, T>) -> bool) -> StopReason {
+    loop {
+      if !self.interp.step_back() {
+        return StopReason::Start;
+      }
+      // Whatever went wrong is ahead of us again
+      self.error = None;
+      if let Some(id) = self.hit_breakpoint() {
+        return StopReason::Breakpoint(id);
+      }
+      if done(&self.interp) {
+        return StopReason::Step;
+      }
+    }
+  }
+
+  /// Undoes the last instruction, going back into any function that it returned from
+  pub fn step_back(&mut self) -> StopReason {
+    self.run_back_until(|_| true)
+  }
+
+  /// Undoes the last instruction, including the whole of any function that it returned from
+  pub fn step_back_over(&mut self) -> StopReason {
+    // Going back from the end of the program is going back into "main"
+    let depth = self.interp.depth().max(1);
+    self.run_back_until(|i| i.depth() <= depth)
+  }
+
+  /// Goes backwards until a breakpoint is hit or there is nothing left to undo
+  pub fn reverse(&mut self) -> StopReason {
+    self.run_back_until(|_| false)
+  }
+}
+
+const HELP: &str = "\
+break (b) @func|.label|LINE  stop when a function is called, at a label, or at a line
+delete (d) [ID]              remove a breakpoint, or all of them
+info breakpoints             list the breakpoints
+continue (c)                 run until a breakpoint or the end of the program
+step (s)                     run one instruction, going into calls
+next (n)                     run one instruction, stepping over calls
+finish                       run until the current function returns
+print (p) VAR                show the value of a variable
+locals                       show every variable of the current function
+backtrace (bt)               show the call stack
+heap [VAR]                   list the allocations, or show the one VAR points to
+reverse-step (rs)            undo one instruction, going back into calls
+reverse-next (rn)            undo one instruction, stepping back over calls
+reverse-continue (rc)        go back to the last breakpoint or the start of the program
+last VAR                     show the instruction that last assigned to a variable
+where                        show the next instruction
+quit (q)                     stop debugging
+An empty line repeats the last command.";
+
+fn write_pos(out: &mut impl Write, pos: Option<&Position>) -> std::io::Result<()> {
+  if let Some(pos) = pos {
+    write!(out, " (line {}, column {})", pos.pos.row, pos.pos.col)?;
+  }
+  Ok(())
+}
+
+fn write_location(out: &mut impl Write, loc: &Location) -> std::io::Result<()> {
+  write!(out, "@{}", loc.func.name)?;
+  if let Some(label) = loc.label {
+    write!(out, ".{label}")?;
+  }
+  write!(out, ": {}", loc.instr)?;
+  write_pos(out, loc.instr.get_pos())?;
+  writeln!(out)
+}
+
+fn report<T: Write>(
+  out: &mut impl Write,
+  debugger: &Debugger<T>,
+  stop: StopReason,
+) -> std::io::Result<()> {
+  match (stop, debugger.error()) {
+    (StopReason::Finished, _) => writeln!(out, "The program has finished"),
+    (StopReason::Error, Some(e)) => writeln!(out, "error: {e}"),
+    (reason, _) => {
+      match reason {
+        StopReason::Breakpoint(id) => write!(out, "Breakpoint {id}, ")?,
+        StopReason::Start => write!(out, "At the start of the recorded history, ")?,
+        _ => {}
+      }
+      debugger
+        .interp()
+        .locations()
+        .first()
+        .map_or(Ok(()), |loc| write_location(out, loc))
+    }
+  }
+}
+
+/// Reads commands from ```input``` until it ends or the user quits, writing what the debugger has to say to ```out```
+pub fn run_debugger<T: Write>(
+  mut debugger: Debugger<T>,
+  input: impl BufRead,
+  out: &mut impl Write,
+) -> std::io::Result<()> {
+  report(out, &debugger, StopReason::Step)?;
+  let mut lines = input.lines();
+  let mut last_command = String::new();
+  loop {
+    write!(out, "(bril) ")?;
+    out.flush()?;
+    let Some(line) = lines.next() else {
+      writeln!(out)?;
+      return Ok(());
+    };
+    let line = line?;
+    let line = if line.trim().is_empty() {
+      last_command.clone()
+    } else {
+      line
+    };
+    last_command.clone_from(&line);
+
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or("");
+    let arg = words.next();
+    match (command, arg) {
+      ("" | "where", _) => report(out, &debugger, StopReason::Step)?,
+      ("help" | "h", _) => writeln!(out, "{HELP}")?,
+      ("quit" | "q", _) => return Ok(()),
+      ("break" | "b", Some(arg)) => match arg.parse::<Breakpoint>() {
+        Ok(bp) => {
+          let msg = format!("{bp}");
+          let id = debugger.add_breakpoint(bp);
+          writeln!(out, "Breakpoint {id} at {msg}")?;
+        }
+        Err(e) => writeln!(out, "{e}")?,
+      },
+      ("delete" | "d", None) => debugger.clear_breakpoints(),
+      ("delete" | "d", Some(arg)) => match arg.parse::<usize>() {
+        Ok(id) if debugger.remove_breakpoint(id) => {}
+        _ => writeln!(out, "No breakpoint `{arg}`")?,
+      },
+      ("info", Some("breakpoints" | "b")) => {
+        if debugger.breakpoints().is_empty() {
+          writeln!(out, "No breakpoints")?;
+        }
+        for (id, bp) in debugger.breakpoints() {
+          writeln!(out, "{id}: {bp}")?;
+        }
+      }
+      ("continue" | "c", _) => {
+        let stop = debugger.resume();
+        report(out, &debugger, stop)?;
+      }
+      ("step" | "s", _) => {
+        let stop = debugger.step_in();
+        report(out, &debugger, stop)?;
+      }
+      ("next" | "n", _) => {
+        let stop = debugger.step_over();
+        report(out, &debugger, stop)?;
+      }
+      ("finish", _) => {
+        let stop = debugger.step_out();
+        report(out, &debugger, stop)?;
+      }
+      ("reverse-step" | "rs", _) => {
+        let stop = debugger.step_back();
+        report(out, &debugger, stop)?;
+      }
+      ("reverse-next" | "rn", _) => {
+        let stop = debugger.step_back_over();
+        report(out, &debugger, stop)?;
+      }
+      ("reverse-continue" | "rc", _) => {
+        let stop = debugger.reverse();
+        report(out, &debugger, stop)?;
+      }
+      ("last", Some(var)) => match debugger.interp().last_write(var) {
+        Some((ago, loc)) => {
+          let plural = if ago == 1 { "" } else { "s" };
+          write!(
+            out,
+            "`{var}` was assigned {ago} instruction{plural} ago by "
+          )?;
+          write_location(out, &loc)?;
+        }
+        None => writeln!(
+          out,
+          "`{var}` has not been assigned in the recorded history of this call"
+        )?,
+      },
+      ("print" | "p", Some(var)) => {
+        match debugger
+          .interp()
+          .variables(0)
+          .into_iter()
+          .find(|(name, _)| *name == var)
+        {
+          Some((_, value)) => writeln!(out, "{var} = {value}")?,
+          None => writeln!(out, "`{var}` has no value")?,
+        }
+      }
+      ("locals", _) | ("info", Some("locals")) => {
+        for (name, value) in debugger.interp().variables(0) {
+          writeln!(out, "{name} = {value}")?;
+        }
+      }
+      ("backtrace" | "bt", _) => {
+        for (i, loc) in debugger.interp().locations().iter().enumerate() {
+          write!(out, "#{i} ")?;
+          write_location(out, loc)?;
+        }
+      }
+      ("heap", None) => {
+        let allocs = debugger.interp().allocations();
+        if allocs.is_empty() {
+          writeln!(out, "Nothing is allocated")?;
+        }
+        for (base, size) in allocs {
+          writeln!(out, "{base}: {size} cells")?;
+        }
+      }
+      ("heap" | "x", Some(var)) => {
+        let interp = debugger.interp();
+        match interp
+          .pointer_base(0, var)
+          .and_then(|base| interp.allocation(base).map(|cells| (base, cells)))
+        {
+          Some((base, cells)) => {
+            let cells: Vec<String> = cells
+              .into_iter()
+              .map(|c| c.unwrap_or_else(|| "_".to_string()))
+              .collect();
+            writeln!(out, "{base}: [{}]", cells.join(", "))?;
+          }
+          None => writeln!(out, "`{var}` does not point to an allocation")?,
+        }
+      }
+      _ => writeln!(out, "Unknown command `{line}`, try `help`")?,
+    }
+  }
+}
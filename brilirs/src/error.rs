@@ -1,5 +1,6 @@
 use std::fmt::Display;
 
+use bril_rs::wellformed::WellFormedErrors;
 use bril_rs::Position;
 use thiserror::Error;
 
@@ -18,6 +19,8 @@ pub enum InterpError {
   UnequalPhiNode,
   #[error("multiple functions of the same name found")]
   DuplicateFunction,
+  #[error(transparent)]
+  NotWellFormed(#[from] WellFormedErrors),
   #[error("Expected empty return for `{0}`, found value")]
   NonEmptyRetForFunc(String),
   #[error("cannot allocate `{0}` entries")]
@@ -34,12 +37,30 @@ pub enum InterpError {
   BadNumLabels(usize, usize), // (expected, actual)
   #[error("Expected `{0}` functions, found `{1}`")]
   BadNumFuncs(usize, usize), // (expected, actual)
+  #[error("stack overflow: more than `{0}` function calls are active at once")]
+  StackOverflow(usize),
+  #[error("instruction limit of `{0}` exceeded")]
+  InstructionLimit(u64),
+  #[error("heap limit of `{0}` memory cells exceeded")]
+  HeapLimit(usize),
+  #[error("output limit of `{0}` bytes exceeded")]
+  OutputLimit(usize),
+  #[error("unknown operation `{0}`, which brilirs does not implement")]
+  UnknownOp(String),
   #[error("no function of name `{0}` found")]
   FuncNotFound(String),
   #[error("undefined variable `{0}`")]
   VarUndefined(String),
   #[error("Label `{0}` for phi node not found")]
   PhiMissingLabel(String),
+  #[error("Label `{0}` for phi node is not a predecessor of this block")]
+  PhiLabelNotPredecessor(String),
+  #[error("`{0}` is not defined along the edge from label `{1}`")]
+  PhiArgNotDefinedOnEdge(String, String), // (arg, label)
+  #[error("`{0}` is assigned more than once which is not allowed in SSA form")]
+  MultipleAssignment(String),
+  #[error("use of `{0}` is not dominated by its definition")]
+  UseNotDominated(String),
   #[error("unspecified pointer type `{0:?}`")]
   ExpectedPointerType(bril_rs::Type), // found type
   #[error("Expected type `{0:?}` for function argument, found `{1:?}`")]
@@ -58,7 +79,7 @@ impl InterpError {
       Self::PositionalInterpErrorConversion(e) => e,
       _ => PositionalInterpError {
         e: Box::new(self),
-        pos,
+        pos: pos.map(Box::new),
       },
     }
   }
@@ -67,7 +88,7 @@ impl InterpError {
 #[derive(Error, Debug)]
 pub struct PositionalInterpError {
   e: Box<InterpError>,
-  pos: Option<Position>,
+  pos: Option<Box<Position>>,
 }
 
 impl PositionalInterpError {
@@ -77,13 +98,32 @@ impl PositionalInterpError {
       pos: None,
     }
   }
+
+  pub fn error(&self) -> &InterpError {
+    &self.e
+  }
+
+  pub fn pos(&self) -> Option<&Position> {
+    self.pos.as_deref()
+  }
+
+  // Running out of a resource gets its own exit code so that it can be told apart from a bug in the program
+  pub const fn exit_code(&self) -> i32 {
+    match *self.e {
+      InterpError::InstructionLimit(_) => 3,
+      InterpError::StackOverflow(_) => 4,
+      InterpError::HeapLimit(_) => 5,
+      InterpError::OutputLimit(_) => 6,
+      _ => 2,
+    }
+  }
 }
 
 impl Display for PositionalInterpError {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     match self {
       PositionalInterpError { e, pos: Some(pos) } => {
-        write!(f, "Line {}, Column {}: {e}", pos.row, pos.col)
+        write!(f, "{pos}: {e}")
       }
       PositionalInterpError { e, pos: None } => write!(f, "{e}"),
     }
@@ -1,10 +1,22 @@
 use fxhash::FxHashMap;
 
+/// Reference counts of each allocation, kept for the whole program and for each function that is running
 pub struct Collector {
-    //Map from pointer base address to count
-    total: FxHashMap<usize, i32>,
-    //Top of stack is the counters for this function
-    diff: Vec<FxHashMap<usize, i32>>
+  //Map from pointer base address to count
+  total: FxHashMap<usize, i32>,
+  //Top of stack is the counters for this function
+  diff: Vec<FxHashMap<usize, i32>>,
+  //Only kept while recording history for the debugger to step backwards through
+  journal: Option<Vec<Change>>,
+}
+
+// How to put back a change to the counters
+enum Change {
+  //The total and top counts of an address before it changed
+  Count(usize, Option<i32>, Option<i32>),
+  Enter,
+  //The counters of the function that exited and the totals they were taken from
+  Exeunt(FxHashMap<usize, i32>, Vec<(usize, i32)>),
 }
 
 impl Default for Collector {
@@ -12,60 +24,132 @@ impl Default for Collector {
     Self {
       total: FxHashMap::with_capacity_and_hasher(20, fxhash::FxBuildHasher::default()),
       diff: vec![],
+      journal: None,
     }
   }
 }
 
 impl Collector {
-  // To call as soon as entering a function
-    pub fn enter(&mut self) {
+  /// To call as soon as entering a function
+  pub fn enter(&mut self) {
     let top = FxHashMap::with_capacity_and_hasher(10, fxhash::FxBuildHasher::default());
     self.diff.push(top);
+    if let Some(journal) = &mut self.journal {
+      journal.push(Change::Enter);
+    }
+  }
+
+  // Saves the counts of address so that they can be put back
+  fn record(&mut self, address: usize) {
+    if let Some(journal) = &mut self.journal {
+      let top = self.diff.last().and_then(|top| top.get(&address)).copied();
+      journal.push(Change::Count(
+        address,
+        self.total.get(&address).copied(),
+        top,
+      ));
+    }
   }
 
+  /// Counts one more reference to ```address```
   pub fn increment(&mut self, address: usize) {
+    self.record(address);
     let a = address;
     let top = self.diff.last_mut().unwrap();
     top.insert(a, 1 + if top.contains_key(&a) { top[&a] } else { 0 });
     let tot = &mut self.total;
-      tot.insert(a, 1 + if tot.contains_key(&a) { tot[&a] } else { 0 });
+    tot.insert(a, 1 + if tot.contains_key(&a) { tot[&a] } else { 0 });
   }
 
-    //Returns true if address now has 0 reference
+  /// Counts one less reference to ```address```, returning true if there are none left
   pub fn decrement(&mut self, address: usize) -> bool {
-      let a = address;
-      let top = self.diff.last_mut().unwrap();
-      let top_new = top.get(&a).unwrap_or(&0) - 1;
-      let tot = &mut self.total;
-      let tot_new = tot.get(&a).unwrap_or(&0) - 1;
-      if tot_new <= 0 {
-	  tot.remove(&a);
-	  top.remove(&a);
-	  true
-      } else{
-	  top.insert(a, top_new);
-	  tot.insert(a, tot_new);
-	  false
+    self.record(address);
+    let a = address;
+    let top = self.diff.last_mut().unwrap();
+    let top_new = top.get(&a).unwrap_or(&0) - 1;
+    let tot = &mut self.total;
+    let tot_new = tot.get(&a).unwrap_or(&0) - 1;
+    if tot_new <= 0 {
+      tot.remove(&a);
+      top.remove(&a);
+      true
+    } else {
+      top.insert(a, top_new);
+      tot.insert(a, tot_new);
+      false
+    }
+  }
+
+  /// To call when leaving a function, which resets the counts to before it was entered and returns the addresses with none left
+  pub fn exeunt(&mut self) -> Vec<usize> {
+    let top = self.diff.pop().unwrap();
+    if let Some(journal) = &mut self.journal {
+      let totals = top
+        .keys()
+        .filter_map(|k| self.total.get(k).map(|t| (*k, *t)))
+        .collect();
+      journal.push(Change::Exeunt(top.clone(), totals));
+    }
+    let mut garbo: Vec<usize> = vec![];
+    for (key, val) in top.iter() {
+      let t = self.total.get(key).unwrap();
+      let t_new = t - val;
+      if t_new == 0 {
+        garbo.push(*key);
+        self.total.remove(key);
+      } else {
+        self.total.insert(*key, t_new);
       }
+    }
+    garbo
+  }
+
+  /// Starts keeping the changes to the counters so that they can be undone with [`Collector::rewind`]
+  pub fn record_history(&mut self) {
+    self.journal = Some(Vec::new());
+  }
+
+  /// The number of changes that have been kept
+  pub fn history_len(&self) -> usize {
+    self.journal.as_ref().map_or(0, Vec::len)
   }
 
-  /* Resets collector to previous state
-  Returns everything with 0 counter */
-    pub fn exeunt(&mut self) -> Vec<usize> {
-	let top = self.diff.pop().unwrap();
-	let mut garbo: Vec<usize> = vec![];
-	for (key, val) in top.iter() {
-	    let t = self.total.get(key).unwrap();
-	    let t_new = t - val;
-	    if t_new == 0 {
-		garbo.push(*key);
-		self.total.remove(key);
-	    } else {
-		self.total.insert(*key, t_new);
-	    }
-	}
-	garbo
+  /// Drops the oldest ```len``` changes, which can no longer be undone
+  pub fn forget(&mut self, len: usize) {
+    if let Some(journal) = &mut self.journal {
+      journal.drain(..len);
     }
+  }
+
+  /// Undoes changes until only ```len``` are left
+  pub fn rewind(&mut self, len: usize) {
+    let Some(journal) = &mut self.journal else {
+      return;
+    };
+    for change in journal.drain(len..).rev() {
+      match change {
+        Change::Count(address, total, top) => {
+          match total {
+            Some(t) => self.total.insert(address, t),
+            None => self.total.remove(&address),
+          };
+          if let Some(diff) = self.diff.last_mut() {
+            match top {
+              Some(t) => diff.insert(address, t),
+              None => diff.remove(&address),
+            };
+          }
+        }
+        Change::Enter => {
+          self.diff.pop();
+        }
+        Change::Exeunt(top, totals) => {
+          self.total.extend(totals);
+          self.diff.push(top);
+        }
+      }
+    }
+  }
 }
 
 /*
@@ -4,7 +4,9 @@ use std::hint::unreachable_unchecked;
 use crate::basic_block::{BBFunction, BBProgram, BasicBlock};
 use crate::error::{InterpError, PositionalInterpError};
 use crate::garbage::Collector;
-use bril_rs::Instruction;
+use crate::profile::Profile;
+use crate::trace::Tracer;
+use bril_rs::{Instruction, Position};
 
 use fxhash::FxHashMap;
 
@@ -40,18 +42,32 @@ impl Environment {
 struct Heap {
   memory: FxHashMap<usize, Vec<Value>>,
   base_num_counter: usize,
+  // The number of cells currently allocated
+  cells: usize,
+  // This is usize::MAX when there is no limit so that allocating only has to compare against it
+  max_cells: usize,
+  // Only kept while recording history for the debugger to step backwards through
+  journal: Option<Vec<HeapUndo>>,
 }
 
-impl Default for Heap {
-  fn default() -> Self {
+// How to put back a change to the heap
+enum HeapUndo {
+  Alloc(usize),
+  Free(usize, Vec<Value>),
+  Write(usize, usize, Value),
+}
+
+impl Heap {
+  fn new(max_cells: Option<usize>) -> Self {
     Self {
       memory: FxHashMap::with_capacity_and_hasher(20, fxhash::FxBuildHasher::default()),
       base_num_counter: 0,
+      cells: 0,
+      max_cells: max_cells.unwrap_or(usize::MAX),
+      journal: None,
     }
   }
-}
 
-impl Heap {
   #[inline(always)]
   fn is_empty(&self) -> bool {
     self.memory.is_empty()
@@ -62,17 +78,32 @@ impl Heap {
     if amount < 0 {
       return Err(InterpError::CannotAllocSize(amount));
     }
+    if self.cells + amount as usize > self.max_cells {
+      return Err(InterpError::HeapLimit(self.max_cells));
+    }
+    self.cells += amount as usize;
     let base = self.base_num_counter;
     self.base_num_counter += 1;
     self
       .memory
       .insert(base, vec![Value::default(); amount as usize]);
+    if let Some(journal) = &mut self.journal {
+      journal.push(HeapUndo::Alloc(base));
+    }
     Ok(Value::Pointer(Pointer { base, offset: 0 }))
   }
 
   #[inline(always)]
   fn free(&mut self, key: &Pointer) -> Result<(), InterpError> {
-    if self.memory.remove(&key.base).is_some() && key.offset == 0 {
+    let removed = self.memory.remove(&key.base);
+    let freed = removed.is_some();
+    if let Some(cells) = removed {
+      self.cells -= cells.len();
+      if let Some(journal) = &mut self.journal {
+        journal.push(HeapUndo::Free(key.base, cells));
+      }
+    }
+    if freed && key.offset == 0 {
       Ok(())
     } else {
       Err(InterpError::IllegalFree(key.base, key.offset))
@@ -83,13 +114,42 @@ impl Heap {
   fn write(&mut self, key: &Pointer, val: Value) -> Result<(), InterpError> {
     match self.memory.get_mut(&key.base) {
       Some(vec) if vec.len() > (key.offset as usize) && key.offset >= 0 => {
-        vec[key.offset as usize] = val;
+        let old = std::mem::replace(&mut vec[key.offset as usize], val);
+        if let Some(journal) = &mut self.journal {
+          journal.push(HeapUndo::Write(key.base, key.offset as usize, old));
+        }
         Ok(())
       }
       Some(_) | None => Err(InterpError::InvalidMemoryAccess(key.base, key.offset)),
     }
   }
 
+  // Puts back changes until only ```len``` are left in the journal
+  fn rewind(&mut self, len: usize) {
+    let Some(journal) = &mut self.journal else {
+      return;
+    };
+    for undo in journal.drain(len..).rev() {
+      match undo {
+        HeapUndo::Alloc(base) => {
+          if let Some(cells) = self.memory.remove(&base) {
+            self.cells -= cells.len();
+          }
+          self.base_num_counter = base;
+        }
+        HeapUndo::Free(base, cells) => {
+          self.cells += cells.len();
+          self.memory.insert(base, cells);
+        }
+        HeapUndo::Write(base, offset, old) => {
+          if let Some(cells) = self.memory.get_mut(&base) {
+            cells[offset] = old;
+          }
+        }
+      }
+    }
+  }
+
   #[inline(always)]
   fn read(&self, key: &Pointer) -> Result<&Value, InterpError> {
     self
@@ -104,6 +164,44 @@ impl Heap {
   }
 }
 
+// Where print instructions write to, which stops the program once it has printed too much
+struct Output<T: std::io::Write> {
+  out: T,
+  written: usize,
+  // The most that has been written, which is more than ```written``` after stepping backwards. Prints are not shown again when they are run again.
+  shown: usize,
+  // This is usize::MAX when there is no limit so that printing only has to compare against it
+  max_bytes: usize,
+}
+
+impl<T: std::io::Write> Output<T> {
+  fn new(out: T, max_bytes: Option<usize>) -> Self {
+    Self {
+      out,
+      written: 0,
+      shown: 0,
+      max_bytes: max_bytes.unwrap_or(usize::MAX),
+    }
+  }
+
+  fn print(&mut self, line: &str) -> Result<(), InterpError> {
+    // Plus one for the newline
+    self.written += line.len() + 1;
+    if self.written > self.max_bytes {
+      return Err(InterpError::OutputLimit(self.max_bytes));
+    }
+    if self.written <= self.shown {
+      return Ok(());
+    }
+    self.shown = self.written;
+    writeln!(self.out, "{line}").map_err(|e| InterpError::IoError(Box::new(e)))?;
+    self
+      .out
+      .flush()
+      .map_err(|e| InterpError::IoError(Box::new(e)))
+  }
+}
+
 #[inline(always)]
 fn get_value<'a>(vars: &'a Environment, index: usize, args: &[u32]) -> &'a Value {
   vars.get(&args[index])
@@ -118,7 +216,7 @@ where
 }
 
 #[derive(Debug, Clone)]
-enum Value {
+pub(crate) enum Value {
   Int(i64),
   Bool(bool),
   Float(f64),
@@ -133,7 +231,7 @@ impl Default for Value {
 }
 
 #[derive(Debug, Clone, PartialEq)]
-struct Pointer {
+pub(crate) struct Pointer {
   base: usize,
   offset: i64,
 }
@@ -160,6 +258,26 @@ impl fmt::Display for Value {
   }
 }
 
+impl Value {
+  // Like Display, but uninitialized memory has no value to show
+  pub(crate) fn describe(&self) -> Option<String> {
+    match self {
+      Self::Uninitialized => None,
+      _ => Some(self.to_string()),
+    }
+  }
+
+  pub(crate) fn to_json(&self) -> serde_json::Value {
+    match self {
+      Self::Int(i) => serde_json::json!(i),
+      Self::Bool(b) => serde_json::json!(b),
+      Self::Float(f) => serde_json::json!(f),
+      Self::Pointer(p) => serde_json::json!({ "base": p.base, "offset": p.offset }),
+      Self::Uninitialized => serde_json::Value::Null,
+    }
+  }
+}
+
 impl From<&bril_rs::Literal> for Value {
   #[inline(always)]
   fn from(l: &bril_rs::Literal) -> Self {
@@ -230,21 +348,32 @@ impl<'a> From<&'a Value> for &'a Pointer {
   }
 }
 
+#[inline(always)]
+fn const_value(const_type: &bril_rs::Type, value: &bril_rs::Literal) -> Value {
+  // Integer literals can be promoted to Floating point
+  if const_type == &bril_rs::Type::Float {
+    match value {
+      bril_rs::Literal::Int(i) => Value::Float(*i as f64),
+      bril_rs::Literal::Float(f) => Value::Float(*f),
+      // this is safe because we type check this beforehand
+      bril_rs::Literal::Bool(_) => unsafe { unreachable_unchecked() },
+    }
+  } else {
+    Value::from(value)
+  }
+}
+
 // todo do this with less function arguments
 #[inline(always)]
-fn execute_value_op<'a, T: std::io::Write>(
-  prog: &'a BBProgram,
+fn execute_value_op(
   op: &bril_rs::ValueOps,
   dest: u32,
   args: &[u32],
   labels: &[String],
-  funcs: &[String],
-  out: &mut T,
   value_store: &mut Environment,
   heap: &mut Heap,
   gc: &mut Collector,
   last_label: Option<&String>,
-  instruction_count: &mut u32,
 ) -> Result<(), InterpError> {
   use bril_rs::ValueOps::*;
   match *op {
@@ -309,14 +438,17 @@ fn execute_value_op<'a, T: std::io::Write>(
     }
     Id => {
       let src = get_value(value_store, 0, args).clone();
-	if let Value::Pointer(ref ptr) = src {
-            gc.increment(ptr.base);
-	}
-	if let Value::Pointer(ptr) = value_store.get(&dest) {
-            if gc.decrement(ptr.base) {
-		heap.free(ptr);
-	    }
-	}
+      if let Value::Pointer(ref ptr) = src {
+        gc.increment(ptr.base);
+      }
+      if let Value::Pointer(ptr) = value_store.get(&dest) {
+        if gc.decrement(ptr.base) {
+          heap.free(&Pointer {
+            base: ptr.base,
+            offset: 0,
+          })?;
+        }
+      }
       value_store.set(dest, src);
     }
     Fadd => {
@@ -364,29 +496,10 @@ fn execute_value_op<'a, T: std::io::Write>(
       let arg1 = get_arg::<f64>(value_store, 1, args);
       value_store.set(dest, Value::Bool(arg0 >= arg1));
     }
-    Call => {
-      let callee_func = prog
-        .get(&funcs[0])
-        .ok_or_else(|| InterpError::FuncNotFound(funcs[0].clone()))?;
-
-      let next_env = make_func_args(callee_func, args, value_store);
-      let ret_val = execute(
-        prog,
-        callee_func,
-        out,
-        next_env,
-        heap,
-        gc,
-        instruction_count,
-      )?
-      .unwrap();
-      match ret_val {
-        // If assigning to pointer, bump in gc
-        Value::Pointer(ref pointer) => gc.increment(pointer.base),
-        _ => {}
-      }
-      value_store.set(dest, ret_val)
-    }
+    // Calls need a new frame on the call stack so they are handled by execute
+    Call => unreachable!(),
+    // The type checker rejects operations that it doesn't know
+    Other(_) => unreachable!(),
     Phi => {
       if last_label.is_none() {
         return Err(InterpError::NoLastLabel);
@@ -404,18 +517,21 @@ fn execute_value_op<'a, T: std::io::Write>(
       let arg0 = get_arg::<i64>(value_store, 0, args);
       let res = heap.alloc(arg0)?;
       if let Value::Pointer(ref ptr) = res {
-          gc.increment(ptr.base);
+        gc.increment(ptr.base);
       }
-	if let Value::Pointer(ptr) = value_store.get(&dest) {
-	    if gc.decrement(ptr.base){
-		heap.free(ptr);
-            }
+      if let Value::Pointer(ptr) = value_store.get(&dest) {
+        if gc.decrement(ptr.base) {
+          heap.free(&Pointer {
+            base: ptr.base,
+            offset: 0,
+          })?;
+        }
       }
       value_store.set(dest, res)
     }
     Load => {
       let arg0 = get_arg::<&Pointer>(value_store, 0, args);
-	let res = heap.read(arg0)?;
+      let res = heap.read(arg0)?;
       // if let Value::Pointer(ref ptr) = res {
       //   gc.increment(ptr.base);
       // }
@@ -434,9 +550,12 @@ fn execute_value_op<'a, T: std::io::Write>(
         gc.increment(ptr.base);
       }
       if let Value::Pointer(ptr) = value_store.get(&dest) {
-          if gc.decrement(ptr.base){
-	      heap.free(ptr);
-          }
+        if gc.decrement(ptr.base) {
+          heap.free(&Pointer {
+            base: ptr.base,
+            offset: 0,
+          })?;
+        }
       }
       value_store.set(dest, res)
     }
@@ -467,20 +586,15 @@ fn make_func_args<'a>(
 
 // todo do this with less function arguments
 #[inline(always)]
-fn execute_effect_op<'a, T: std::io::Write>(
-  prog: &'a BBProgram,
-  func: &BBFunction,
+fn execute_effect_op<T: std::io::Write>(
   op: &bril_rs::EffectOps,
   args: &[u32],
-  funcs: &[String],
   curr_block: &BasicBlock,
-  out: &mut T,
+  out: &mut Output<T>,
   value_store: &Environment,
   heap: &mut Heap,
-  gc: &mut Collector,
   next_block_idx: &mut Option<usize>,
-  instruction_count: &mut u32,
-) -> Result<Option<Value>, InterpError> {
+) -> Result<(), InterpError> {
   use bril_rs::EffectOps::*;
   match op {
     Jump => {
@@ -491,53 +605,18 @@ fn execute_effect_op<'a, T: std::io::Write>(
       let exit_idx = if bool_arg0 { 0 } else { 1 };
       *next_block_idx = Some(curr_block.exit[exit_idx]);
     }
-    Return => {
-      match &func.return_type {
-        Some(_) => {
-          let arg0 = get_value(value_store, 0, args);
-          return Ok(Some(arg0.clone()));
-        }
-        None => {
-          return Ok(None);
-        }
-      }
-    }
     Print => {
-      writeln!(
-        out,
-        "{}",
-        args
+      out.print(
+        &args
           .iter()
           .map(|a| value_store.get(a).to_string())
           .collect::<Vec<String>>()
-          .join(" ")
-      )
-      .map_err(|e| InterpError::IoError(Box::new(e)))?;
-      out.flush().map_err(|e| InterpError::IoError(Box::new(e)))?;
-    }
-    Nop => {}
-    Call => {
-      let callee_func = prog
-        .get(&funcs[0])
-        .ok_or_else(|| InterpError::FuncNotFound(funcs[0].clone()))?;
-
-      let next_env = make_func_args(callee_func, args, value_store);
-      for arg in args.iter() {
-        match next_env.get(arg) {
-          Value::Pointer(ref ptr) => gc.increment(ptr.base),
-          _ => {}
-        }
-      }
-      execute(
-        prog,
-        callee_func,
-        out,
-        next_env,
-        heap,
-        gc,
-        instruction_count,
+          .join(" "),
       )?;
     }
+    Nop => {}
+    // Calls and returns change the call stack so they are handled by execute
+    Call | Return => unreachable!(),
     Store => {
       let arg0 = get_arg::<&Pointer>(value_store, 0, args);
       let arg1 = get_value(value_store, 1, args);
@@ -550,147 +629,946 @@ fn execute_effect_op<'a, T: std::io::Write>(
        */
     }
     Speculate | Commit | Guard => unimplemented!(),
+    Other(_) => unreachable!(),
   }
-  Ok(None)
+  Ok(())
 }
 
-fn execute<'a, T: std::io::Write>(
-  prog: &'a BBProgram,
+/// The value of [`Limits::max_call_depth`] if it is not set
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 100_000;
+
+/// How many instructions the debugger records for stepping backwards if it is not told otherwise
+pub const DEFAULT_HISTORY_LEN: usize = 200_000;
+
+/// Bounds on the resources that a bril program may use before [`execute_main`] stops it with an error. [`None`] means that there is no bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+  /// The most dynamic instructions that may be run, counted in the same way as ```total_dyn_inst```
+  pub max_instructions: Option<u64>,
+  /// The most function calls that may be active at once. Tail calls reuse the frame of their caller so they do not count towards this.
+  pub max_call_depth: usize,
+  /// The most memory cells that may be allocated at once
+  pub max_heap_cells: Option<usize>,
+  /// The most bytes that may be printed, including newlines
+  pub max_output_bytes: Option<usize>,
+}
+
+impl Default for Limits {
+  fn default() -> Self {
+    Self {
+      max_instructions: None,
+      max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+      max_heap_cells: None,
+      max_output_bytes: None,
+    }
+  }
+}
+
+// The state of a function call which has not returned yet
+struct Frame<'a> {
   func: &'a BBFunction,
-  out: &mut T,
-  mut value_store: Environment,
+  value_store: Environment,
+  curr_block_idx: usize,
+  // The index of the next instruction to run in the current block
+  instr_idx: usize,
+  next_block_idx: Option<usize>,
+  last_label: Option<&'a String>,
+  current_label: Option<&'a String>,
+  // The variable of the caller that the return value is assigned to
+  dest: Option<u32>,
+  // The call stack that this frame is on top of when profiling
+  stack: usize,
+}
+
+impl<'a> Frame<'a> {
+  const fn new(
+    func: &'a BBFunction,
+    value_store: Environment,
+    dest: Option<u32>,
+    stack: usize,
+  ) -> Self {
+    Self {
+      func,
+      value_store,
+      curr_block_idx: 0,
+      instr_idx: 0,
+      next_block_idx: None,
+      last_label: None,
+      current_label: None,
+      dest,
+      stack,
+    }
+  }
+
+  // Starts running ```func``` in place of the current function, which will return whatever ```func``` returns. The replaced frame is given back.
+  const fn replace(
+    &mut self,
+    func: &'a BBFunction,
+    value_store: Environment,
+    stack: usize,
+  ) -> Self {
+    let dest = self.dest;
+    std::mem::replace(self, Self::new(func, value_store, dest, stack))
+  }
+
+  const fn control(&self) -> Control<'a> {
+    Control {
+      curr_block_idx: self.curr_block_idx,
+      instr_idx: self.instr_idx,
+      next_block_idx: self.next_block_idx,
+      last_label: self.last_label,
+      current_label: self.current_label,
+    }
+  }
+
+  const fn set_control(&mut self, control: Control<'a>) {
+    self.curr_block_idx = control.curr_block_idx;
+    self.instr_idx = control.instr_idx;
+    self.next_block_idx = control.next_block_idx;
+    self.last_label = control.last_label;
+    self.current_label = control.current_label;
+  }
+}
+
+// Where a frame is up to, which is everything about it besides its variables that running an instruction can change
+#[derive(Clone, Copy)]
+struct Control<'a> {
+  curr_block_idx: usize,
+  instr_idx: usize,
+  next_block_idx: Option<usize>,
+  last_label: Option<&'a String>,
+  current_label: Option<&'a String>,
+}
+
+// How to put back a change to the call stack
+enum Undo<'a> {
+  // The variable ```slot``` of the frame at index ```frame``` of the call stack held ```old``` before it was assigned
+  Set { frame: usize, slot: u32, old: Value },
+  // The frame at index ```frame``` of the call stack was here before it moved to another block
+  Control { frame: usize, control: Control<'a> },
+  Push,
+  Pop(Frame<'a>),
+  Replace(Frame<'a>),
+}
+
+// Everything needed to go back to just before an instruction was run
+struct Step<'a> {
+  // The index in the call stack of the function that ran the instruction and where it was up to
+  frame: usize,
+  func: &'a BBFunction,
+  control: Control<'a>,
+  // The lengths of the undo logs
+  undo: usize,
+  heap: usize,
+  gc: usize,
+  instruction_count: u64,
+  written: usize,
+}
+
+// A record of the instructions that have been run along with how to undo them, so that the debugger can step backwards
+struct History<'a> {
+  steps: Vec<Step<'a>>,
+  undo: Vec<Undo<'a>>,
+  // The most steps to keep, after which the oldest are forgotten
+  max_steps: usize,
+}
+
+// A call is in tail position when the caller returns straight after with the result of the call
+fn is_tail_call(curr_block: &BasicBlock, call_idx: usize, dest: Option<u32>) -> bool {
+  match (
+    curr_block.instrs.get(call_idx + 1),
+    curr_block.numified_instrs.get(call_idx + 1),
+  ) {
+    (
+      Some(Instruction::Effect {
+        op: bril_rs::EffectOps::Return,
+        ..
+      }),
+      Some(numified_ret),
+    ) => numified_ret.args.first().copied() == dest,
+    _ => false,
+  }
+}
+
+// Frees everything that is no longer referenced once the current function returns ```result```
+fn exit_function(
   heap: &mut Heap,
   gc: &mut Collector,
-  instruction_count: &mut u32,
-) -> Result<Option<Value>, PositionalInterpError> {
-  // Map from variable name to value.
-  let mut last_label;
-  let mut current_label = None;
-  let mut curr_block_idx = 0;
-  let mut result = None;
+  result: Option<&Value>,
+) -> Result<(), InterpError> {
+  let garbo = gc.exeunt();
+  match result {
+    Some(Value::Pointer(ptr)) => {
+      for garb in garbo.iter() {
+        if ptr.base != *garb {
+          let pointer = Pointer {
+            base: *garb,
+            offset: 0,
+          };
+          heap.free(&pointer)?;
+        }
+      }
+    }
+    _ => {
+      for garb in garbo.iter() {
+        let pointer = Pointer {
+          base: *garb,
+          offset: 0,
+        };
+        heap.free(&pointer)?;
+      }
+    }
+  }
+  Ok(())
+}
 
-    gc.enter();
+/// A bril program part way through being run, which is advanced one instruction at a time by [`Interpreter::step`]. [`execute_main`] runs one of these until it finishes while the debugger looks at it between steps.
+pub(crate) struct Interpreter<'a, 'r, T: std::io::Write> {
+  prog: &'a BBProgram,
+  // Calls push onto this instead of recursing so that deep recursion in a bril program can't overflow the stack of the interpreter
+  call_stack: Vec<Frame<'a>>,
+  out: Output<T>,
+  heap: Heap,
+  gc: Collector,
+  instruction_count: u64,
+  // This is u64::MAX when there is no limit so that entering a block only has to compare against it
+  max_instructions: u64,
+  max_call_depth: usize,
+  profile: Option<&'r mut Profile<'a>>,
+  trace: Option<&'r mut Tracer>,
+  history: Option<History<'a>>,
+  // Whether "main" waits for more instructions once it gets to the end instead of returning, which is how the REPL runs it
+  keep_main: bool,
+}
 
-  loop {
-    let curr_block = &func.blocks[curr_block_idx];
-    let curr_instrs = &curr_block.instrs;
-    let curr_numified_instrs = &curr_block.numified_instrs;
-    // WARNING!!! We can add the # of instructions at once because you can only jump to a new block at the end. This may need to be changed if speculation is implemented
-    *instruction_count += curr_instrs.len() as u32;
-    last_label = current_label;
-    current_label = curr_block.label.as_ref();
+/// Where a function on the call stack of an [`Interpreter`] is up to
+pub(crate) struct Location<'a> {
+  pub(crate) func: &'a BBFunction,
+  pub(crate) label: Option<&'a String>,
+  /// The next instruction to run for the innermost function, or the call that is being waited on for the others
+  pub(crate) instr: &'a Instruction,
+  /// Whether ```instr``` is the first instruction of its basic block
+  pub(crate) block_start: bool,
+}
 
-    let mut next_block_idx = if curr_block.exit.len() == 1 {
+impl<'a, 'r, T: std::io::Write> Interpreter<'a, 'r, T> {
+  /// Sets up a call to the "main" function of ```prog``` with ```input_args``` as arguments
+  pub(crate) fn new(
+    prog: &'a BBProgram,
+    out: T,
+    input_args: &[String],
+    limits: &Limits,
+    profile: Option<&'r mut Profile<'a>>,
+    trace: Option<&'r mut Tracer>,
+  ) -> Result<Self, PositionalInterpError> {
+    let main_func = prog
+      .get("main")
+      .ok_or_else(|| PositionalInterpError::new(InterpError::NoMainFunction))?;
+
+    if main_func.return_type.is_some() {
+      return Err(InterpError::NonEmptyRetForFunc(main_func.name.clone()))
+        .map_err(|e| e.add_pos(main_func.pos.clone()));
+    }
+
+    let env = Environment::new(main_func.num_of_vars);
+    let value_store = parse_args(env, &main_func.args, &main_func.args_as_nums, input_args)
+      .map_err(|e| e.add_pos(main_func.pos.clone()))?;
+
+    let mut interp = Self {
+      prog,
+      call_stack: Vec::new(),
+      out: Output::new(out, limits.max_output_bytes),
+      heap: Heap::new(limits.max_heap_cells),
+      gc: Collector::default(),
+      instruction_count: 0,
+      max_instructions: limits.max_instructions.unwrap_or(u64::MAX),
+      max_call_depth: limits.max_call_depth,
+      profile,
+      trace,
+      history: None,
+      keep_main: false,
+    };
+    let stack = interp
+      .profile
+      .as_deref_mut()
+      .map_or(0, |p| p.enter_function(None, &main_func.name));
+    interp.gc.enter();
+    interp
+      .call_stack
+      .push(Frame::new(main_func, value_store, None, stack));
+    interp.enter_block()?;
+    interp.settle::<false>()?;
+    Ok(interp)
+  }
+
+  /// Whether "main" has returned
+  pub(crate) const fn is_finished(&self) -> bool {
+    self.call_stack.is_empty()
+  }
+
+  /// The number of functions which have been called but have not returned
+  pub(crate) const fn depth(&self) -> usize {
+    self.call_stack.len()
+  }
+
+  /// Runs the program until it finishes
+  pub(crate) fn run(&mut self) -> Result<(), PositionalInterpError> {
+    if self.history.is_none() && self.trace.is_none() && self.profile.is_none() {
+      return self.run_plain();
+    }
+    while !self.is_finished() {
+      self.step()?;
+    }
+    Ok(())
+  }
+
+  // Runs the program until it finishes when there is nothing to record along the way. Each basic block is run in one loop and only calls and returns go through ```execute_next```, so plain runs don't pay for the checks that stepping needs.
+  fn run_plain(&mut self) -> Result<(), PositionalInterpError> {
+    while let Some(frame) = self.call_stack.last_mut() {
+      let curr_block = &frame.func.blocks[frame.curr_block_idx];
+      let start = frame.instr_idx;
+      let mut ran = start;
+      for (code, numified_code) in curr_block.instrs[start..]
+        .iter()
+        .zip(&curr_block.numified_instrs[start..])
+      {
+        match code {
+          Instruction::Constant {
+            const_type, value, ..
+          } => frame
+            .value_store
+            .set(numified_code.dest.unwrap(), const_value(const_type, value)),
+          Instruction::Value {
+            op, labels, pos, ..
+          } if !matches!(op, bril_rs::ValueOps::Call) => execute_value_op(
+            op,
+            numified_code.dest.unwrap(),
+            &numified_code.args,
+            labels,
+            &mut frame.value_store,
+            &mut self.heap,
+            &mut self.gc,
+            frame.last_label,
+          )
+          .map_err(|e| e.add_pos(pos.clone()))?,
+          Instruction::Effect { op, pos, .. }
+            if !matches!(op, bril_rs::EffectOps::Call | bril_rs::EffectOps::Return) =>
+          {
+            execute_effect_op(
+              op,
+              &numified_code.args,
+              curr_block,
+              &mut self.out,
+              &frame.value_store,
+              &mut self.heap,
+              &mut frame.next_block_idx,
+            )
+            .map_err(|e| e.add_pos(pos.clone()))?;
+          }
+          // These move to another function
+          _ => break,
+        }
+        ran += 1;
+      }
+      frame.instr_idx = ran;
+      if ran < curr_block.instrs.len() {
+        self.execute_next::<false>()?;
+      } else if let Some(idx) = frame.next_block_idx {
+        frame.curr_block_idx = idx;
+        frame.instr_idx = 0;
+        self.enter_block()?;
+      } else {
+        self.settle::<false>()?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Checks that everything was freed once the program has finished and returns the number of instructions that were run
+  pub(crate) fn finish(self) -> Result<u64, PositionalInterpError> {
+    if !self.heap.is_empty() {
+      return Err(InterpError::MemLeak)
+        .map_err(|e| e.add_pos(self.prog.get("main").unwrap().pos.clone()));
+    }
+    Ok(self.instruction_count)
+  }
+
+  // Counts the instructions of the block that the innermost function has just moved to
+  #[inline(always)]
+  fn enter_block(&mut self) -> Result<(), PositionalInterpError> {
+    let frame = self.call_stack.last_mut().unwrap();
+    let curr_block = &frame.func.blocks[frame.curr_block_idx];
+    // WARNING!!! We can add the # of instructions at once because you can only jump to a new block at the end. This may need to be changed if speculation is implemented
+    self.instruction_count += curr_block.instrs.len() as u64;
+    if let Some(profile) = self.profile.as_deref_mut() {
+      profile.enter_block(frame.stack, frame.curr_block_idx, curr_block.instrs.len());
+    }
+    if self.instruction_count > self.max_instructions {
+      return Err(
+        InterpError::InstructionLimit(self.max_instructions).add_pos(
+          curr_block
+            .instrs
+            .first()
+            .and_then(Instruction::get_pos)
+            .cloned(),
+        ),
+      );
+    }
+    frame.last_label = frame.current_label;
+    frame.current_label = curr_block.label.as_ref();
+    frame.next_block_idx = if curr_block.exit.len() == 1 {
       Some(curr_block.exit[0])
     } else {
       None
     };
+    Ok(())
+  }
 
-    for (code, numified_code) in curr_instrs.iter().zip(curr_numified_instrs.iter()) {
-      match code {
-        Instruction::Constant {
-          op: bril_rs::ConstOps::Const,
-          dest: _,
-          const_type,
-          value,
-          pos: _,
-        } => {
-          // Integer literals can be promoted to Floating point
-          if const_type == &bril_rs::Type::Float {
-            match value {
-              bril_rs::Literal::Int(i) => {
-                value_store.set(numified_code.dest.unwrap(), Value::Float(*i as f64))
-              }
-              bril_rs::Literal::Float(f) => {
-                value_store.set(numified_code.dest.unwrap(), Value::Float(*f))
-              }
-              // this is safe because we type check this beforehand
-              bril_rs::Literal::Bool(_) => unsafe { unreachable_unchecked() },
-            }
-          } else {
-            value_store.set(numified_code.dest.unwrap(), Value::from(value));
-          };
+  // Moves on from the ends of blocks and functions until there is an instruction to run or the program has finished
+  fn settle<const HISTORY: bool>(&mut self) -> Result<(), PositionalInterpError> {
+    while let Some(top) = self.call_stack.len().checked_sub(1) {
+      let frame = &mut self.call_stack[top];
+      if frame.instr_idx < frame.func.blocks[frame.curr_block_idx].instrs.len() {
+        break;
+      }
+      if let Some(idx) = frame.next_block_idx {
+        if let Some(history) = self.history.as_mut().filter(|_| HISTORY) {
+          history.undo.push(Undo::Control {
+            frame: top,
+            control: frame.control(),
+          });
+        }
+        frame.curr_block_idx = idx;
+        frame.instr_idx = 0;
+        self.enter_block()?;
+      } else if self.keep_main && top == 0 {
+        break;
+      } else {
+        // Falling off the end of a function is the same as an empty return
+        let func = frame.func;
+        self.return_from_function::<HISTORY>(None, func.pos.as_ref())?;
+      }
+    }
+    Ok(())
+  }
+
+  // Pops the innermost function and gives ```result``` to its caller
+  fn return_from_function<const HISTORY: bool>(
+    &mut self,
+    result: Option<Value>,
+    pos: Option<&Position>,
+  ) -> Result<(), PositionalInterpError> {
+    exit_function(&mut self.heap, &mut self.gc, result.as_ref())
+      .map_err(|e| e.add_pos(pos.cloned()))?;
+    let frame = self.call_stack.pop().unwrap();
+    let dest = frame.dest;
+    if let Some(history) = self.history.as_mut().filter(|_| HISTORY) {
+      history.undo.push(Undo::Pop(frame));
+    }
+    if let (Some(caller_idx), Some(dest), Some(ret_val)) =
+      (self.call_stack.len().checked_sub(1), dest, result)
+    {
+      let caller = &mut self.call_stack[caller_idx];
+      // If assigning to pointer, bump in gc
+      if let Value::Pointer(ref pointer) = ret_val {
+        self.gc.increment(pointer.base);
+      }
+      if let Some(history) = self.history.as_mut().filter(|_| HISTORY) {
+        history.undo.push(Undo::Set {
+          frame: caller_idx,
+          slot: dest,
+          old: caller.value_store.get(&dest).clone(),
+        });
+      }
+      caller.value_store.set(dest, ret_val);
+    }
+    Ok(())
+  }
+
+  /// Starts recording the instructions that are run so that the program can be stepped backwards with [`Interpreter::step_back`]. Only the last ```max_steps``` or more are kept.
+  pub(crate) fn record_history(&mut self, max_steps: usize) {
+    self.history = Some(History {
+      steps: Vec::new(),
+      undo: Vec::new(),
+      max_steps,
+    });
+    self.heap.journal = Some(Vec::new());
+    self.gc.record_history();
+  }
+
+  // Drops the oldest half of the history once it is full, so that the cost of moving what is kept is spread over many steps
+  fn forget_history(&mut self) {
+    let Some(history) = &mut self.history else {
+      return;
+    };
+    if history.steps.len() <= history.max_steps {
+      return;
+    }
+    let forget = history.steps.len() - (history.max_steps / 2).max(1);
+    let first = &history.steps[forget];
+    let (undo, heap, gc) = (first.undo, first.heap, first.gc);
+    history.steps.drain(..forget);
+    for step in &mut history.steps {
+      step.undo -= undo;
+      step.heap -= heap;
+      step.gc -= gc;
+    }
+    history.undo.drain(..undo);
+    if let Some(journal) = &mut self.heap.journal {
+      journal.drain(..heap);
+    }
+    self.gc.forget(gc);
+  }
+
+  /// Undoes the last instruction that was run, returning false if there is nothing recorded to undo
+  pub(crate) fn step_back(&mut self) -> bool {
+    let Some(step) = self.history.as_mut().and_then(|h| h.steps.pop()) else {
+      return false;
+    };
+    self.rewind(step);
+    true
+  }
+
+  fn rewind(&mut self, step: Step<'a>) {
+    let Some(history) = &mut self.history else {
+      return;
+    };
+    for undo in history.undo.drain(step.undo..).rev() {
+      match undo {
+        Undo::Set { frame, slot, old } => self.call_stack[frame].value_store.set(slot, old),
+        Undo::Control { frame, control } => self.call_stack[frame].set_control(control),
+        Undo::Push => {
+          self.call_stack.pop();
+        }
+        Undo::Pop(frame) => self.call_stack.push(frame),
+        Undo::Replace(frame) => *self.call_stack.last_mut().unwrap() = frame,
+      }
+    }
+    self.heap.rewind(step.heap);
+    self.gc.rewind(step.gc);
+    self.instruction_count = step.instruction_count;
+    self.out.written = step.written;
+    self.call_stack[step.frame].set_control(step.control);
+  }
+
+  /// When the variable ```name``` of the innermost function was last assigned, as the number of instructions ago and the instruction that did it. This is [`None`] if it has not been assigned since the function was called, or not as far back as the history goes.
+  pub(crate) fn last_write(&self, name: &str) -> Option<(usize, Location<'a>)> {
+    let history = self.history.as_ref()?;
+    let frame = self.call_stack.len().checked_sub(1)?;
+    let slot = *self.call_stack[frame].func.num_var_map.get(name)?;
+    let mut end = history.undo.len();
+    for (i, step) in history.steps.iter().enumerate().rev() {
+      for undo in history.undo[step.undo..end].iter().rev() {
+        match undo {
+          Undo::Set {
+            frame: f, slot: s, ..
+          } if *f == frame && *s == slot => {
+            let block = &step.func.blocks[step.control.curr_block_idx];
+            let location = Location {
+              func: step.func,
+              label: block.label.as_ref(),
+              instr: &block.instrs[step.control.instr_idx],
+              block_start: step.control.instr_idx == 0,
+            };
+            return Some((history.steps.len() - i, location));
+          }
+          // The function was called here, so anything earlier belongs to another call
+          Undo::Push if step.frame + 1 == frame => return None,
+          Undo::Replace(_) if step.frame == frame => return None,
+          _ => {}
         }
+      }
+      end = step.undo;
+    }
+    None
+  }
+
+  /// Runs the next instruction. This does nothing once the program has finished. If the instruction fails, it is left as the next one so that where the program stopped can be looked at.
+  pub(crate) fn step(&mut self) -> Result<(), PositionalInterpError> {
+    let Some(history) = &mut self.history else {
+      return self.execute_next::<false>();
+    };
+    let Some(frame) = self.call_stack.last() else {
+      return Ok(());
+    };
+    history.steps.push(Step {
+      frame: self.call_stack.len() - 1,
+      func: frame.func,
+      control: frame.control(),
+      undo: history.undo.len(),
+      heap: self.heap.journal.as_ref().map_or(0, Vec::len),
+      gc: self.gc.history_len(),
+      instruction_count: self.instruction_count,
+      written: self.out.written,
+    });
+    let result = self.execute_next::<true>();
+    if result.is_err() {
+      // Put back whatever the instruction did before it failed
+      if let Some(step) = self.history.as_mut().and_then(|h| h.steps.pop()) {
+        self.rewind(step);
+      }
+    }
+    self.forget_history();
+    result
+  }
+
+  // ```HISTORY``` is whether to record how to undo the instruction, which is decided once by [`Interpreter::step`] so that running without history doesn't look for it at every change
+  fn execute_next<const HISTORY: bool>(&mut self) -> Result<(), PositionalInterpError> {
+    let depth = self.call_stack.len();
+    let Some(frame) = self.call_stack.last_mut() else {
+      return Ok(());
+    };
+    let func = frame.func;
+    let curr_block = &func.blocks[frame.curr_block_idx];
+    let call_idx = frame.instr_idx;
+    let code = &curr_block.instrs[call_idx];
+    let numified_code = &curr_block.numified_instrs[call_idx];
+
+    if let (Some(history), Some(dest)) = (
+      self.history.as_mut().filter(|_| HISTORY),
+      numified_code.dest,
+    ) {
+      // Calls assign to their destination when they return
+      if !matches!(
+        code,
         Instruction::Value {
+          op: bril_rs::ValueOps::Call,
+          ..
+        }
+      ) {
+        history.undo.push(Undo::Set {
+          frame: depth - 1,
+          slot: dest,
+          old: frame.value_store.get(&dest).clone(),
+        });
+      }
+    }
+
+    // What the instruction assigned, which is only kept when tracing
+    let mut written = None;
+    let tracing = self.trace.is_some();
+    match code {
+      Instruction::Constant {
+        op: bril_rs::ConstOps::Const,
+        dest: _,
+        const_type,
+        value,
+        pos: _,
+      } => {
+        frame
+          .value_store
+          .set(numified_code.dest.unwrap(), const_value(const_type, value));
+        if tracing {
+          written = Some(frame.value_store.get(&numified_code.dest.unwrap()).clone());
+        }
+        frame.instr_idx += 1;
+      }
+      Instruction::Value {
+        op: bril_rs::ValueOps::Call,
+        funcs,
+        pos,
+        ..
+      }
+      | Instruction::Effect {
+        op: bril_rs::EffectOps::Call,
+        funcs,
+        pos,
+        ..
+      } => {
+        let callee_func = self
+          .prog
+          .get(&funcs[0])
+          .ok_or_else(|| InterpError::FuncNotFound(funcs[0].clone()))
+          .map_err(|e| e.add_pos(pos.clone()))?;
+
+        let next_env = make_func_args(callee_func, &numified_code.args, &frame.value_store);
+        if numified_code.dest.is_none() {
+          // The args are numbered by the variables of the caller, not of the callee
+          for arg in numified_code.args.iter() {
+            if let Value::Pointer(ref ptr) = frame.value_store.get(arg) {
+              self.gc.increment(ptr.base);
+            }
+          }
+        }
+
+        let caller_stack = frame.stack;
+        if is_tail_call(curr_block, call_idx, numified_code.dest) {
+          let stack = self.profile.as_deref_mut().map_or(0, |p| {
+            let caller = p.caller(caller_stack);
+            p.enter_function(caller, &callee_func.name)
+          });
+          // The garbage of this function is collected along with the callee's when it returns
+          let replaced = frame.replace(callee_func, next_env, stack);
+          if let Some(history) = self.history.as_mut().filter(|_| HISTORY) {
+            history.undo.push(Undo::Replace(replaced));
+          }
+        } else if depth >= self.max_call_depth {
+          return Err(InterpError::StackOverflow(self.max_call_depth).add_pos(pos.clone()));
+        } else {
+          let stack = self.profile.as_deref_mut().map_or(0, |p| {
+            p.enter_function(Some(caller_stack), &callee_func.name)
+          });
+          frame.instr_idx += 1;
+          self.gc.enter();
+          self
+            .call_stack
+            .push(Frame::new(callee_func, next_env, numified_code.dest, stack));
+          if let Some(history) = self.history.as_mut().filter(|_| HISTORY) {
+            history.undo.push(Undo::Push);
+          }
+        }
+        self.enter_block()?;
+      }
+      Instruction::Effect {
+        op: bril_rs::EffectOps::Return,
+        pos,
+        ..
+      } => {
+        let result = func
+          .return_type
+          .as_ref()
+          .map(|_| get_value(&frame.value_store, 0, &numified_code.args).clone());
+        if tracing {
+          written = result.clone();
+        }
+        self.return_from_function::<HISTORY>(result, pos.as_ref())?;
+      }
+      Instruction::Value {
+        op,
+        dest: _,
+        op_type: _,
+        args: _,
+        labels,
+        funcs: _,
+        pos,
+      } => {
+        execute_value_op(
           op,
-          dest: _,
-          op_type: _,
-          args: _,
+          numified_code.dest.unwrap(),
+          &numified_code.args,
           labels,
-          funcs,
-          pos,
-        } => {
-          execute_value_op(
-            prog,
-            op,
-            numified_code.dest.unwrap(),
-            &numified_code.args,
-            labels,
-            funcs,
-            out,
-            &mut value_store,
-            heap,
-            gc,
-            last_label,
-            instruction_count,
-          )
-          .map_err(|e| e.add_pos(*pos))?;
+          &mut frame.value_store,
+          &mut self.heap,
+          &mut self.gc,
+          frame.last_label,
+        )
+        .map_err(|e| e.add_pos(pos.clone()))?;
+        if tracing {
+          written = Some(frame.value_store.get(&numified_code.dest.unwrap()).clone());
         }
-        Instruction::Effect {
+        frame.instr_idx += 1;
+      }
+      Instruction::Effect {
+        op,
+        args: _,
+        labels: _,
+        funcs: _,
+        pos,
+      } => {
+        execute_effect_op(
           op,
-          args: _,
-          labels: _,
-          funcs,
-          pos,
-        } => {
-          result = execute_effect_op(
-            prog,
-            func,
-            op,
-            &numified_code.args,
-            funcs,
-            curr_block,
-            out,
-            &value_store,
-            heap,
-            gc,
-            &mut next_block_idx,
-            instruction_count,
-          )
-          .map_err(|e| e.add_pos(*pos))?;
-        }
+          &numified_code.args,
+          curr_block,
+          &mut self.out,
+          &frame.value_store,
+          &mut self.heap,
+          &mut frame.next_block_idx,
+        )
+        .map_err(|e| e.add_pos(pos.clone()))?;
+        frame.instr_idx += 1;
       }
     }
-    if let Some(idx) = next_block_idx {
-      curr_block_idx = idx;
-    } else {
-	break;
-    }
-  }
-    let garbo = gc.exeunt();
-    match result {
-        Some(Value::Pointer(ref ptr)) => {
-            for garb in garbo.iter() {
-		if ptr.base != *garb {
-		    let pointer = Pointer {
-			base: *garb,
-			offset: 0,
-		    };
-		    heap.free(&pointer);
-		}
-            }
+
+    if let Some(tracer) = self.trace.as_deref_mut() {
+      tracer
+        .record(
+          &func.name,
+          curr_block.label.as_ref(),
+          code,
+          written.as_ref(),
+        )
+        .map_err(|e| e.add_pos(code.get_pos().cloned()))?;
+    }
+    self.settle::<HISTORY>()
+  }
+
+  /// Where each function on the call stack is up to, from the innermost out. This is empty once the program has finished.
+  pub(crate) fn locations(&self) -> Vec<Location<'a>> {
+    self
+      .call_stack
+      .iter()
+      .rev()
+      .enumerate()
+      .map(|(i, frame)| {
+        let block = &frame.func.blocks[frame.curr_block_idx];
+        // Callers have already moved past the call they are waiting on
+        let idx = if i == 0 {
+          frame.instr_idx
+        } else {
+          frame.instr_idx - 1
+        };
+        Location {
+          func: frame.func,
+          label: block.label.as_ref(),
+          instr: &block.instrs[idx],
+          block_start: idx == 0,
         }
-        _ => {
-            for garb in garbo.iter() {
-		let pointer = Pointer {
-		    base: *garb,
-		    offset: 0,
-		};
-		heap.free(&pointer);
-            }
+      })
+      .collect()
+  }
+
+  /// Whether the innermost function has just been called and has not run anything yet
+  pub(crate) fn at_function_entry(&self) -> bool {
+    self
+      .call_stack
+      .last()
+      .is_some_and(|f| f.curr_block_idx == 0 && f.instr_idx == 0 && f.last_label.is_none())
+  }
+
+  /// The variables which have been assigned in the function ```depth``` frames out from the innermost one, sorted by name
+  pub(crate) fn variables(&self, depth: usize) -> Vec<(&'a str, String)> {
+    self
+      .call_stack
+      .iter()
+      .rev()
+      .nth(depth)
+      .map_or_else(Vec::new, |frame| {
+        assigned_variables(frame.func, &frame.value_store)
+      })
+  }
+
+  /// The base of the allocation that the pointer variable ```name``` points into, in the function ```depth``` frames out from the innermost one
+  pub(crate) fn pointer_base(&self, depth: usize, name: &str) -> Option<usize> {
+    let frame = self.call_stack.iter().rev().nth(depth)?;
+    let num = frame.func.num_var_map.get(name)?;
+    match frame.value_store.env.get(*num as usize)? {
+      Value::Pointer(p) => Some(p.base),
+      _ => None,
+    }
+  }
+
+  /// The base and size of every allocation on the heap, sorted by base
+  pub(crate) fn allocations(&self) -> Vec<(usize, usize)> {
+    let mut allocs: Vec<(usize, usize)> = self
+      .heap
+      .memory
+      .iter()
+      .map(|(base, cells)| (*base, cells.len()))
+      .collect();
+    allocs.sort_unstable();
+    allocs
+  }
+
+  /// The contents of the allocation at ```base```, with [`None`] for cells that have not been written to
+  pub(crate) fn allocation(&self, base: usize) -> Option<Vec<Option<String>>> {
+    self
+      .heap
+      .memory
+      .get(&base)
+      .map(|cells| cells.iter().map(Value::describe).collect())
+  }
+}
+
+// The variables of ```func``` which have been assigned in ```value_store```, sorted by name
+fn assigned_variables<'a>(
+  func: &'a BBFunction,
+  value_store: &Environment,
+) -> Vec<(&'a str, String)> {
+  let mut vars: Vec<(&'a str, String)> = func
+    .num_var_map
+    .iter()
+    .filter_map(|(name, num)| {
+      value_store
+        .env
+        .get(*num as usize)
+        .and_then(Value::describe)
+        .map(|v| (name.as_str(), v))
+    })
+    .collect();
+  vars.sort_unstable();
+  vars
+}
+
+/// The variables of "main" and the heap, which the REPL keeps from one instruction to the next
+pub(crate) struct Globals {
+  value_store: Environment,
+  heap: Heap,
+  gc: Collector,
+}
+
+impl Globals {
+  pub(crate) fn new() -> Self {
+    let mut gc = Collector::default();
+    // Everything is run inside of "main", which never returns
+    gc.enter();
+    Self {
+      value_store: Environment::new(0),
+      heap: Heap::new(None),
+      gc,
+    }
+  }
+
+  /// The variables of ```main``` which have been assigned, sorted by name
+  pub(crate) fn variables<'a>(&self, main: &'a BBFunction) -> Vec<(&'a str, String)> {
+    assigned_variables(main, &self.value_store)
+  }
+}
+
+/// Runs the last instruction of "main" in ```prog```, which the REPL has just added, with the variables and heap that ```globals``` kept from the instructions before it. "main" has to be a single basic block. When the instruction fails, any functions it called are abandoned and ```globals``` keeps whatever was done up until then.
+pub(crate) fn execute_last<T: std::io::Write>(
+  prog: &BBProgram,
+  out: T,
+  globals: &mut Globals,
+) -> Result<(), PositionalInterpError> {
+  let main_func = prog
+    .get("main")
+    .ok_or_else(|| PositionalInterpError::new(InterpError::NoMainFunction))?;
+  let mut value_store = std::mem::replace(&mut globals.value_store, Environment::new(0));
+  // The new instruction may have brought in new variables
+  value_store
+    .env
+    .resize(main_func.num_of_vars as usize, Value::default());
+  let mut frame = Frame::new(main_func, value_store, None, 0);
+  frame.instr_idx = main_func.blocks[0].instrs.len() - 1;
+
+  let mut interp = Interpreter {
+    prog,
+    call_stack: vec![frame],
+    out: Output::new(out, None),
+    heap: std::mem::replace(&mut globals.heap, Heap::new(None)),
+    gc: std::mem::take(&mut globals.gc),
+    instruction_count: 0,
+    max_instructions: u64::MAX,
+    max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+    profile: None,
+    trace: None,
+    history: None,
+    keep_main: true,
+  };
+  let mut result = loop {
+    match interp.call_stack.as_slice() {
+      [main] if main.instr_idx == main.func.blocks[main.curr_block_idx].instrs.len() => {
+        break Ok(())
+      }
+      _ => {
+        if let Err(e) = interp.step() {
+          break Err(e);
         }
+      }
     }
-    return Ok(result);
+  };
+
+  while interp.call_stack.len() > 1 {
+    let func = interp.call_stack.last().unwrap().func;
+    let freed = exit_function(&mut interp.heap, &mut interp.gc, None)
+      .map_err(|e| e.add_pos(func.pos.clone()));
+    result = result.and(freed);
+    interp.call_stack.pop();
+  }
+  globals.value_store = interp.call_stack.pop().unwrap().value_store;
+  globals.heap = interp.heap;
+  globals.gc = interp.gc;
+  result
 }
 
 fn parse_args(
@@ -752,45 +1630,19 @@ fn parse_args(
   }
 }
 
-/// The entrance point to the interpreter. It runs over a ```prog```:[`BBProgram`] starting at the "main" function with ```input_args``` as input. Print statements output to ```out``` which implements [std::io::Write]. You also need to include whether you want the interpreter to count the number of instructions run with ```profiling```. This information is outputted to [std::io::stderr]
-// todo we could probably output the profiling thing to a user defined location. If the program can output to a file, you should probably also be allowed to output this debug info to a file as well.
-pub fn execute_main<T: std::io::Write>(
-  prog: &BBProgram,
-  mut out: T,
+/// The entrance point to the interpreter. It runs over a ```prog```:[`BBProgram`] starting at the "main" function with ```input_args``` as input. Print statements output to ```out``` which implements [std::io::Write]. You also need to include whether you want the interpreter to count the number of instructions run with ```profiling```. This information is outputted to [std::io::stderr]. The program is stopped with an error if it goes over any of the ```limits```. For a breakdown of where those instructions were run, pass a [`Profile`] as ```profile``` which can then be written out with [`Profile::write`]. Each instruction is also written out as it runs if there is a [`Tracer`] in ```trace```.
+pub fn execute_main<'a, T: std::io::Write>(
+  prog: &'a BBProgram,
+  out: T,
   input_args: &[String],
   profiling: bool,
+  limits: &Limits,
+  profile: Option<&mut Profile<'a>>,
+  trace: Option<&mut Tracer>,
 ) -> Result<(), PositionalInterpError> {
-  let main_func = prog
-    .get("main")
-    .ok_or_else(|| PositionalInterpError::new(InterpError::NoMainFunction))?;
-
-  if main_func.return_type.is_some() {
-    return Err(InterpError::NonEmptyRetForFunc(main_func.name.clone()))
-      .map_err(|e| e.add_pos(main_func.pos));
-  }
-
-  let env = Environment::new(main_func.num_of_vars);
-  let mut heap = Heap::default();
-  let mut gc = Collector::default();
-
-  let value_store = parse_args(env, &main_func.args, &main_func.args_as_nums, input_args)
-    .map_err(|e| e.add_pos(main_func.pos))?;
-
-  let mut instruction_count = 0;
-
-  execute(
-    prog,
-    main_func,
-    &mut out,
-    value_store,
-    &mut heap,
-    &mut gc,
-    &mut instruction_count,
-  )?;
-
-  if !heap.is_empty() {
-    return Err(InterpError::MemLeak).map_err(|e| e.add_pos(main_func.pos));
-  }
+  let mut interp = Interpreter::new(prog, out, input_args, limits, profile, trace)?;
+  interp.run()?;
+  let instruction_count = interp.finish()?;
 
   if profiling {
     eprintln!("total_dyn_inst: {instruction_count}");
@@ -5,46 +5,197 @@
 #![allow(clippy::too_many_arguments)]
 
 use std::error::Error;
+use std::path::PathBuf;
 
 use basic_block::BBProgram;
+use bril_rs::infer::infer_types;
+use bril_rs::link::link;
 use bril_rs::Program;
+use error::PositionalInterpError;
+use interp::Limits;
+use profile::{Profile, ProfileFormat};
+use trace::Tracer;
 
 /// The internal representation of brilirs, provided a ```TryFrom<Program>``` conversion
 pub mod basic_block;
-/// Provides ```check::type_check``` to validate [Program]
+/// Provides ```check::type_check``` and ```check::ssa_check``` to validate [Program]
 pub mod check;
 #[doc(hidden)]
 pub mod cli;
+mod dap;
+mod debug;
 
+/// Provides ```garbage::Collector``` to count the references to each allocation so that the ones no longer reachable can be freed
 pub mod garbage;
 
 mod error;
 /// Provides ```interp::execute_main``` to execute [Program] that have been converted into [BBProgram]
 pub mod interp;
+mod lsp;
+/// Provides ```profile::Profile``` to record where instructions were run by ```interp::execute_main```
+pub mod profile;
+mod repl;
+mod rpc;
+/// Provides ```trace::Tracer``` to write out each instruction run by ```interp::execute_main```
+pub mod trace;
+
+/// The programs to run along with the files they were read from, which the paths of their imports are relative to
+#[doc(hidden)]
+pub type Inputs = Vec<(Option<PathBuf>, Box<dyn std::io::Read>)>;
+
+// Reads in programs in text form if ```text``` and JSON otherwise, and links them together along with the files they import, filling in any types they leave out
+fn parse_program(inputs: Inputs, text: bool) -> Result<Program, Box<dyn Error>> {
+  // Positions only need to say which file they are in when there is more than one
+  let several = inputs.len() > 1;
+  let mut programs = Vec::new();
+  for (path, mut input) in inputs {
+    // It's a little confusing because of the naming conventions.
+    //      - bril_rs takes file.json as input
+    //      - bril2json takes file.bril as input
+    let prog = match &path {
+      Some(path) if text && several => {
+        let mut buffer = String::new();
+        input.read_to_string(&mut buffer)?;
+        bril2json::parse_abstract_program_from_src(&buffer, true, &path.to_string_lossy())?
+      }
+      _ if text => bril2json::parse_abstract_program_from_read(input, true),
+      _ => bril_rs::load_abstract_program_from_read(input),
+    };
+    programs.push((path, prog));
+  }
+  // Imported files are read as JSON or text depending on their extension
+  let mut prog = link(programs, |path| {
+    bril2json::load_abstract_program_from_file(path, true)
+  })?;
+  // Hand-written text often leaves out types which can be worked out from how variables are used
+  infer_types(&mut prog)?;
+  let prog: Program = prog.try_into()?;
+  Ok(prog)
+}
+
+// Reads in a program and checks that it is well typed
+fn load_program(input: Inputs, text: bool) -> Result<BBProgram, Box<dyn Error>> {
+  let bbprog: BBProgram = parse_program(input, text)?.try_into()?;
+  check::type_check(&bbprog)?;
+  Ok(bbprog)
+}
 
 #[doc(hidden)]
 pub fn run_input<T: std::io::Write>(
-  input: Box<dyn std::io::Read>,
+  input: Inputs,
   out: T,
   input_args: Vec<String>,
   profiling: bool,
   check: bool,
+  ssa: bool,
   text: bool,
+  limits: &Limits,
+  profile_out: Option<(ProfileFormat, Box<dyn std::io::Write>)>,
+  mut trace: Option<Tracer>,
 ) -> Result<(), Box<dyn Error>> {
-  // It's a little confusing because of the naming conventions.
-  //      - bril_rs takes file.json as input
-  //      - bril2json takes file.bril as input
-  let prog: Program = if text {
-    bril2json::parse_abstract_program_from_read(input, true).try_into()?
-  } else {
-    bril_rs::load_abstract_program_from_read(input).try_into()?
-  };
-  let bbprog: BBProgram = prog.try_into()?;
-  check::type_check(&bbprog)?;
+  let bbprog = load_program(input, text)?;
+  if ssa {
+    check::ssa_check(&bbprog)?;
+  }
 
   if !check {
-    interp::execute_main(&bbprog, out, &input_args, profiling)?;
+    if let Some((format, profile_file)) = profile_out {
+      let mut profile = Profile::new();
+      let result = interp::execute_main(
+        &bbprog,
+        out,
+        &input_args,
+        profiling,
+        limits,
+        Some(&mut profile),
+        trace.as_mut(),
+      );
+      // The profile is still written when the program fails so that runaway programs can be looked into
+      profile.write(&bbprog, format, profile_file)?;
+      result?;
+    } else {
+      interp::execute_main(
+        &bbprog,
+        out,
+        &input_args,
+        profiling,
+        limits,
+        None,
+        trace.as_mut(),
+      )?;
+    }
+  }
+
+  Ok(())
+}
+
+/// Runs the program from ```input``` under the debugger, which reads commands from ```commands```. Output from the program and the debugger both go to stdout. Up to ```history``` instructions are recorded so that the program can be stepped backwards.
+#[doc(hidden)]
+pub fn debug_input(
+  input: Inputs,
+  commands: impl std::io::BufRead,
+  input_args: Vec<String>,
+  text: bool,
+  history: usize,
+) -> Result<(), Box<dyn Error>> {
+  let bbprog = load_program(input, text)?;
+  let mut interp = interp::Interpreter::new(
+    &bbprog,
+    std::io::stdout(),
+    &input_args,
+    &Limits::default(),
+    None,
+    None,
+  )?;
+  if history > 0 {
+    interp.record_history(history);
   }
+  debug::run_debugger(
+    debug::Debugger::new(interp),
+    commands,
+    &mut std::io::stdout(),
+  )?;
+  Ok(())
+}
 
+/// Runs the REPL, which reads Bril text and commands from ```commands```. The functions of the program from ```input```, if there is any, are defined to start with. Output from the REPL and the program both go to stdout.
+#[doc(hidden)]
+pub fn repl_input(
+  input: Option<Inputs>,
+  commands: impl std::io::BufRead,
+  text: bool,
+) -> Result<(), Box<dyn Error>> {
+  let mut repl = repl::Repl::new();
+  if let Some(input) = input {
+    repl.define(parse_program(input, text)?.functions)?;
+  }
+  repl::run_repl(repl, commands, &mut std::io::stdout())?;
   Ok(())
 }
+
+/// Serves the Debug Adapter Protocol over ```input``` and ```output``` so that editors can debug a program
+#[doc(hidden)]
+pub fn serve_dap(
+  input: impl std::io::BufRead,
+  output: impl std::io::Write,
+) -> Result<(), Box<dyn Error>> {
+  dap::serve(input, output)?;
+  Ok(())
+}
+
+/// Serves the Language Server Protocol over ```input``` and ```output``` so that editors can check and navigate Bril text
+#[doc(hidden)]
+pub fn serve_lsp(
+  input: impl std::io::BufRead,
+  output: impl std::io::Write,
+) -> Result<(), Box<dyn Error>> {
+  lsp::serve(input, output)?;
+  Ok(())
+}
+
+/// The exit code for when brilirs fails with ```e```. Errors from going over one of the [`Limits`] each have their own code and anything else is 2.
+#[doc(hidden)]
+pub fn exit_code(e: &(dyn Error + 'static)) -> i32 {
+  e.downcast_ref::<PositionalInterpError>()
+    .map_or(2, PositionalInterpError::exit_code)
+}
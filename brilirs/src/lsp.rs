@@ -0,0 +1,760 @@
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use bril_rs::infer::infer_types;
+use bril_rs::link::link;
+use bril_rs::ops::OPS;
+use bril_rs::wellformed::check_well_formed;
+use bril_rs::{ColRow, Position, Program};
+use fxhash::FxHashMap;
+use serde_json::{json, Value};
+
+use crate::basic_block::BBProgram;
+use crate::check;
+use crate::error::PositionalInterpError;
+use crate::rpc::{read_message, write_message};
+
+// The kinds of tokens in Bril text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+  Ident,
+  Func,
+  Label,
+  Number,
+  // The quoted path of an import
+  Str,
+  Punct(char),
+}
+
+// A token along with its byte range. The range of a function or label leaves out its `@` or `.`
+struct Lexeme<'a> {
+  token: Token,
+  text: &'a str,
+  start: usize,
+  end: usize,
+}
+
+const fn is_ident_start(b: u8) -> bool {
+  b == b'_' || b == b'%' || b.is_ascii_alphabetic()
+}
+
+const fn is_ident_char(b: u8) -> bool {
+  is_ident_start(b) || b == b'.' || b.is_ascii_digit()
+}
+
+// Splits ```text``` into tokens the same way as the bril2json grammar, skipping whitespace and comments
+fn lex(text: &str) -> Vec<Lexeme<'_>> {
+  let bytes = text.as_bytes();
+  // The end of the run of bytes starting at ```from``` which satisfy ```pred```
+  let run = |from: usize, pred: fn(u8) -> bool| {
+    from + bytes[from..].iter().take_while(|b| pred(**b)).count()
+  };
+  let mut lexemes = Vec::new();
+  let mut i = 0;
+  while i < bytes.len() {
+    let b = bytes[i];
+    let next = bytes.get(i + 1).copied().unwrap_or_default();
+    let (token, start, end) = match b {
+      b'#' => {
+        i = run(i, |b| b != b'\n');
+        continue;
+      }
+      _ if b.is_ascii_whitespace() => {
+        i += 1;
+        continue;
+      }
+      b'"' => (
+        Token::Str,
+        i,
+        text[i + 1..]
+          .find('"')
+          .map_or(text.len(), |len| i + len + 2),
+      ),
+      b'@' if is_ident_start(next) => (Token::Func, i + 1, run(i + 1, is_ident_char)),
+      b'.' if is_ident_start(next) => (Token::Label, i + 1, run(i + 1, is_ident_char)),
+      _ if is_ident_start(b) => (Token::Ident, i, run(i, is_ident_char)),
+      _ if b.is_ascii_digit()
+        || (matches!(b, b'+' | b'-' | b'.') && (next.is_ascii_digit() || next == b'.')) =>
+      {
+        (
+          Token::Number,
+          i,
+          run(i + 1, |b| b.is_ascii_digit() || b == b'.'),
+        )
+      }
+      _ => {
+        let c = text[i..].chars().next().unwrap();
+        (Token::Punct(c), i, i + c.len_utf8())
+      }
+    };
+    lexemes.push(Lexeme {
+      token,
+      text: &text[start..end],
+      start,
+      end,
+    });
+    // Step over the `@` or `.` as well
+    i = end.max(i + 1);
+  }
+  lexemes
+}
+
+fn is_punct(lexemes: &[Lexeme], i: usize, c: char) -> bool {
+  lexemes.get(i).is_some_and(|l| l.token == Token::Punct(c))
+}
+
+// The text of the type starting at ```i```, such as `ptr<int>`, and the index just after it
+fn type_text(lexemes: &[Lexeme], mut i: usize) -> (String, usize) {
+  let mut typ = String::new();
+  while let Some(l) = lexemes.get(i) {
+    match l.token {
+      Token::Ident | Token::Punct('<' | '>') => typ.push_str(l.text),
+      _ => break,
+    }
+    i += 1;
+  }
+  (typ, i)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+  Variable,
+  Label,
+  Function,
+}
+
+// Where a name appears in a document
+struct Occurrence {
+  kind: Kind,
+  name: String,
+  // The function that this is inside of. Variables and labels are only the same symbol within one function
+  func: usize,
+  start: usize,
+  end: usize,
+  definition: bool,
+  // The type written where a variable is assigned or declared as an argument
+  typ: Option<String>,
+}
+
+impl Occurrence {
+  fn is_same_symbol(&self, other: &Self) -> bool {
+    self.kind == other.kind
+      && self.name == other.name
+      && (self.kind == Kind::Function || self.func == other.func)
+  }
+}
+
+// A function definition in a document
+struct Outline {
+  name: String,
+  signature: String,
+  start: usize,
+  end: usize,
+  name_start: usize,
+  name_end: usize,
+}
+
+// The symbols of a document, found from its tokens so that they are still there while the text is being edited and does not parse
+#[derive(Default)]
+struct Analysis {
+  occurrences: Vec<Occurrence>,
+  functions: Vec<Outline>,
+  // The byte range of each instruction
+  statements: Vec<(usize, usize)>,
+  // Mistakes which would stop the program from being built to type check it
+  problems: Vec<(usize, usize, String)>,
+}
+
+impl Analysis {
+  fn new(text: &str) -> Self {
+    let lexemes = lex(text);
+    let mut analysis = Self::default();
+    let mut i = 0;
+    while i < lexemes.len() {
+      if lexemes[i].token == Token::Func {
+        i = analysis.function(&lexemes, i);
+      } else if lexemes[i].text == "from" {
+        // The functions named by an import are defined in another file
+        while i < lexemes.len() && !is_punct(&lexemes, i, ';') {
+          i += 1;
+        }
+      } else {
+        i += 1;
+      }
+    }
+    analysis.find_problems();
+    analysis
+  }
+
+  fn push(&mut self, kind: Kind, func: usize, l: &Lexeme, definition: bool, typ: Option<String>) {
+    self.occurrences.push(Occurrence {
+      kind,
+      name: l.text.to_string(),
+      func,
+      start: l.start,
+      end: l.end,
+      definition,
+      typ,
+    });
+  }
+
+  // Reads the function whose name is at ```i``` and returns the index after it
+  fn function(&mut self, lexemes: &[Lexeme], mut i: usize) -> usize {
+    let func = self.functions.len();
+    let name = &lexemes[i];
+    self.push(Kind::Function, func, name, true, None);
+    let mut signature = format!("@{}", name.text);
+    i += 1;
+
+    if is_punct(lexemes, i, '(') {
+      i += 1;
+      let mut args = Vec::new();
+      while i < lexemes.len() && !is_punct(lexemes, i, ')') && !is_punct(lexemes, i, '{') {
+        if lexemes[i].token == Token::Ident && is_punct(lexemes, i + 1, ':') {
+          let (typ, next) = type_text(lexemes, i + 2);
+          args.push(format!("{}: {typ}", lexemes[i].text));
+          self.push(Kind::Variable, func, &lexemes[i], true, Some(typ));
+          i = next;
+        } else {
+          i += 1;
+        }
+      }
+      if !args.is_empty() {
+        signature = format!("{signature}({})", args.join(", "));
+      }
+      if is_punct(lexemes, i, ')') {
+        i += 1;
+      }
+    }
+    if is_punct(lexemes, i, ':') {
+      let (typ, next) = type_text(lexemes, i + 1);
+      signature = format!("{signature}: {typ}");
+      i = next;
+    }
+
+    let end = if is_punct(lexemes, i, '{') {
+      i += 1;
+      while i < lexemes.len() && !is_punct(lexemes, i, '}') {
+        if lexemes[i].token == Token::Label && is_punct(lexemes, i + 1, ':') {
+          self.push(Kind::Label, func, &lexemes[i], true, None);
+          i += 2;
+          continue;
+        }
+        // The closing brace is missing so this is the start of the next function
+        if lexemes[i].token == Token::Func {
+          break;
+        }
+        let first = i;
+        while i < lexemes.len() && !is_punct(lexemes, i, ';') && !is_punct(lexemes, i, '}') {
+          i += 1;
+        }
+        let statement = &lexemes[first..i];
+        if is_punct(lexemes, i, ';') {
+          i += 1;
+        }
+        self
+          .statements
+          .push((lexemes[first].start, lexemes[i - 1].end));
+        self.statement(func, statement);
+      }
+      if is_punct(lexemes, i, '}') {
+        i += 1;
+      }
+      lexemes[i - 1].end
+    } else {
+      lexemes[i.min(lexemes.len()) - 1].end
+    };
+
+    self.functions.push(Outline {
+      name: name.text.to_string(),
+      signature,
+      start: name.start - 1,
+      end,
+      name_start: name.start,
+      name_end: name.end,
+    });
+    i
+  }
+
+  // Finds the names in an instruction without its `;`
+  fn statement(&mut self, func: usize, statement: &[Lexeme]) {
+    let eq = statement.iter().position(|l| l.token == Token::Punct('='));
+    if let Some(dest) = statement
+      .first()
+      .filter(|l| eq.is_some() && l.token == Token::Ident)
+    {
+      let typ = is_punct(statement, 1, ':').then(|| type_text(statement, 2).0);
+      self.push(Kind::Variable, func, dest, true, typ);
+    }
+    let operands = eq.map_or_else(
+      || statement.get(1..).unwrap_or_default(),
+      |eq| match statement.get(eq + 1) {
+        // The literal of a constant is not a variable even if it is `true` or `false`
+        Some(op) if op.text == "const" => &[],
+        Some(_) => &statement[eq + 2..],
+        None => &[],
+      },
+    );
+    for l in operands {
+      match l.token {
+        Token::Ident => self.push(Kind::Variable, func, l, false, None),
+        Token::Label => self.push(Kind::Label, func, l, false, None),
+        Token::Func => self.push(Kind::Function, func, l, false, None),
+        Token::Number | Token::Str | Token::Punct(_) => {}
+      }
+    }
+  }
+
+  // Building the program panics on labels that don't exist and gives no position for functions that are defined twice, so these are found here instead
+  fn find_problems(&mut self) {
+    for (i, o) in self.occurrences.iter().enumerate() {
+      let defined_before = self.occurrences[..i]
+        .iter()
+        .any(|d| d.definition && d.is_same_symbol(o));
+      let defined = defined_before
+        || self.occurrences[i..]
+          .iter()
+          .any(|d| d.definition && d.is_same_symbol(o));
+      if o.kind == Kind::Label && !o.definition && !defined {
+        self
+          .problems
+          .push((o.start, o.end, format!("undefined label `.{}`", o.name)));
+      }
+      if o.kind == Kind::Function && o.definition && defined_before {
+        self.problems.push((
+          o.start,
+          o.end,
+          format!("function `@{}` is defined more than once", o.name),
+        ));
+      }
+    }
+  }
+
+  fn at(&self, offset: usize) -> Option<&Occurrence> {
+    self
+      .occurrences
+      .iter()
+      .find(|o| o.start <= offset && offset <= o.end)
+  }
+
+  fn occurrences_of<'a>(&'a self, symbol: &'a Occurrence) -> impl Iterator<Item = &'a Occurrence> {
+    self
+      .occurrences
+      .iter()
+      .filter(move |o| o.is_same_symbol(symbol))
+  }
+
+  // What to underline for an error at ```offset```: the instruction or function name that starts there, or else the rest of the line
+  fn span(&self, text: &str, offset: usize) -> (usize, usize) {
+    if let Some((start, end)) = self.statements.iter().find(|(start, _)| *start == offset) {
+      return (*start, *end);
+    }
+    if let Some(f) = self.functions.iter().find(|f| f.start == offset) {
+      return (f.name_start, f.name_end);
+    }
+    (
+      offset,
+      text[offset..].find('\n').map_or(text.len(), |n| offset + n),
+    )
+  }
+}
+
+// Converts byte offsets to and from the positions of the Language Server Protocol, which count lines and UTF-16 code units from 0
+struct Lines<'a> {
+  text: &'a str,
+  starts: Vec<usize>,
+}
+
+impl<'a> Lines<'a> {
+  fn new(text: &'a str) -> Self {
+    let mut starts = vec![0];
+    starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+    Self { text, starts }
+  }
+
+  fn position(&self, offset: usize) -> Value {
+    let line = self.starts.partition_point(|s| *s <= offset) - 1;
+    let character = self.text[self.starts[line]..offset].encode_utf16().count();
+    json!({ "line": line, "character": character })
+  }
+
+  fn range(&self, start: usize, end: usize) -> Value {
+    json!({ "start": self.position(start), "end": self.position(end) })
+  }
+
+  fn offset(&self, position: &Value) -> usize {
+    let line = position["line"].as_u64().unwrap_or_default() as usize;
+    let Some(start) = self.starts.get(line) else {
+      return self.text.len();
+    };
+    let mut character = position["character"].as_u64().unwrap_or_default() as usize;
+    for (i, c) in self.text[*start..].char_indices() {
+      if character == 0 || c == '\n' {
+        return start + i;
+      }
+      character = character.saturating_sub(c.len_utf16());
+    }
+    self.text.len()
+  }
+
+  // Where a Bril position points, which counts lines and bytes from 1
+  fn bril_offset(&self, pos: ColRow) -> usize {
+    let offset = self
+      .starts
+      .get((pos.row as usize).saturating_sub(1))
+      .map_or(self.text.len(), |start| {
+        start + (pos.col as usize).saturating_sub(1)
+      });
+    let mut offset = offset.min(self.text.len());
+    while !self.text.is_char_boundary(offset) {
+      offset -= 1;
+    }
+    offset
+  }
+}
+
+// An open file and what is known about it
+struct Document {
+  text: String,
+  analysis: Analysis,
+  // The errors in the text as byte ranges
+  errors: Vec<(usize, usize, String)>,
+}
+
+impl Document {
+  // ```path``` is the file that the text is from, which the paths of its imports are relative to
+  fn new(text: String, path: Option<PathBuf>) -> Self {
+    let analysis = Analysis::new(&text);
+    let errors = find_errors(&text, path, &analysis);
+    Self {
+      text,
+      analysis,
+      errors,
+    }
+  }
+
+  fn lines(&self) -> Lines<'_> {
+    Lines::new(&self.text)
+  }
+
+  fn diagnostics(&self) -> Value {
+    let lines = self.lines();
+    self
+      .errors
+      .iter()
+      .map(|(start, end, message)| {
+        json!({
+          "range": lines.range(*start, *end),
+          "severity": 1,
+          "source": "brilirs",
+          "message": message,
+        })
+      })
+      .collect()
+  }
+
+  fn symbol_at(&self, params: &Value) -> Option<&Occurrence> {
+    self.analysis.at(self.lines().offset(&params["position"]))
+  }
+
+  fn definition(&self, uri: &Value, params: &Value) -> Value {
+    let lines = self.lines();
+    self.symbol_at(params).map_or(Value::Null, |symbol| {
+      self
+        .analysis
+        .occurrences_of(symbol)
+        .filter(|o| o.definition)
+        .map(|o| json!({ "uri": uri, "range": lines.range(o.start, o.end) }))
+        .collect()
+    })
+  }
+
+  fn references(&self, uri: &Value, params: &Value) -> Value {
+    let lines = self.lines();
+    let declarations = params["context"]["includeDeclaration"]
+      .as_bool()
+      .unwrap_or(true);
+    self.symbol_at(params).map_or(Value::Null, |symbol| {
+      self
+        .analysis
+        .occurrences_of(symbol)
+        .filter(|o| declarations || !o.definition)
+        .map(|o| json!({ "uri": uri, "range": lines.range(o.start, o.end) }))
+        .collect()
+    })
+  }
+
+  fn hover(&self, params: &Value) -> Value {
+    let Some(symbol) = self.symbol_at(params) else {
+      return Value::Null;
+    };
+    let text = match symbol.kind {
+      Kind::Variable => self
+        .analysis
+        .occurrences_of(symbol)
+        .find_map(|o| o.typ.as_ref())
+        .map_or_else(|| symbol.name.clone(), |t| format!("{}: {t}", symbol.name)),
+      Kind::Label => format!(".{}", symbol.name),
+      Kind::Function => self
+        .analysis
+        .functions
+        .iter()
+        .find(|f| f.name == symbol.name)
+        .map_or_else(|| format!("@{}", symbol.name), |f| f.signature.clone()),
+    };
+    json!({
+      "contents": { "kind": "markdown", "value": format!("```bril\n{text}\n```") },
+      "range": self.lines().range(symbol.start, symbol.end),
+    })
+  }
+
+  fn rename(&self, uri: &Value, params: &Value) -> Result<Value, String> {
+    let Some(symbol) = self.symbol_at(params) else {
+      return Ok(Value::Null);
+    };
+    let new_name = params["newName"].as_str().unwrap_or_default();
+    // The `@` or `.` is not part of the name but it may have been typed anyway
+    let sigil = match symbol.kind {
+      Kind::Variable => "",
+      Kind::Label => ".",
+      Kind::Function => "@",
+    };
+    let name = new_name.strip_prefix(sigil).unwrap_or(new_name);
+    if !name.bytes().next().is_some_and(is_ident_start) || !name.bytes().all(is_ident_char) {
+      return Err(format!("`{new_name}` is not a valid name"));
+    }
+    let lines = self.lines();
+    let edits: Vec<Value> = self
+      .analysis
+      .occurrences_of(symbol)
+      .map(|o| json!({ "range": lines.range(o.start, o.end), "newText": name }))
+      .collect();
+    let mut changes = serde_json::Map::new();
+    changes.insert(uri.as_str().unwrap_or_default().to_string(), json!(edits));
+    Ok(json!({ "changes": changes }))
+  }
+
+  fn document_symbols(&self) -> Value {
+    let lines = self.lines();
+    self
+      .analysis
+      .functions
+      .iter()
+      .enumerate()
+      .map(|(i, f)| {
+        let mut children: Vec<Value> = Vec::new();
+        let mut seen: Vec<(Kind, &str)> = Vec::new();
+        for o in &self.analysis.occurrences {
+          if o.func != i || !o.definition || o.kind == Kind::Function {
+            continue;
+          }
+          // Variables can be assigned more than once but are only listed the first time
+          if seen.contains(&(o.kind, o.name.as_str())) {
+            continue;
+          }
+          seen.push((o.kind, &o.name));
+          let (name, kind) = match o.kind {
+            Kind::Label => (format!(".{}", o.name), 20),
+            _ => (o.name.clone(), 13),
+          };
+          let mut child = json!({
+            "name": name,
+            "kind": kind,
+            "range": lines.range(o.start, o.end),
+            "selectionRange": lines.range(o.start, o.end),
+          });
+          if let Some(typ) = &o.typ {
+            child["detail"] = json!(typ);
+          }
+          children.push(child);
+        }
+        json!({
+          "name": format!("@{}", f.name),
+          "detail": f.signature,
+          "kind": 12,
+          "range": lines.range(f.start, f.end),
+          "selectionRange": lines.range(f.name_start, f.name_end),
+          "children": children,
+        })
+      })
+      .collect()
+  }
+}
+
+// Parses and type checks ```text```, giving the errors as byte ranges
+fn find_errors(
+  text: &str,
+  path: Option<PathBuf>,
+  analysis: &Analysis,
+) -> Vec<(usize, usize, String)> {
+  let prog = match bril2json::parse_abstract_program_from_str(text, true) {
+    Ok(prog) => prog,
+    Err(e) => return vec![(e.start, e.end, e.message)],
+  };
+  if !analysis.problems.is_empty() {
+    return analysis.problems.clone();
+  }
+  let lines = Lines::new(text);
+  let error = |pos: Option<&Position>, message: String| {
+    // Mistakes in the files that are imported are shown at the start of this one
+    if let Some(pos) = pos.filter(|pos| pos.src.is_some()) {
+      return vec![(0, 0, format!("{pos}: {message}"))];
+    }
+    let (start, end) = pos.map_or((0, 0), |pos| {
+      let start = lines.bril_offset(pos.pos);
+      match pos.pos_end {
+        // A function is underlined by its name rather than its whole body
+        Some(end) if !analysis.functions.iter().any(|f| f.start == start) => {
+          (start, lines.bril_offset(end))
+        }
+        _ => analysis.span(text, start),
+      }
+    });
+    vec![(start, end, message)]
+  };
+  let mut prog = match link(vec![(path, prog)], |path: &Path| {
+    bril2json::load_abstract_program_from_file(path, true)
+  }) {
+    Ok(prog) => prog,
+    Err(e) => return error(e.pos(), e.error().to_string()),
+  };
+  if let Err(e) = infer_types(&mut prog) {
+    return error(e.pos(), e.error().to_string());
+  }
+  let prog: Program = match prog.try_into() {
+    Ok(prog) => prog,
+    Err(e) => return error(e.pos(), e.error().to_string()),
+  };
+  // Every rule that is broken gets its own diagnostic
+  if let Err(errors) = check_well_formed(&prog) {
+    return errors
+      .errors()
+      .iter()
+      .flat_map(|e| error(e.pos(), e.error().to_string()))
+      .collect();
+  }
+  match BBProgram::new(prog)
+    .map_err(PositionalInterpError::new)
+    .and_then(|bbprog| check::type_check(&bbprog))
+  {
+    Ok(()) => Vec::new(),
+    Err(e) => error(e.pos(), e.error().to_string()),
+  }
+}
+
+fn capabilities() -> Value {
+  json!({
+    "capabilities": {
+      // The whole text is sent on every change
+      "textDocumentSync": 1,
+      "definitionProvider": true,
+      "referencesProvider": true,
+      "hoverProvider": true,
+      "renameProvider": true,
+      "documentSymbolProvider": true,
+      "completionProvider": {},
+    },
+    "serverInfo": { "name": "brilirs" },
+  })
+}
+
+fn completions() -> Value {
+  // `call` is both a value and an effect operation but is only offered once
+  let mut seen = Vec::new();
+  OPS
+    .iter()
+    .filter(|info| {
+      let new = !seen.contains(&info.name);
+      seen.push(info.name);
+      new
+    })
+    .map(|info| json!({ "label": info.name, "kind": 14, "detail": info.signature.to_string() }))
+    .collect()
+}
+
+fn publish(output: &mut impl Write, uri: &Value, diagnostics: Value) -> io::Result<()> {
+  write_message(
+    output,
+    &json!({
+      "jsonrpc": "2.0",
+      "method": "textDocument/publishDiagnostics",
+      "params": { "uri": uri, "diagnostics": diagnostics },
+    }),
+  )
+}
+
+// The JSON-RPC error code for when a method doesn't exist
+const METHOD_NOT_FOUND: i64 = -32601;
+// The JSON-RPC error code for bad parameters
+const INVALID_PARAMS: i64 = -32602;
+
+/// Serves the Language Server Protocol over ```input``` and ```output``` until the client exits or closes the input
+pub fn serve(mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+  let mut documents: FxHashMap<String, Document> = FxHashMap::default();
+  while let Some(msg) = read_message(&mut input)? {
+    let method = msg["method"].as_str().unwrap_or_default();
+    let params = &msg["params"];
+    let uri = &params["textDocument"]["uri"];
+    let key = uri.as_str().unwrap_or_default().to_string();
+    match method {
+      "exit" => return Ok(()),
+      "textDocument/didOpen" | "textDocument/didChange" => {
+        let text = if method == "textDocument/didOpen" {
+          &params["textDocument"]["text"]
+        } else {
+          // Only the whole text is asked for so the last change has all of it
+          params["contentChanges"]
+            .as_array()
+            .and_then(|c| c.last())
+            .map_or(&Value::Null, |c| &c["text"])
+        };
+        let path = key.strip_prefix("file://").map(PathBuf::from);
+        let doc = Document::new(text.as_str().unwrap_or_default().to_string(), path);
+        publish(&mut output, uri, doc.diagnostics())?;
+        documents.insert(key, doc);
+        continue;
+      }
+      "textDocument/didClose" => {
+        documents.remove(&key);
+        publish(&mut output, uri, json!([]))?;
+        continue;
+      }
+      _ => {}
+    }
+    // Anything else without an id is a notification that needs no answer
+    let Some(id) = msg.get("id") else {
+      continue;
+    };
+
+    let doc = documents.get(&key);
+    let result = match (method, doc) {
+      ("initialize", _) => Ok(capabilities()),
+      ("shutdown", _) => Ok(Value::Null),
+      ("textDocument/completion", _) => Ok(completions()),
+      ("textDocument/definition", Some(doc)) => Ok(doc.definition(uri, params)),
+      ("textDocument/references", Some(doc)) => Ok(doc.references(uri, params)),
+      ("textDocument/hover", Some(doc)) => Ok(doc.hover(params)),
+      ("textDocument/rename", Some(doc)) => {
+        doc.rename(uri, params).map_err(|e| (INVALID_PARAMS, e))
+      }
+      ("textDocument/documentSymbol", Some(doc)) => Ok(doc.document_symbols()),
+      (
+        "textDocument/definition"
+        | "textDocument/references"
+        | "textDocument/hover"
+        | "textDocument/rename"
+        | "textDocument/documentSymbol",
+        None,
+      ) => Err((INVALID_PARAMS, format!("`{key}` is not open"))),
+      _ => Err((METHOD_NOT_FOUND, format!("unsupported method `{method}`"))),
+    };
+    let response = match result {
+      Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+      Err((code, message)) => {
+        json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+      }
+    };
+    write_message(&mut output, &response)?;
+  }
+  Ok(())
+}
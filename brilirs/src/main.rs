@@ -1,25 +1,116 @@
-use brilirs::cli::Cli;
+use brilirs::cli::{Cli, Command};
+use brilirs::interp::{Limits, DEFAULT_HISTORY_LEN, DEFAULT_MAX_CALL_DEPTH};
+use brilirs::trace::{TraceOptions, Tracer};
 use clap::Parser;
 use std::fs::File;
+use std::io::{Read, Write};
+use std::path::PathBuf;
 
 fn main() {
   let args = Cli::parse();
 
-  let input: Box<dyn std::io::Read> = match args.file {
-    None => Box::new(std::io::stdin()),
-
-    Some(input_file) => Box::new(File::open(input_file).unwrap()),
+  let input: brilirs::Inputs = if args.file.is_empty() {
+    vec![(None, Box::new(std::io::stdin()))]
+  } else {
+    args
+      .file
+      .iter()
+      .map(|input_file| {
+        let file: Box<dyn Read> = Box::new(File::open(input_file).unwrap());
+        (Some(PathBuf::from(input_file)), file)
+      })
+      .collect()
   };
 
+  match args.command {
+    Some(Command::Debug {
+      history,
+      args: debug_args,
+    }) => {
+      // The debugger reads its commands from stdin so the program can't come from there too
+      if args.file.is_empty() {
+        eprintln!("error: the program to debug must be given with --file");
+        std::process::exit(2)
+      }
+      if let Err(e) = brilirs::debug_input(
+        input,
+        std::io::stdin().lock(),
+        debug_args,
+        args.text,
+        history.unwrap_or(DEFAULT_HISTORY_LEN),
+      ) {
+        eprintln!("error: {e}");
+        std::process::exit(brilirs::exit_code(e.as_ref()))
+      }
+      return;
+    }
+    Some(Command::Dap) => {
+      if let Err(e) = brilirs::serve_dap(std::io::stdin().lock(), std::io::stdout().lock()) {
+        eprintln!("error: {e}");
+        std::process::exit(2)
+      }
+      return;
+    }
+    Some(Command::Repl) => {
+      if let Err(e) = brilirs::repl_input(
+        (!args.file.is_empty()).then_some(input),
+        std::io::stdin().lock(),
+        args.text,
+      ) {
+        eprintln!("error: {e}");
+        std::process::exit(2)
+      }
+      return;
+    }
+    Some(Command::Lsp) => {
+      if let Err(e) = brilirs::serve_lsp(std::io::stdin().lock(), std::io::stdout().lock()) {
+        eprintln!("error: {e}");
+        std::process::exit(2)
+      }
+      return;
+    }
+    None => {}
+  }
+
+  let trace = args.trace.then(|| {
+    let out: Box<dyn Write> = match &args.trace_file {
+      None => Box::new(std::io::stderr()),
+      Some(trace_file) => Box::new(File::create(trace_file).unwrap()),
+    };
+    Tracer::new(
+      out,
+      TraceOptions {
+        format: args.trace_format.parse().unwrap(),
+        functions: args.trace_func,
+        start: args.trace_start,
+        end: args.trace_end,
+      },
+    )
+  });
+
   if let Err(e) = brilirs::run_input(
     input,
     std::io::stdout(),
     args.args,
     args.profile,
     args.check,
+    args.ssa,
     args.text,
+    &Limits {
+      max_instructions: args.max_instructions,
+      max_call_depth: args.max_call_depth.unwrap_or(DEFAULT_MAX_CALL_DEPTH),
+      max_heap_cells: args.max_heap,
+      max_output_bytes: args.max_output,
+    },
+    args.profile_file.map(|f| {
+      (
+        args.profile_format.parse().unwrap(),
+        Box::new(File::create(f).unwrap()) as Box<dyn Write>,
+      )
+    }),
+    trace,
   ) {
     eprintln!("error: {e}");
-    std::process::exit(2)
+    std::process::exit(brilirs::exit_code(e.as_ref()))
   }
 }
@@ -0,0 +1,169 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use fxhash::FxHashMap;
+use serde_json::json;
+
+use crate::basic_block::BBProgram;
+
+/// The ways that a [`Profile`] can be written out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileFormat {
+  /// Counts per function, basic block, opcode and source line as a JSON object
+  Json,
+  /// One line per call stack like `main;fib;fib 42`, as used by flamegraph tools
+  Collapsed,
+}
+
+impl FromStr for ProfileFormat {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "json" => Ok(Self::Json),
+      "collapsed" => Ok(Self::Collapsed),
+      _ => Err(format!("unknown profile format `{s}`")),
+    }
+  }
+}
+
+/// Dynamic instruction counts from running a program with [`crate::interp::execute_main`]
+///
+/// Only the number of times each basic block is entered and the call stack it was entered with are recorded while the program runs. Counts per function, opcode and line are worked out from these when the profile is written. Like ```total_dyn_inst```, every instruction of a block is counted when the block is entered.
+#[derive(Debug, Default)]
+pub struct Profile<'a> {
+  // The number of times each block, by function and index, was entered
+  blocks: FxHashMap<(&'a str, usize), u64>,
+  // Each call stack is the stack of its caller with one more function on top
+  stacks: Vec<(Option<usize>, &'a str)>,
+  stack_ids: FxHashMap<(Option<usize>, &'a str), usize>,
+  // The number of instructions run with each call stack
+  stack_counts: Vec<u64>,
+}
+
+impl<'a> Profile<'a> {
+  /// Creates an empty [`Profile`]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // The id of the call stack made by calling ```func``` from ```caller```
+  pub(crate) fn enter_function(&mut self, caller: Option<usize>, func: &'a str) -> usize {
+    *self.stack_ids.entry((caller, func)).or_insert_with(|| {
+      self.stacks.push((caller, func));
+      self.stack_counts.push(0);
+      self.stacks.len() - 1
+    })
+  }
+
+  // The stack that the function on top of ```stack``` was called from
+  pub(crate) fn caller(&self, stack: usize) -> Option<usize> {
+    self.stacks[stack].0
+  }
+
+  pub(crate) fn enter_block(&mut self, stack: usize, block: usize, num_instrs: usize) {
+    let func = self.stacks[stack].1;
+    *self.blocks.entry((func, block)).or_default() += 1;
+    self.stack_counts[stack] += num_instrs as u64;
+  }
+
+  /// The total number of instructions counted, which is the same as ```total_dyn_inst```
+  pub fn total(&self) -> u64 {
+    self.stack_counts.iter().sum()
+  }
+
+  /// Writes the profile to ```out``` in ```format```. ```prog``` must be the program that was profiled.
+  pub fn write<W: std::io::Write>(
+    &self,
+    prog: &BBProgram,
+    format: ProfileFormat,
+    mut out: W,
+  ) -> std::io::Result<()> {
+    match format {
+      ProfileFormat::Json => {
+        serde_json::to_writer_pretty(&mut out, &self.to_json(prog))?;
+        writeln!(out)
+      }
+      ProfileFormat::Collapsed => {
+        for line in self.collapsed() {
+          writeln!(out, "{line}")?;
+        }
+        Ok(())
+      }
+    }
+  }
+
+  fn to_json(&self, prog: &BBProgram) -> serde_json::Value {
+    let mut functions: BTreeMap<&str, u64> = BTreeMap::new();
+    let mut opcodes: BTreeMap<String, u64> = BTreeMap::new();
+    // Programs linked together from several files have lines with the same number in each of them
+    let mut lines: BTreeMap<(Option<&str>, u64), u64> = BTreeMap::new();
+
+    let mut blocks: Vec<(&(&str, usize), &u64)> = self.blocks.iter().collect();
+    blocks.sort_unstable();
+    let blocks: Vec<serde_json::Value> = blocks
+      .into_iter()
+      .filter_map(|((func, idx), entries)| {
+        let block = &prog.get(func)?.blocks[*idx];
+        let dyn_inst = entries * block.instrs.len() as u64;
+        *functions.entry(func).or_default() += dyn_inst;
+        for instr in &block.instrs {
+          let op = match instr {
+            bril_rs::Instruction::Constant { op, .. } => op.to_string(),
+            bril_rs::Instruction::Value { op, .. } => op.to_string(),
+            bril_rs::Instruction::Effect { op, .. } => op.to_string(),
+          };
+          *opcodes.entry(op).or_default() += entries;
+          if let Some(pos) = instr.get_pos() {
+            *lines.entry((pos.src.as_deref(), pos.pos.row)).or_default() += entries;
+          }
+        }
+        Some(json!({
+          "function": func,
+          "index": idx,
+          "label": block.label,
+          "entries": entries,
+          "dyn_inst": dyn_inst,
+        }))
+      })
+      .collect();
+
+    json!({
+      "total_dyn_inst": self.total(),
+      "functions": functions,
+      "blocks": blocks,
+      "opcodes": opcodes,
+      "lines": lines
+        .into_iter()
+        .map(|((file, line), dyn_inst)| {
+          let mut entry = json!({ "line": line, "dyn_inst": dyn_inst });
+          if let Some(file) = file {
+            entry["file"] = json!(file);
+          }
+          entry
+        })
+        .collect::<Vec<_>>(),
+    })
+  }
+
+  fn collapsed(&self) -> Vec<String> {
+    let mut lines: Vec<String> = self
+      .stack_counts
+      .iter()
+      .enumerate()
+      .filter(|(_, count)| **count > 0)
+      .map(|(stack, count)| {
+        let mut funcs = Vec::new();
+        let mut curr = Some(stack);
+        while let Some(s) = curr {
+          funcs.push(self.stacks[s].1);
+          curr = self.stacks[s].0;
+        }
+        funcs.reverse();
+        format!("{} {count}", funcs.join(";"))
+      })
+      .collect();
+    lines.sort_unstable();
+    lines
+  }
+}
@@ -0,0 +1,202 @@
+use std::error::Error;
+use std::io::{BufRead, Write};
+
+use bril_rs::{Code, EffectOps, Function, Instruction, Program};
+
+use crate::basic_block::BBProgram;
+use crate::check;
+use crate::interp::{execute_last, Globals};
+
+const HELP: &str = "\
+Type an instruction to run it straight away, or a function to define it.
+A function can go over more than one line and ends once its braces match.
+:vars           show every variable that has been assigned
+:funcs          list the functions that have been defined
+:dump [json]    show the program so far as text, or as JSON
+:help           show this message
+:quit           leave the REPL";
+
+/// A program built up a piece at a time. The instructions typed at the top level make up "main", which is run one instruction at a time as they come in while its variables and the heap are kept in between.
+pub struct Repl {
+  functions: Vec<Function>,
+  main: Function,
+  // The program as of the last change that type checked
+  bbprog: BBProgram,
+  globals: Globals,
+}
+
+impl Repl {
+  pub fn new() -> Self {
+    let main = Function {
+      name: "main".to_string(),
+      args: Vec::new(),
+      return_type: None,
+      instrs: Vec::new(),
+      pos: None,
+    };
+    Self {
+      bbprog: BBProgram::new(Program {
+        functions: vec![main.clone()],
+        imports: Vec::new(),
+      })
+      .unwrap(),
+      functions: Vec::new(),
+      main,
+      globals: Globals::new(),
+    }
+  }
+
+  /// Everything typed in so far
+  pub fn program(&self) -> Program {
+    let mut functions = vec![self.main.clone()];
+    functions.extend(self.functions.iter().cloned());
+    Program {
+      functions,
+      imports: Vec::new(),
+    }
+  }
+
+  // Type checks the program as it is now so that it can be run
+  fn rebuild(&mut self) -> Result<(), Box<dyn Error>> {
+    let prog = self.program();
+    let bbprog = BBProgram::new(prog)?;
+    check::type_check(&bbprog)?;
+    self.bbprog = bbprog;
+    Ok(())
+  }
+
+  /// Adds ```funcs``` to the program, replacing any functions that have the same name. Nothing is changed if the program no longer type checks.
+  pub fn define(&mut self, funcs: Vec<Function>) -> Result<(), Box<dyn Error>> {
+    if funcs.iter().any(|f| f.name == "main") {
+      return Err("`@main` is made up of the instructions typed at the top level".into());
+    }
+    let old = self.functions.clone();
+    for func in funcs {
+      match self.functions.iter_mut().find(|f| f.name == func.name) {
+        Some(f) => *f = func,
+        None => self.functions.push(func),
+      }
+    }
+    if let Err(e) = self.rebuild() {
+      self.functions = old;
+      return Err(e);
+    }
+    Ok(())
+  }
+
+  /// Adds ```code``` to the end of "main" and runs it. It is taken back out if it does not type check or fails while running, though anything it did before failing is kept.
+  pub fn run(&mut self, code: Code, out: &mut impl Write) -> Result<(), Box<dyn Error>> {
+    match &code {
+      Code::Label { .. }
+      | Code::Instruction(Instruction::Effect {
+        op: EffectOps::Jump | EffectOps::Branch | EffectOps::Return,
+        ..
+      }) => return Err("labels and control flow can only be used inside of a function".into()),
+      Code::Instruction(_) => {}
+    }
+    self.main.instrs.push(code);
+    if let Err(e) = self.rebuild() {
+      self.main.instrs.pop();
+      return Err(e);
+    }
+    if let Err(e) = execute_last(&self.bbprog, &mut *out, &mut self.globals) {
+      self.main.instrs.pop();
+      // The failed instruction can't be left in the program that will be run next
+      self.rebuild()?;
+      return Err(e.into());
+    }
+    Ok(())
+  }
+
+  /// The variables of "main" which have been assigned, sorted by name
+  pub fn variables(&self) -> Vec<(&str, String)> {
+    self.globals.variables(self.bbprog.get("main").unwrap())
+  }
+
+  /// The functions which have been defined, in the order they were first defined
+  pub fn functions(&self) -> &[Function] {
+    &self.functions
+  }
+
+  // Reads one function or instruction from the text of ```input```
+  fn handle_input(&mut self, input: &str, out: &mut impl Write) -> Result<(), Box<dyn Error>> {
+    if input.trim_start().starts_with('@') {
+      let func = bril2json::parse_abstract_function(input, false)?;
+      self.define(vec![func.try_into()?])
+    } else {
+      let code = bril2json::parse_abstract_code(input, false)?;
+      self.run(code.try_into()?, out)
+    }
+  }
+}
+
+// Whether ```input``` has opened more braces than it has closed, in which case the function goes on to the next line
+fn is_unfinished(input: &str) -> bool {
+  input.matches('{').count() > input.matches('}').count()
+}
+
+/// Reads Bril text and commands from ```input``` until it runs out or `:quit` is typed. Prompts, errors and what the program prints all go to ```out```.
+pub fn run_repl(mut repl: Repl, input: impl BufRead, out: &mut impl Write) -> std::io::Result<()> {
+  let mut lines = input.lines();
+  let mut pending = String::new();
+  loop {
+    write!(
+      out,
+      "{}",
+      if pending.is_empty() {
+        "bril> "
+      } else {
+        "...   "
+      }
+    )?;
+    out.flush()?;
+    let Some(line) = lines.next() else {
+      writeln!(out)?;
+      return Ok(());
+    };
+    pending.push_str(&line?);
+    pending.push('\n');
+    if is_unfinished(&pending) {
+      continue;
+    }
+    let input = std::mem::take(&mut pending);
+
+    let mut words = input.split_whitespace();
+    let result = match (words.next(), words.next()) {
+      (None, _) => Ok(()),
+      (Some(":help" | ":h"), _) => writeln!(out, "{HELP}").map_err(Into::into),
+      (Some(":quit" | ":q"), _) => return Ok(()),
+      (Some(":vars"), _) => repl
+        .variables()
+        .iter()
+        .try_for_each(|(name, value)| writeln!(out, "{name} = {value}"))
+        .map_err(Into::into),
+      (Some(":funcs"), _) => repl
+        .functions()
+        .iter()
+        .try_for_each(|f| {
+          let args: Vec<String> = f.args.iter().map(ToString::to_string).collect();
+          write!(out, "@{}", f.name)?;
+          if !args.is_empty() {
+            write!(out, "({})", args.join(", "))?;
+          }
+          match &f.return_type {
+            Some(t) => writeln!(out, ": {t}"),
+            None => writeln!(out),
+          }
+        })
+        .map_err(Into::into),
+      (Some(":dump"), None | Some("text")) => write!(out, "{}", repl.program()).map_err(Into::into),
+      (Some(":dump"), Some("json")) => serde_json::to_string_pretty(&repl.program())
+        .map_err(Into::into)
+        .and_then(|json| writeln!(out, "{json}").map_err(Into::into)),
+      (Some(command), _) if command.starts_with(':') => {
+        Err(format!("unknown command `{}`, see :help", input.trim()).into())
+      }
+      _ => repl.handle_input(&input, out),
+    };
+    if let Err(e) = result {
+      writeln!(out, "error: {e}")?;
+    }
+  }
+}
@@ -0,0 +1,33 @@
+use std::io::{self, BufRead, Write};
+
+use serde_json::Value;
+
+/// The next message from ```input``` with the `Content-Length` header framing used by both the Debug Adapter Protocol and the Language Server Protocol, or None once the input has been closed
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+  let mut len = None;
+  loop {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+      return Ok(None);
+    }
+    let line = line.trim_end();
+    if line.is_empty() && len.is_some() {
+      break;
+    }
+    if let Some(n) = line.strip_prefix("Content-Length:") {
+      len = n.trim().parse::<usize>().ok();
+    }
+  }
+  let mut buf = vec![0; len.unwrap_or_default()];
+  input.read_exact(&mut buf)?;
+  serde_json::from_slice(&buf)
+    .map(Some)
+    .map_err(io::Error::from)
+}
+
+/// Writes ```msg``` to ```output``` with the framing that [`read_message`] expects
+pub fn write_message(output: &mut impl Write, msg: &Value) -> io::Result<()> {
+  let body = msg.to_string();
+  write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+  output.flush()
+}
@@ -0,0 +1,117 @@
+use std::io::Write;
+use std::str::FromStr;
+
+use bril_rs::Instruction;
+use serde_json::json;
+
+use crate::error::InterpError;
+use crate::interp::Value;
+
+/// The ways that a [`Tracer`] can write out each instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+  /// One line per instruction like `3 @main.loop: i: int = add i one; => 2 (line 5, column 3)`
+  Text,
+  /// One JSON object per line, which is convenient for diffing the traces of two versions of a program
+  Jsonl,
+}
+
+impl FromStr for TraceFormat {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "text" => Ok(Self::Text),
+      "jsonl" => Ok(Self::Jsonl),
+      _ => Err(format!("unknown trace format `{s}`")),
+    }
+  }
+}
+
+/// Which instructions a [`Tracer`] writes out and how
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceOptions {
+  /// How each instruction is written
+  pub format: TraceFormat,
+  /// Only instructions in these functions are written. Every function is traced when this is empty.
+  pub functions: Vec<String>,
+  /// Instructions are numbered from 0 in the order they are run. Those numbered before this are not written.
+  pub start: u64,
+  /// Instructions numbered from this on are not written
+  pub end: Option<u64>,
+}
+
+impl Default for TraceOptions {
+  fn default() -> Self {
+    Self {
+      format: TraceFormat::Text,
+      functions: Vec::new(),
+      start: 0,
+      end: None,
+    }
+  }
+}
+
+/// Writes a record of each instruction run by [`crate::interp::execute_main`] with the function and block it is in, its source position, and the value it assigned. For `ret`, the value is the one returned. Calls have no value because they assign theirs once the function returns.
+pub struct Tracer {
+  out: Box<dyn Write>,
+  options: TraceOptions,
+  // The number of the next instruction to be run
+  step: u64,
+}
+
+impl Tracer {
+  /// Creates a [`Tracer`] that writes to ```out```
+  pub fn new(out: Box<dyn Write>, options: TraceOptions) -> Self {
+    Self {
+      out,
+      options,
+      step: 0,
+    }
+  }
+
+  pub(crate) fn record(
+    &mut self,
+    func: &str,
+    label: Option<&String>,
+    instr: &Instruction,
+    value: Option<&Value>,
+  ) -> Result<(), InterpError> {
+    let step = self.step;
+    self.step += 1;
+    if step < self.options.start
+      || self.options.end.is_some_and(|end| step >= end)
+      || !(self.options.functions.is_empty() || self.options.functions.iter().any(|f| f == func))
+    {
+      return Ok(());
+    }
+
+    let pos = instr.get_pos();
+    let line = match self.options.format {
+      TraceFormat::Text => {
+        let mut line = format!("{step} @{func}");
+        if let Some(label) = label {
+          line.push_str(&format!(".{label}"));
+        }
+        line.push_str(&format!(": {instr}"));
+        if let Some(value) = value {
+          line.push_str(&format!(" => {value}"));
+        }
+        if let Some(pos) = pos {
+          line.push_str(&format!(" (line {}, column {})", pos.pos.row, pos.pos.col));
+        }
+        line
+      }
+      TraceFormat::Jsonl => json!({
+        "step": step,
+        "function": func,
+        "label": label,
+        "instr": instr.to_string(),
+        "pos": pos.map(|p| json!({ "row": p.pos.row, "col": p.pos.col })),
+        "value": value.map(Value::to_json),
+      })
+      .to_string(),
+    };
+    writeln!(self.out, "{line}").map_err(|e| InterpError::IoError(Box::new(e)))
+  }
+}
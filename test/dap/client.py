@@ -0,0 +1,68 @@
+"""A scripted Debug Adapter Protocol client for testing `brilirs dap`.
+
+Usage: python3 client.py REQUESTS COMMAND...
+
+Starts the adapter with COMMAND and sends it each request in the file
+REQUESTS, which holds one JSON object per line with a `command` and
+optional `arguments`. Every message the adapter sends back is printed
+as a line of JSON.
+"""
+
+import json
+import subprocess
+import sys
+
+
+def send(proc, seq, request):
+    request = dict(request, seq=seq, type='request')
+    body = json.dumps(request).encode()
+    proc.stdin.write(b'Content-Length: %d\r\n\r\n' % len(body))
+    proc.stdin.write(body)
+    proc.stdin.flush()
+
+
+def recv(proc):
+    """Read one message, or return None once the adapter has exited."""
+    length = None
+    while True:
+        line = proc.stdout.readline()
+        if not line:
+            return None
+        line = line.strip()
+        if not line and length is not None:
+            break
+        if line.startswith(b'Content-Length:'):
+            length = int(line.split(b':')[1])
+    return json.loads(proc.stdout.read(length))
+
+
+def show(msg):
+    print(json.dumps(msg, sort_keys=True))
+
+
+def run(requests, command):
+    proc = subprocess.Popen(command, stdin=subprocess.PIPE,
+                            stdout=subprocess.PIPE)
+    for seq, request in enumerate(requests, 1):
+        send(proc, seq, request)
+        # Wait for the response, showing any events on the way.
+        while True:
+            msg = recv(proc)
+            if msg is None:
+                return proc.wait()
+            show(msg)
+            if msg['type'] == 'response' and msg['request_seq'] == seq:
+                break
+
+    proc.stdin.close()
+    while True:
+        msg = recv(proc)
+        if msg is None:
+            return proc.wait()
+        show(msg)
+
+
+if __name__ == '__main__':
+    with open(sys.argv[1]) as f:
+        requests = [json.loads(line) for line in f if line.strip()]
+    sys.exit(run(requests, sys.argv[2:]))
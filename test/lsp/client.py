@@ -0,0 +1,82 @@
+"""A scripted Language Server Protocol client for testing `brilirs lsp`.
+
+Usage: python3 client.py MESSAGES COMMAND...
+
+Starts the server with COMMAND and sends it each message in the file
+MESSAGES, which holds one JSON object per line with a `method` and
+optional `params`. Messages with an `id` are requests and the client
+waits for their response before going on, while the rest are
+notifications. A `textDocument/didOpen` without any `text` is sent the
+contents of the file named by its `uri`. Every message the server sends
+back is printed as a line of JSON.
+"""
+
+import json
+import subprocess
+import sys
+
+
+def send(proc, msg):
+    body = json.dumps(dict(msg, jsonrpc='2.0')).encode()
+    proc.stdin.write(b'Content-Length: %d\r\n\r\n' % len(body))
+    proc.stdin.write(body)
+    proc.stdin.flush()
+
+
+def recv(proc):
+    """Read one message, or return None once the server has exited."""
+    length = None
+    while True:
+        line = proc.stdout.readline()
+        if not line:
+            return None
+        line = line.strip()
+        if not line and length is not None:
+            break
+        if line.startswith(b'Content-Length:'):
+            length = int(line.split(b':')[1])
+    return json.loads(proc.stdout.read(length))
+
+
+def show(msg):
+    print(json.dumps(msg, sort_keys=True))
+
+
+def fill_text(msg):
+    if msg.get('method') != 'textDocument/didOpen':
+        return msg
+    doc = msg['params']['textDocument']
+    if 'text' not in doc:
+        with open(doc['uri']) as f:
+            doc['text'] = f.read()
+    return msg
+
+
+def run(messages, command):
+    proc = subprocess.Popen(command, stdin=subprocess.PIPE,
+                            stdout=subprocess.PIPE)
+    for msg in messages:
+        send(proc, fill_text(msg))
+        if 'id' not in msg:
+            continue
+        # Wait for the response, showing any notifications on the way.
+        while True:
+            reply = recv(proc)
+            if reply is None:
+                return proc.wait()
+            show(reply)
+            if reply.get('id') == msg['id'] and 'method' not in reply:
+                break
+
+    proc.stdin.close()
+    while True:
+        reply = recv(proc)
+        if reply is None:
+            return proc.wait()
+        show(reply)
+
+
+if __name__ == '__main__':
+    with open(sys.argv[1]) as f:
+        messages = [json.loads(line) for line in f if line.strip()]
+    sys.exit(run(messages, sys.argv[2:]))